@@ -1,10 +1,10 @@
 use cosmwasm_std::{
     entry_point, to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
-    Addr, Order,
+    Addr, Order, Storage,
 };
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, ConfigResponse, ModelResponse, ModelsResponse, ValidatorResponse, ValidatorsResponse, ValidationResponse, ModelValidationsResponse, ValidatorStatsResponse, BridgeResponse, BridgesResponse, CrossRuntimeStateResponse};
-use crate::state::{Config, AIModel, AIValidator, ValidationRecord, ValidationResult, CrossRuntimeBridge, BridgeType, ModelType, CONFIG, MODELS, VALIDATORS, VALIDATIONS, MODEL_COUNT, VALIDATOR_COUNT, CROSS_BRIDGES};
+use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, ConfigResponse, ModelResponse, ModelsResponse, ValidatorResponse, ValidatorsResponse, ValidationResponse, ModelValidationsResponse, ValidatorStatsResponse, BridgeResponse, BridgesResponse, CrossRuntimeStateResponse, NetworkStatsResponse};
+use crate::state::{Config, AIModel, AIValidator, ValidationRecord, ValidationResult, CrossRuntimeBridge, BridgeType, ModelType, CONFIG, MODELS, PAUSED, OWNER_MODEL_COUNT, LAST_VALIDATION, VALIDATORS, VALIDATIONS, MODEL_COUNT, VALIDATOR_COUNT, CROSS_BRIDGES};
 
 #[entry_point]
 pub fn instantiate(
@@ -18,6 +18,8 @@ pub fn instantiate(
         neo_token: deps.api.addr_validate(&msg.neo_token)?,
         min_stake_for_registration: msg.min_stake_for_registration,
         ai_validator_threshold: msg.ai_validator_threshold,
+        max_models_per_owner: msg.max_models_per_owner,
+        validation_cooldown: msg.validation_cooldown,
     };
     
     CONFIG.save(deps.storage, &config)?;
@@ -37,6 +39,12 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    if matches!(msg, ExecuteMsg::RegisterModel { .. } | ExecuteMsg::ValidateModel { .. } | ExecuteMsg::RegisterValidator { .. })
+        && PAUSED.may_load(deps.storage)?.unwrap_or(false)
+    {
+        return Err(ContractError::Paused {});
+    }
+
     match msg {
         ExecuteMsg::RegisterModel { model_id, name, description, ipfs_hash, model_type, quantum_signature } => {
             execute_register_model(deps, env, info, model_id, name, description, ipfs_hash, model_type, quantum_signature)
@@ -47,6 +55,9 @@ pub fn execute(
         ExecuteMsg::DeactivateModel { model_id } => {
             execute_deactivate_model(deps, info, model_id)
         },
+        ExecuteMsg::ReactivateModel { model_id } => {
+            execute_reactivate_model(deps, env, info, model_id)
+        },
         ExecuteMsg::RegisterValidator { neo_address, quantum_key_hash } => {
             execute_register_validator(deps, env, info, neo_address, quantum_key_hash)
         },
@@ -65,12 +76,16 @@ pub fn execute(
         ExecuteMsg::CrossRuntimeCall { bridge_id, method, params } => {
             execute_cross_runtime_call(deps, env, info, bridge_id, method, params)
         },
-        ExecuteMsg::UpdateConfig { min_stake_for_registration, ai_validator_threshold } => {
-            execute_update_config(deps, info, min_stake_for_registration, ai_validator_threshold)
+        ExecuteMsg::UpdateConfig { min_stake_for_registration, ai_validator_threshold, max_models_per_owner, validation_cooldown } => {
+            execute_update_config(deps, info, min_stake_for_registration, ai_validator_threshold, max_models_per_owner, validation_cooldown)
+        },
+        ExecuteMsg::SetPaused { paused } => {
+            execute_set_paused(deps, info, paused)
         },
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn execute_register_model(
     deps: DepsMut,
     env: Env,
@@ -86,6 +101,8 @@ fn execute_register_model(
         return Err(ContractError::ModelAlreadyExists { model_id });
     }
     
+    take_model_slot(deps.storage, &info.sender)?;
+    
     let model = AIModel {
         model_id: model_id.clone(),
         owner: info.sender.clone(),
@@ -164,6 +181,10 @@ fn execute_deactivate_model(
         return Err(ContractError::Unauthorized {});
     }
     
+    // an inactive model no longer counts against its owner's cap
+    if model.is_active {
+        release_model_slot(deps.storage, &model.owner)?;
+    }
     model.is_active = false;
     MODELS.save(deps.storage, &model_id, &model)?;
     
@@ -172,6 +193,51 @@ fn execute_deactivate_model(
         .add_attribute("model_id", model_id))
 }
 
+fn execute_reactivate_model(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    model_id: String,
+) -> Result<Response, ContractError> {
+    let mut model = MODELS.load(deps.storage, &model_id)
+        .map_err(|_| ContractError::ModelNotFound { model_id: model_id.clone() })?;
+    
+    let config = CONFIG.load(deps.storage)?;
+    if model.owner != info.sender && config.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    
+    if !model.is_active {
+        take_model_slot(deps.storage, &model.owner)?;
+    }
+    model.is_active = true;
+    model.updated_at = env.block.time.seconds();
+    MODELS.save(deps.storage, &model_id, &model)?;
+    
+    Ok(Response::new()
+        .add_attribute("method", "reactivate_model")
+        .add_attribute("model_id", model_id))
+}
+
+fn take_model_slot(storage: &mut dyn Storage, owner: &Addr) -> Result<(), ContractError> {
+    let config = CONFIG.load(storage)?;
+    let count = OWNER_MODEL_COUNT.may_load(storage, owner)?.unwrap_or(0);
+    if count >= config.max_models_per_owner {
+        return Err(ContractError::ModelLimitReached {
+            owner: owner.to_string(),
+            max: config.max_models_per_owner,
+        });
+    }
+    OWNER_MODEL_COUNT.save(storage, owner, &(count + 1))?;
+    Ok(())
+}
+
+fn release_model_slot(storage: &mut dyn Storage, owner: &Addr) -> Result<(), ContractError> {
+    let count = OWNER_MODEL_COUNT.may_load(storage, owner)?.unwrap_or(0);
+    OWNER_MODEL_COUNT.save(storage, owner, &count.saturating_sub(1))?;
+    Ok(())
+}
+
 fn execute_register_validator(
     deps: DepsMut,
     env: Env,
@@ -225,6 +291,20 @@ fn execute_validate_model(
     let mut model = MODELS.load(deps.storage, &model_id)
         .map_err(|_| ContractError::ModelNotFound { model_id: model_id.clone() })?;
     
+    let now = env.block.time.seconds();
+    let cooldown = CONFIG.load(deps.storage)?.validation_cooldown;
+    if let Some(last) = LAST_VALIDATION.may_load(deps.storage, (&info.sender, &model_id))? {
+        let available_at = last.saturating_add(cooldown);
+        if now < available_at {
+            return Err(ContractError::ValidationCooldown {
+                validator: info.sender.to_string(),
+                model_id,
+                available_at,
+            });
+        }
+    }
+    LAST_VALIDATION.save(deps.storage, (&info.sender, &model_id), &now)?;
+    
     let validation_id = format!("{}_{}", model_id, env.block.time.seconds());
     
     let is_success = matches!(result, ValidationResult::Approved);
@@ -356,6 +436,8 @@ fn execute_update_config(
     info: MessageInfo,
     min_stake_for_registration: Option<u128>,
     ai_validator_threshold: Option<u32>,
+    max_models_per_owner: Option<u64>,
+    validation_cooldown: Option<u64>,
 ) -> Result<Response, ContractError> {
     let mut config = CONFIG.load(deps.storage)?;
     
@@ -369,6 +451,12 @@ fn execute_update_config(
     if let Some(threshold) = ai_validator_threshold {
         config.ai_validator_threshold = threshold;
     }
+    if let Some(max) = max_models_per_owner {
+        config.max_models_per_owner = max;
+    }
+    if let Some(cooldown) = validation_cooldown {
+        config.validation_cooldown = cooldown;
+    }
     
     CONFIG.save(deps.storage, &config)?;
     
@@ -376,6 +464,23 @@ fn execute_update_config(
         .add_attribute("method", "update_config"))
 }
 
+fn execute_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    
+    PAUSED.save(deps.storage, &paused)?;
+    
+    Ok(Response::new()
+        .add_attribute("method", "set_paused")
+        .add_attribute("paused", paused.to_string()))
+}
+
 #[entry_point]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
@@ -390,6 +495,7 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::Bridge { bridge_id } => to_json_binary(&query_bridge(deps, bridge_id)?),
         QueryMsg::Bridges {} => to_json_binary(&query_bridges(deps)?),
         QueryMsg::CrossRuntimeState { bridge_id, key } => to_json_binary(&query_cross_runtime_state(deps, bridge_id, key)?),
+        QueryMsg::NetworkStats {} => to_json_binary(&query_network_stats(deps)?),
     }
 }
 
@@ -403,6 +509,9 @@ fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
         neo_token: config.neo_token,
         min_stake_for_registration: config.min_stake_for_registration,
         ai_validator_threshold: config.ai_validator_threshold,
+        max_models_per_owner: config.max_models_per_owner,
+        validation_cooldown: config.validation_cooldown,
+        paused: PAUSED.may_load(deps.storage)?.unwrap_or(false),
         total_models: model_count,
         total_validators: validator_count,
     })
@@ -418,7 +527,7 @@ fn query_models(deps: Deps, start_after: Option<String>, limit: Option<u32>) ->
     let start = start_after.as_deref();
     
     let models: Vec<AIModel> = MODELS
-        .range(deps.storage, start.map(cosmwasm_std::Bound::exclusive), None, Order::Ascending)
+        .range(deps.storage, start.map(cw_storage_plus::Bound::exclusive), None, Order::Ascending)
         .take(limit)
         .map(|r| r.map(|(_, m)| m))
         .collect::<StdResult<_>>()?;
@@ -434,9 +543,10 @@ fn query_validator(deps: Deps, address: String) -> StdResult<ValidatorResponse>
 
 fn query_validators(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<ValidatorsResponse> {
     let limit = limit.unwrap_or(30).min(100) as usize;
+    let start = start_after.map(|a| deps.api.addr_validate(&a)).transpose()?;
     
     let validators: Vec<AIValidator> = VALIDATORS
-        .range(deps.storage, None, None, Order::Ascending)
+        .range(deps.storage, start.as_ref().map(cw_storage_plus::Bound::exclusive), None, Order::Ascending)
         .take(limit)
         .map(|r| r.map(|(_, v)| v))
         .collect::<StdResult<_>>()?;
@@ -489,6 +599,53 @@ fn query_validator_stats(deps: Deps, address: String) -> StdResult<ValidatorStat
     })
 }
 
+// Walks every model, validator and validation record, so its gas cost grows
+// with the registry; large registries should page through `Models` and
+// `Validators` instead.
+fn query_network_stats(deps: Deps) -> StdResult<NetworkStatsResponse> {
+    let mut total_models = 0;
+    let mut active_models = 0;
+    for model in MODELS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, model) = model?;
+        total_models += 1;
+        if model.is_active {
+            active_models += 1;
+        }
+    }
+    
+    let mut total_validators = 0;
+    let mut active_validators = 0;
+    for validator in VALIDATORS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, validator) = validator?;
+        total_validators += 1;
+        if validator.is_active {
+            active_validators += 1;
+        }
+    }
+    
+    let mut total_validations = 0u64;
+    let mut accuracy_sum = 0u128;
+    for validation in VALIDATIONS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, validation) = validation?;
+        total_validations += 1;
+        accuracy_sum += validation.accuracy_score as u128;
+    }
+    let average_accuracy = if total_validations == 0 {
+        0
+    } else {
+        (accuracy_sum / total_validations as u128) as u64
+    };
+    
+    Ok(NetworkStatsResponse {
+        total_models,
+        active_models,
+        total_validators,
+        active_validators,
+        total_validations,
+        average_accuracy,
+    })
+}
+
 fn query_bridge(deps: Deps, bridge_id: String) -> StdResult<BridgeResponse> {
     let bridge = CROSS_BRIDGES.load(deps.storage, &bridge_id)?;
     Ok(BridgeResponse {
@@ -525,3 +682,228 @@ fn query_cross_runtime_state(deps: Deps, bridge_id: String, key: String) -> StdR
         synced: true,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    fn setup(deps: DepsMut) {
+        let msg = InstantiateMsg {
+            neo_token: "neotoken".to_string(),
+            min_stake_for_registration: 0,
+            ai_validator_threshold: 1,
+            max_models_per_owner: 2,
+            validation_cooldown: 3600,
+        };
+        instantiate(deps, mock_env(), mock_info("admin", &[]), msg).unwrap();
+    }
+
+    fn register(deps: DepsMut, owner: &str, model_id: &str) {
+        try_register(deps, owner, model_id).unwrap();
+    }
+
+    fn try_register(deps: DepsMut, owner: &str, model_id: &str) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::RegisterModel {
+            model_id: model_id.to_string(),
+            name: "model".to_string(),
+            description: "desc".to_string(),
+            ipfs_hash: "QmHash".to_string(),
+            model_type: ModelType::FraudDetection,
+            quantum_signature: None,
+        };
+        execute(deps, mock_env(), mock_info(owner, &[]), msg)
+    }
+
+    fn model(deps: Deps, model_id: &str) -> AIModel {
+        let res = query(deps, mock_env(), QueryMsg::Model { model_id: model_id.to_string() }).unwrap();
+        from_json::<ModelResponse>(&res).unwrap().model
+    }
+
+    #[test]
+    fn owner_can_deactivate_and_reactivate() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        register(deps.as_mut(), "alice", "m1");
+
+        let deactivate = ExecuteMsg::DeactivateModel { model_id: "m1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), deactivate).unwrap();
+        assert!(!model(deps.as_ref(), "m1").is_active);
+
+        let mut later = mock_env();
+        later.block.time = later.block.time.plus_seconds(60);
+        let reactivate = ExecuteMsg::ReactivateModel { model_id: "m1".to_string() };
+        execute(deps.as_mut(), later.clone(), mock_info("alice", &[]), reactivate).unwrap();
+
+        let m = model(deps.as_ref(), "m1");
+        assert!(m.is_active);
+        assert_eq!(m.updated_at, later.block.time.seconds());
+    }
+
+    #[test]
+    fn unrelated_address_cannot_reactivate() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        register(deps.as_mut(), "alice", "m1");
+
+        let deactivate = ExecuteMsg::DeactivateModel { model_id: "m1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), deactivate).unwrap();
+
+        let reactivate = ExecuteMsg::ReactivateModel { model_id: "m1".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("mallory", &[]), reactivate).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        assert!(!model(deps.as_ref(), "m1").is_active);
+    }
+
+    #[test]
+    fn registration_is_capped_per_owner() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        register(deps.as_mut(), "alice", "m1");
+        register(deps.as_mut(), "alice", "m2");
+
+        let err = try_register(deps.as_mut(), "alice", "m3").unwrap_err();
+        assert!(matches!(err, ContractError::ModelLimitReached { max: 2, .. }));
+        assert_eq!(err.to_string(), "Model limit reached: alice already has 2 active models");
+        // the cap is per owner
+        register(deps.as_mut(), "bob", "m3");
+    }
+
+    #[test]
+    fn deactivation_frees_a_slot() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        register(deps.as_mut(), "alice", "m1");
+        register(deps.as_mut(), "alice", "m2");
+
+        let deactivate = ExecuteMsg::DeactivateModel { model_id: "m1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), deactivate).unwrap();
+        register(deps.as_mut(), "alice", "m3");
+
+        // reactivating would exceed the cap again
+        let reactivate = ExecuteMsg::ReactivateModel { model_id: "m1".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), reactivate).unwrap_err();
+        assert!(matches!(err, ContractError::ModelLimitReached { .. }));
+    }
+
+    fn validate(deps: DepsMut, env: Env, validator: &str, model_id: &str) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::ValidateModel {
+            model_id: model_id.to_string(),
+            result: ValidationResult::Approved,
+            accuracy_score: 90,
+        };
+        execute(deps, env, mock_info(validator, &[]), msg)
+    }
+
+    #[test]
+    fn validation_cooldown_is_per_validator_and_model() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        register(deps.as_mut(), "alice", "m1");
+        register(deps.as_mut(), "alice", "m2");
+        let register_validator = ExecuteMsg::RegisterValidator { neo_address: "neo1val".to_string(), quantum_key_hash: None };
+        execute(deps.as_mut(), mock_env(), mock_info("val", &[]), register_validator).unwrap();
+
+        let start = mock_env();
+        validate(deps.as_mut(), start.clone(), "val", "m1").unwrap();
+
+        let mut inside = start.clone();
+        inside.block.time = start.block.time.plus_seconds(3599);
+        let err = validate(deps.as_mut(), inside.clone(), "val", "m1").unwrap_err();
+        assert!(matches!(err, ContractError::ValidationCooldown { available_at, .. } if available_at == start.block.time.seconds() + 3600));
+        assert_eq!(model(deps.as_ref(), "m1").total_validations, 1);
+        // a different model is not affected
+        validate(deps.as_mut(), inside, "val", "m2").unwrap();
+
+        let mut after = start.clone();
+        after.block.time = start.block.time.plus_seconds(3600);
+        validate(deps.as_mut(), after, "val", "m1").unwrap();
+        assert_eq!(model(deps.as_ref(), "m1").total_validations, 2);
+    }
+
+    fn set_paused(deps: DepsMut, sender: &str, paused: bool) -> Result<Response, ContractError> {
+        execute(deps, mock_env(), mock_info(sender, &[]), ExecuteMsg::SetPaused { paused })
+    }
+
+    #[test]
+    fn pause_blocks_mutations_until_unpaused() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        register(deps.as_mut(), "alice", "m1");
+        let register_validator = ExecuteMsg::RegisterValidator { neo_address: "neo1val".to_string(), quantum_key_hash: None };
+        execute(deps.as_mut(), mock_env(), mock_info("val", &[]), register_validator.clone()).unwrap();
+
+        let err = set_paused(deps.as_mut(), "mallory", true).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        set_paused(deps.as_mut(), "admin", true).unwrap();
+
+        let err = try_register(deps.as_mut(), "alice", "m2").unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        let err = validate(deps.as_mut(), mock_env(), "val", "m1").unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("val2", &[]), register_validator).unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+
+        // queries and deactivation still work while paused
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        assert!(from_json::<ConfigResponse>(&res).unwrap().paused);
+        assert_eq!(model(deps.as_ref(), "m1").total_validations, 0);
+        let deactivate = ExecuteMsg::DeactivateModel { model_id: "m1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), deactivate).unwrap();
+
+        set_paused(deps.as_mut(), "admin", false).unwrap();
+        register(deps.as_mut(), "alice", "m2");
+        validate(deps.as_mut(), mock_env(), "val", "m2").unwrap();
+        assert_eq!(model(deps.as_ref(), "m2").total_validations, 1);
+    }
+
+    #[test]
+    fn network_stats_summarize_the_registry() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let stats = |deps: Deps| -> NetworkStatsResponse {
+            from_json(&query(deps, mock_env(), QueryMsg::NetworkStats {}).unwrap()).unwrap()
+        };
+        assert_eq!(stats(deps.as_ref()), NetworkStatsResponse {
+            total_models: 0,
+            active_models: 0,
+            total_validators: 0,
+            active_validators: 0,
+            total_validations: 0,
+            average_accuracy: 0,
+        });
+
+        register(deps.as_mut(), "alice", "m1");
+        register(deps.as_mut(), "alice", "m2");
+        register(deps.as_mut(), "bob", "m3");
+        let deactivate = ExecuteMsg::DeactivateModel { model_id: "m2".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), deactivate).unwrap();
+        for validator in ["val1", "val2"] {
+            let msg = ExecuteMsg::RegisterValidator { neo_address: format!("neo1{}", validator), quantum_key_hash: None };
+            execute(deps.as_mut(), mock_env(), mock_info(validator, &[]), msg).unwrap();
+        }
+
+        // validation ids are keyed by model and block time, so space them out
+        let runs = [("val1", "m1", 90), ("val2", "m3", 70), ("val1", "m3", 80)];
+        for (i, (validator, model_id, accuracy_score)) in runs.into_iter().enumerate() {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(i as u64);
+            let msg = ExecuteMsg::ValidateModel {
+                model_id: model_id.to_string(),
+                result: ValidationResult::Approved,
+                accuracy_score,
+            };
+            execute(deps.as_mut(), env, mock_info(validator, &[]), msg).unwrap();
+        }
+
+        assert_eq!(stats(deps.as_ref()), NetworkStatsResponse {
+            total_models: 3,
+            active_models: 2,
+            total_validators: 2,
+            active_validators: 2,
+            total_validations: 3,
+            average_accuracy: 80,
+        });
+    }
+}
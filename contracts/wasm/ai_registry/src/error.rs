@@ -27,6 +27,15 @@ pub enum ContractError {
     #[error("Invalid quantum signature")]
     InvalidQuantumSignature {},
 
+    #[error("Model limit reached: {owner} already has {max} active models")]
+    ModelLimitReached { owner: String, max: u64 },
+
+    #[error("Validation cooldown: {validator} cannot validate {model_id} again until {available_at}")]
+    ValidationCooldown { validator: String, model_id: String, available_at: u64 },
+
+    #[error("Contract is paused")]
+    Paused {},
+
     #[error("Model version conflict")]
     VersionConflict {},
 }
@@ -7,6 +7,8 @@ pub struct InstantiateMsg {
     pub neo_token: String,
     pub min_stake_for_registration: u128,
     pub ai_validator_threshold: u32,
+    pub max_models_per_owner: u64,
+    pub validation_cooldown: u64,
 }
 
 #[cw_serde]
@@ -28,6 +30,9 @@ pub enum ExecuteMsg {
     DeactivateModel {
         model_id: String,
     },
+    ReactivateModel {
+        model_id: String,
+    },
     RegisterValidator {
         neo_address: String,
         quantum_key_hash: Option<String>,
@@ -52,6 +57,11 @@ pub enum ExecuteMsg {
     UpdateConfig {
         min_stake_for_registration: Option<u128>,
         ai_validator_threshold: Option<u32>,
+        max_models_per_owner: Option<u64>,
+        validation_cooldown: Option<u64>,
+    },
+    SetPaused {
+        paused: bool,
     },
 }
 
@@ -90,6 +100,9 @@ pub enum QueryMsg {
     
     #[returns(CrossRuntimeStateResponse)]
     CrossRuntimeState { bridge_id: String, key: String },
+    
+    #[returns(NetworkStatsResponse)]
+    NetworkStats {},
 }
 
 #[cw_serde]
@@ -98,6 +111,9 @@ pub struct ConfigResponse {
     pub neo_token: Addr,
     pub min_stake_for_registration: u128,
     pub ai_validator_threshold: u32,
+    pub max_models_per_owner: u64,
+    pub validation_cooldown: u64,
+    pub paused: bool,
     pub total_models: u64,
     pub total_validators: u64,
 }
@@ -166,3 +182,14 @@ pub struct CrossRuntimeStateResponse {
     pub wasm_value: Option<Vec<u8>>,
     pub synced: bool,
 }
+
+#[cw_serde]
+pub struct NetworkStatsResponse {
+    pub total_models: u64,
+    pub active_models: u64,
+    pub total_validators: u64,
+    pub active_validators: u64,
+    pub total_validations: u64,
+    // mean `accuracy_score` over every validation record, 0 when there are none
+    pub average_accuracy: u64,
+}
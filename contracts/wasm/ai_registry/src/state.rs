@@ -8,6 +8,9 @@ pub struct Config {
     pub neo_token: Addr,
     pub min_stake_for_registration: u128,
     pub ai_validator_threshold: u32,
+    pub max_models_per_owner: u64,
+    // seconds a validator must wait before validating the same model again
+    pub validation_cooldown: u64,
 }
 
 #[cw_serde]
@@ -94,4 +97,10 @@ pub const VALIDATORS: Map<&Addr, AIValidator> = Map::new("validators");
 pub const VALIDATIONS: Map<&str, ValidationRecord> = Map::new("validations");
 pub const MODEL_COUNT: Item<u64> = Item::new("model_count");
 pub const VALIDATOR_COUNT: Item<u64> = Item::new("validator_count");
+// active models per owner, bounded by `Config::max_models_per_owner`
+pub const OWNER_MODEL_COUNT: Map<&Addr, u64> = Map::new("owner_model_count");
+// (validator, model) -> block time of that validator's latest validation of the model
+pub const LAST_VALIDATION: Map<(&Addr, &str), u64> = Map::new("last_validation");
+// owner's circuit breaker; while set, registrations and validations are refused
+pub const PAUSED: Item<bool> = Item::new("paused");
 pub const CROSS_BRIDGES: Map<&str, CrossRuntimeBridge> = Map::new("bridges");
@@ -1,5 +1,9 @@
 // Governance Contract для NeoNet WASM - DualGov (AI + DAO)
+use cosmwasm_std::{Api, Uint128};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::stake::StakingContract;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Proposal {
@@ -13,8 +17,64 @@ pub struct Proposal {
     pub for_votes: u128,
     pub against_votes: u128,
     pub ai_score: f64,
+    // total voting power in `source` at the start time; the quorum denominator
+    pub snapshot_power: u128,
     pub executed: bool,
     pub passed: bool,
+    #[serde(default)]
+    pub cancelled: bool,
+    // time the succeeded proposal was queued for execution
+    #[serde(default)]
+    pub queued_at: Option<u64>,
+    // parameter change applied when the proposal executes and has passed
+    #[serde(default)]
+    pub action: ProposalAction,
+    // vote cap in force at creation; later changes don't reach open proposals
+    #[serde(default)]
+    pub max_voting_power: Option<VotingPowerCap>,
+}
+
+/// On-chain effect of a passed proposal on the governance parameters.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ProposalAction {
+    #[default]
+    Noop,
+    SetQuorum(u8),
+    SetWeights { ai: u8, dao: u8 },
+}
+
+/// Most weight one vote can carry; anything a voter holds above it is ignored.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VotingPowerCap {
+    Absolute(u128),
+    // share of the proposal's snapshot power
+    Percent(u8),
+}
+
+impl VotingPowerCap {
+    fn limit(&self, snapshot_power: u128) -> u128 {
+        match *self {
+            VotingPowerCap::Absolute(max) => max,
+            VotingPowerCap::Percent(percent) => share(snapshot_power, percent),
+        }
+    }
+}
+
+/// `percent` (at most 100) of `power`, rounded down; the product can't overflow.
+fn share(power: u128, percent: u8) -> u128 {
+    Uint128::new(power).multiply_ratio(percent, 100u128).u128()
+}
+
+/// Lifecycle stage derived from a proposal's fields and the current time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProposalStatus {
+    Pending,
+    Active,
+    Defeated,
+    Succeeded,
+    Queued,
+    Executed,
+    Cancelled,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -25,13 +85,62 @@ pub struct Vote {
     pub weight: u128,
 }
 
+/// A vote signed off-chain by the voter and submitted by a relayer.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SignedVote {
+    pub voter: String,
+    pub proposal_id: u64,
+    pub support: bool,
+    // Ed25519 signature over `message()` by the voter's registered key
+    pub signature: Vec<u8>,
+}
+
+impl SignedVote {
+    /// Bytes the voter signs, bound to `domain` so a vote for one deployment
+    /// can't be replayed on another. The domain is length-prefixed and the
+    /// voter comes last, so the encoding is unambiguous.
+    pub fn message(&self, domain: &str) -> Vec<u8> {
+        format!(
+            "neonet-gov-vote:{}:{}:{}:{}:{}",
+            domain.len(), domain, self.proposal_id, self.support, self.voter
+        ).into_bytes()
+    }
+}
+
+/// Where a voter's weight comes from, evaluated at a proposal's snapshot time.
+pub trait VotingPowerSource {
+    fn voting_power(&self, voter: &str, snapshot_time: u64) -> u128;
+
+    /// Power of every voter together; a proposal's quorum denominator.
+    fn total_power(&self, snapshot_time: u64) -> u128;
+}
+
+// only own stake votes; delegated stake neither counts for the delegator nor
+// the validator it was delegated to
+impl VotingPowerSource for StakingContract {
+    fn voting_power(&self, voter: &str, snapshot_time: u64) -> u128 {
+        self.staked_at(voter, snapshot_time)
+    }
+
+    fn total_power(&self, snapshot_time: u64) -> u128 {
+        self.total_staked_at(snapshot_time)
+    }
+}
+
 pub struct GovernanceContract {
     pub proposals: Vec<Proposal>,
     pub votes: Vec<Vote>,
+    // voter -> Ed25519 public key that signs their off-chain votes
+    pub vote_keys: HashMap<String, Vec<u8>>,
+    // chain id and contract address signed votes are bound to
+    pub vote_domain: String,
     pub next_proposal_id: u64,
     pub quorum_percentage: u8,
     pub ai_weight: u8,
     pub dao_weight: u8,
+    // voting power a proposer needs at the proposal's start time
+    pub proposal_threshold: u128,
+    pub max_voting_power: Option<VotingPowerCap>,
 }
 
 impl GovernanceContract {
@@ -39,13 +148,31 @@ impl GovernanceContract {
         GovernanceContract {
             proposals: Vec::new(),
             votes: Vec::new(),
+            vote_keys: HashMap::new(),
+            vote_domain: "neonet".to_string(),
             next_proposal_id: 1,
             quorum_percentage: 10,
             ai_weight: 30,
             dao_weight: 70,
+            proposal_threshold: 0,
+            max_voting_power: None,
         }
     }
 
+    /// Contract deployed as `contract_address` on `chain_id`; signed votes
+    /// only count on the deployment they name.
+    pub fn with_vote_domain(chain_id: &str, contract_address: &str) -> Self {
+        GovernanceContract {
+            vote_domain: format!("{}/{}", chain_id, contract_address),
+            ..Self::new()
+        }
+    }
+
+    /// Open a proposal. The proposer needs `proposal_threshold` power from
+    /// `source` at `start_time`, the snapshot its votes are counted at and
+    /// its quorum is taken from. `action` is checked now so a passed proposal
+    /// can always be applied.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_proposal(
         &mut self,
         proposer: String,
@@ -54,7 +181,30 @@ impl GovernanceContract {
         ipfs_hash: String,
         start_time: u64,
         duration: u64,
+        source: &dyn VotingPowerSource,
+        action: ProposalAction,
     ) -> Result<u64, String> {
+        let eligible_power = source.total_power(start_time);
+        if eligible_power == 0 {
+            return Err("Eligible voting power must be positive".to_string());
+        }
+        match action {
+            ProposalAction::SetQuorum(quorum) if quorum > 100 => {
+                return Err("Quorum must be at most 100".to_string());
+            }
+            ProposalAction::SetWeights { ai, dao } if ai as u16 + dao as u16 != 100 => {
+                return Err("AI weight + DAO weight must equal 100".to_string());
+            }
+            _ => {}
+        }
+        let power = source.voting_power(&proposer, start_time);
+        if power < self.proposal_threshold {
+            return Err(format!(
+                "Proposer has {} voting power, below the proposal threshold of {}",
+                power, self.proposal_threshold
+            ));
+        }
+
         let proposal = Proposal {
             id: self.next_proposal_id,
             proposer,
@@ -66,8 +216,13 @@ impl GovernanceContract {
             for_votes: 0,
             against_votes: 0,
             ai_score: 0.0,
+            snapshot_power: eligible_power,
             executed: false,
             passed: false,
+            cancelled: false,
+            queued_at: None,
+            action,
+            max_voting_power: self.max_voting_power,
         };
 
         self.proposals.push(proposal);
@@ -76,7 +231,91 @@ impl GovernanceContract {
         Ok(id)
     }
 
+    /// Vote with the voter's power at the proposal's start time.
     pub fn vote(
+        &mut self,
+        voter: String,
+        proposal_id: u64,
+        support: bool,
+        source: &dyn VotingPowerSource,
+        current_time: u64,
+    ) -> Result<String, String> {
+        let snapshot_time = self.get_proposal(proposal_id)
+            .ok_or_else(|| "Proposal not found".to_string())?
+            .start_time;
+        let weight = source.voting_power(&voter, snapshot_time);
+        if weight == 0 {
+            return Err("No voting power at snapshot".to_string());
+        }
+        self.record_vote(voter, proposal_id, support, weight, current_time)
+    }
+
+    /// Register the key that signs `voter`'s relayed votes. Only the voter
+    /// may, and only once: a key can't be replaced by whoever calls next.
+    pub fn register_vote_key(&mut self, caller: &str, voter: String, public_key: Vec<u8>) -> Result<(), String> {
+        if caller != voter {
+            return Err("Only the voter can register a vote key".to_string());
+        }
+        if public_key.len() != 32 {
+            return Err("Invalid public key length".to_string());
+        }
+        if self.vote_keys.contains_key(&voter) {
+            return Err("Vote key already registered".to_string());
+        }
+        self.vote_keys.insert(voter, public_key);
+        Ok(())
+    }
+
+    /// Record a relayed batch of signed votes. The batch is all-or-nothing:
+    /// a bad signature, missing power or duplicate vote rejects every vote in it.
+    pub fn submit_signed_votes(
+        &mut self,
+        votes: Vec<SignedVote>,
+        api: &dyn Api,
+        source: &dyn VotingPowerSource,
+        current_time: u64,
+    ) -> Result<Vec<String>, String> {
+        let (proposals, recorded) = (self.proposals.clone(), self.votes.clone());
+        let result = votes.into_iter()
+            .enumerate()
+            .map(|(i, vote)| self.submit_signed_vote(vote, api, source, current_time).map_err(|e| format!("vote {}: {}", i, e)))
+            .collect::<Result<Vec<_>, _>>();
+        if result.is_err() {
+            self.proposals = proposals;
+            self.votes = recorded;
+        }
+        result
+    }
+
+    fn submit_signed_vote(
+        &mut self,
+        vote: SignedVote,
+        api: &dyn Api,
+        source: &dyn VotingPowerSource,
+        current_time: u64,
+    ) -> Result<String, String> {
+        let key = self.vote_keys.get(&vote.voter)
+            .ok_or_else(|| "No registered vote key".to_string())?;
+        if !api.ed25519_verify(&vote.message(&self.vote_domain), &vote.signature, key).unwrap_or(false) {
+            return Err("Invalid vote signature".to_string());
+        }
+        self.vote(vote.voter, vote.proposal_id, vote.support, source, current_time)
+    }
+
+    /// Vote with an explicit weight, bypassing any power source.
+    #[cfg(test)]
+    pub fn vote_with_weight(
+        &mut self,
+        voter: String,
+        proposal_id: u64,
+        support: bool,
+        weight: u128,
+        current_time: u64,
+    ) -> Result<String, String> {
+        self.record_vote(voter, proposal_id, support, weight, current_time)
+    }
+
+    fn record_vote(
         &mut self,
         voter: String,
         proposal_id: u64,
@@ -88,6 +327,10 @@ impl GovernanceContract {
             .find(|p| p.id == proposal_id)
             .ok_or_else(|| "Proposal not found".to_string())?;
 
+        if proposal.cancelled {
+            return Err("Proposal cancelled".to_string());
+        }
+
         if current_time < proposal.start_time {
             return Err("Voting not started yet".to_string());
         }
@@ -100,6 +343,11 @@ impl GovernanceContract {
             return Err("Already voted".to_string());
         }
 
+        let weight = match proposal.max_voting_power {
+            Some(cap) => weight.min(cap.limit(proposal.snapshot_power)),
+            None => weight,
+        };
+
         if support {
             proposal.for_votes += weight;
         } else {
@@ -128,7 +376,6 @@ impl GovernanceContract {
     pub fn execute_proposal(
         &mut self,
         proposal_id: u64,
-        total_supply: u128,
         current_time: u64,
     ) -> Result<bool, String> {
         let proposal = self.proposals.iter_mut()
@@ -143,8 +390,31 @@ impl GovernanceContract {
             return Err("Proposal already executed".to_string());
         }
 
+        if proposal.cancelled {
+            return Err("Proposal cancelled".to_string());
+        }
+
+        let passed = Self::tally(proposal, self.quorum_percentage, self.ai_weight, self.dao_weight)?;
+        proposal.passed = passed;
+        proposal.executed = true;
+
+        if passed {
+            match proposal.action {
+                ProposalAction::Noop => {}
+                ProposalAction::SetQuorum(quorum) => self.quorum_percentage = quorum,
+                ProposalAction::SetWeights { ai, dao } => {
+                    self.ai_weight = ai;
+                    self.dao_weight = dao;
+                }
+            }
+        }
+        Ok(passed)
+    }
+
+    /// Hybrid AI + DAO outcome once voting has closed; errors if quorum is missed.
+    fn tally(proposal: &Proposal, quorum_percentage: u8, ai_weight: u8, dao_weight: u8) -> Result<bool, String> {
         let total_votes = proposal.for_votes + proposal.against_votes;
-        let quorum = (total_supply * self.quorum_percentage as u128) / 100;
+        let quorum = share(proposal.snapshot_power, quorum_percentage);
 
         if total_votes < quorum {
             return Err("Quorum not reached".to_string());
@@ -156,12 +426,59 @@ impl GovernanceContract {
             0.0
         };
 
-        let hybrid_score = (dao_score * self.dao_weight as f64 + proposal.ai_score * self.ai_weight as f64) / 100.0;
+        let hybrid_score = (dao_score * dao_weight as f64 + proposal.ai_score * ai_weight as f64) / 100.0;
+        Ok(hybrid_score > 0.5)
+    }
 
-        proposal.passed = hybrid_score > 0.5;
-        proposal.executed = true;
+    /// Queue a succeeded proposal for execution.
+    pub fn queue_proposal(&mut self, proposal_id: u64, current_time: u64) -> Result<(), String> {
+        if self.status(proposal_id, current_time)? != ProposalStatus::Succeeded {
+            return Err("Only succeeded proposals can be queued".to_string());
+        }
+        let proposal = self.proposals.iter_mut().find(|p| p.id == proposal_id).unwrap();
+        proposal.queued_at = Some(current_time);
+        Ok(())
+    }
+
+    /// Withdraw a proposal; only its proposer may, and only before execution.
+    pub fn cancel_proposal(&mut self, proposal_id: u64, caller: &str) -> Result<(), String> {
+        let proposal = self.proposals.iter_mut()
+            .find(|p| p.id == proposal_id)
+            .ok_or_else(|| "Proposal not found".to_string())?;
+
+        if proposal.proposer != caller {
+            return Err("Only the proposer can cancel".to_string());
+        }
+
+        if proposal.executed {
+            return Err("Proposal already executed".to_string());
+        }
+
+        proposal.cancelled = true;
+        Ok(())
+    }
 
-        Ok(proposal.passed)
+    pub fn status(&self, proposal_id: u64, current_time: u64) -> Result<ProposalStatus, String> {
+        let proposal = self.get_proposal(proposal_id)
+            .ok_or_else(|| "Proposal not found".to_string())?;
+
+        let status = if proposal.cancelled {
+            ProposalStatus::Cancelled
+        } else if proposal.executed {
+            if proposal.passed { ProposalStatus::Executed } else { ProposalStatus::Defeated }
+        } else if current_time < proposal.start_time {
+            ProposalStatus::Pending
+        } else if current_time <= proposal.end_time {
+            ProposalStatus::Active
+        } else if proposal.queued_at.is_some() {
+            ProposalStatus::Queued
+        } else {
+            match Self::tally(proposal, self.quorum_percentage, self.ai_weight, self.dao_weight) {
+                Ok(true) => ProposalStatus::Succeeded,
+                _ => ProposalStatus::Defeated,
+            }
+        };
+        Ok(status)
     }
 
     pub fn get_proposal(&self, proposal_id: u64) -> Option<&Proposal> {
@@ -172,7 +489,10 @@ impl GovernanceContract {
         &self.proposals
     }
 
-    pub fn set_governance_params(&mut self, quorum: u8, ai_weight: u8, dao_weight: u8) -> Result<(), String> {
+    pub fn set_governance_params(&mut self, quorum: u8, ai_weight: u8, dao_weight: u8, proposal_threshold: u128) -> Result<(), String> {
+        if quorum > 100 {
+            return Err("Quorum must be at most 100".to_string());
+        }
         if ai_weight + dao_weight != 100 {
             return Err("AI weight + DAO weight must equal 100".to_string());
         }
@@ -180,6 +500,23 @@ impl GovernanceContract {
         self.quorum_percentage = quorum;
         self.ai_weight = ai_weight;
         self.dao_weight = dao_weight;
+        self.proposal_threshold = proposal_threshold;
+        Ok(())
+    }
+
+    /// Cap the weight any single vote counts with on proposals created from
+    /// now on; `None` removes the cap.
+    pub fn set_max_voting_power(&mut self, cap: Option<VotingPowerCap>) -> Result<(), String> {
+        match cap {
+            Some(VotingPowerCap::Absolute(0)) | Some(VotingPowerCap::Percent(0)) => {
+                return Err("Voting power cap must be positive".to_string());
+            }
+            Some(VotingPowerCap::Percent(percent)) if percent > 100 => {
+                return Err("Voting power cap must be at most 100 percent".to_string());
+            }
+            _ => {}
+        }
+        self.max_voting_power = cap;
         Ok(())
     }
 }
@@ -194,6 +531,21 @@ impl Default for GovernanceContract {
 mod tests {
     use super::*;
 
+    // nobody holds power of their own; `total` is the proposal's eligible power
+    struct Electorate {
+        total: u128,
+    }
+
+    impl VotingPowerSource for Electorate {
+        fn voting_power(&self, _voter: &str, _snapshot_time: u64) -> u128 {
+            0
+        }
+
+        fn total_power(&self, _snapshot_time: u64) -> u128 {
+            self.total
+        }
+    }
+
     #[test]
     fn test_create_proposal() {
         let mut contract = GovernanceContract::new();
@@ -204,6 +556,8 @@ mod tests {
             "QmHash".to_string(),
             0,
             86400,
+            &Electorate { total: 100000 },
+            ProposalAction::Noop,
         );
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 1);
@@ -219,9 +573,11 @@ mod tests {
             "Hash".to_string(),
             0,
             86400,
+            &Electorate { total: 100000 },
+            ProposalAction::Noop,
         ).unwrap();
 
-        let vote_result = contract.vote("bob".to_string(), 1, true, 1000, 100);
+        let vote_result = contract.vote_with_weight("bob".to_string(), 1, true, 1000, 100);
         assert!(vote_result.is_ok());
 
         let proposal = contract.get_proposal(1).unwrap();
@@ -238,15 +594,399 @@ mod tests {
             "Hash".to_string(),
             0,
             100,
+            &Electorate { total: 100000 },
+            ProposalAction::Noop,
         ).unwrap();
 
-        contract.vote("bob".to_string(), 1, true, 7000, 10).unwrap();
-        contract.vote("charlie".to_string(), 1, false, 3000, 10).unwrap();
+        contract.vote_with_weight("bob".to_string(), 1, true, 7000, 10).unwrap();
+        contract.vote_with_weight("charlie".to_string(), 1, false, 3000, 10).unwrap();
 
         contract.set_ai_score(1, 0.8).unwrap();
 
-        let result = contract.execute_proposal(1, 100000, 200);
+        let result = contract.execute_proposal(1, 200);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), true);
+        assert!(result.unwrap());
+    }
+
+    #[test]
+    fn test_quorum_uses_creation_snapshot() {
+        let mut contract = GovernanceContract::new();
+        let new_proposal = |c: &mut GovernanceContract, power: u128| {
+            c.create_proposal(
+                "alice".to_string(),
+                "Test".to_string(),
+                "Desc".to_string(),
+                "Hash".to_string(),
+                0,
+                100,
+                &Electorate { total: power },
+                ProposalAction::Noop,
+            ).unwrap()
+        };
+        // 10% quorum of 100_000 is 10_000
+        let large = new_proposal(&mut contract, 100000);
+        let small = new_proposal(&mut contract, 50000);
+        assert_eq!(contract.get_proposal(large).unwrap().snapshot_power, 100000);
+
+        contract.vote_with_weight("bob".to_string(), large, true, 6000, 10).unwrap();
+        contract.vote_with_weight("bob".to_string(), small, true, 6000, 10).unwrap();
+
+        // same votes, different creation-time denominators
+        assert_eq!(contract.execute_proposal(large, 200), Err("Quorum not reached".to_string()));
+        assert_eq!(contract.execute_proposal(small, 200), Ok(true));
+    }
+
+    #[test]
+    fn test_proposal_requires_eligible_power() {
+        let mut contract = GovernanceContract::new();
+        let result = contract.create_proposal(
+            "alice".to_string(),
+            "Test".to_string(),
+            "Desc".to_string(),
+            "Hash".to_string(),
+            0,
+            100,
+            &StakingContract::new(),
+            ProposalAction::Noop,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_eligible_power_is_total_stake_at_start() {
+        let mut staking = StakingContract::new();
+        staking.stake("bob".to_string(), 100, 0).unwrap();
+        staking.stake("carol".to_string(), 50, 5).unwrap();
+        staking.unstake("bob".to_string(), 20, 8).unwrap();
+        // after the snapshot
+        staking.stake("dave".to_string(), 1000, 20).unwrap();
+        assert_eq!(staking.total_power(10), 130);
+
+        let mut contract = GovernanceContract::new();
+        let id = contract.create_proposal(
+            "bob".to_string(),
+            "Test".to_string(),
+            "Desc".to_string(),
+            "Hash".to_string(),
+            10,
+            100,
+            &staking,
+            ProposalAction::Noop,
+        ).unwrap();
+        assert_eq!(contract.get_proposal(id).unwrap().snapshot_power, 130);
+    }
+
+    #[test]
+    fn test_delegations_do_not_vote() {
+        let mut staking = StakingContract::new();
+        staking.stake("bob".to_string(), 100, 0).unwrap();
+        staking.set_commission("val".to_string(), 0).unwrap();
+        staking.delegate("carol".to_string(), "val".to_string(), 500, 5).unwrap();
+
+        assert_eq!(staking.get_total_staked(), 600);
+        assert_eq!(staking.total_power(10), 100);
+        assert_eq!(staking.voting_power("carol", 10), 0);
+        assert_eq!(staking.voting_power("val", 10), 0);
+    }
+
+    #[test]
+    fn test_proposal_threshold() {
+        let mut staking = StakingContract::new();
+        staking.stake("whale".to_string(), 500, 0).unwrap();
+        staking.stake("minnow".to_string(), 499, 0).unwrap();
+        let mut contract = GovernanceContract::new();
+        contract.set_governance_params(10, 30, 70, 500).unwrap();
+        let propose = |c: &mut GovernanceContract, proposer: &str| {
+            c.create_proposal(
+                proposer.to_string(),
+                "Test".to_string(),
+                "Desc".to_string(),
+                "Hash".to_string(),
+                10,
+                100,
+                &staking,
+                ProposalAction::Noop,
+            )
+        };
+
+        assert_eq!(propose(&mut contract, "whale"), Ok(1));
+        assert_eq!(
+            propose(&mut contract, "minnow"),
+            Err("Proposer has 499 voting power, below the proposal threshold of 500".to_string())
+        );
+        assert!(propose(&mut contract, "nobody").is_err());
+        assert_eq!(contract.get_all_proposals().len(), 1);
+    }
+
+    #[test]
+    fn test_status_follows_proposal_lifecycle() {
+        let mut contract = GovernanceContract::new();
+        let new_proposal = |c: &mut GovernanceContract| {
+            c.create_proposal(
+                "alice".to_string(),
+                "Test".to_string(),
+                "Desc".to_string(),
+                "Hash".to_string(),
+                50,
+                100,
+                &Electorate { total: 100000 },
+                ProposalAction::Noop,
+            ).unwrap()
+        };
+        let id = new_proposal(&mut contract);
+
+        assert_eq!(contract.status(id, 10), Ok(ProposalStatus::Pending));
+        assert_eq!(contract.status(id, 60), Ok(ProposalStatus::Active));
+        contract.vote_with_weight("bob".to_string(), id, true, 20000, 60).unwrap();
+        contract.set_ai_score(id, 0.9).unwrap();
+        assert_eq!(contract.status(id, 160), Ok(ProposalStatus::Succeeded));
+
+        contract.queue_proposal(id, 160).unwrap();
+        assert_eq!(contract.status(id, 170), Ok(ProposalStatus::Queued));
+        assert_eq!(contract.execute_proposal(id, 170), Ok(true));
+        assert_eq!(contract.status(id, 180), Ok(ProposalStatus::Executed));
+
+        // no votes: quorum missed once voting closes
+        let defeated = new_proposal(&mut contract);
+        assert_eq!(contract.status(defeated, 160), Ok(ProposalStatus::Defeated));
+        assert!(contract.queue_proposal(defeated, 160).is_err());
+
+        let cancelled = new_proposal(&mut contract);
+        assert!(contract.cancel_proposal(cancelled, "bob").is_err());
+        contract.cancel_proposal(cancelled, "alice").unwrap();
+        assert_eq!(contract.status(cancelled, 60), Ok(ProposalStatus::Cancelled));
+        assert!(contract.vote_with_weight("bob".to_string(), cancelled, true, 1, 60).is_err());
+
+        assert!(contract.status(99, 0).is_err());
+    }
+
+    #[test]
+    fn test_passed_proposal_applies_its_action() {
+        let mut contract = GovernanceContract::new();
+        let new_proposal = |c: &mut GovernanceContract, action| {
+            c.create_proposal(
+                "alice".to_string(),
+                "Raise quorum".to_string(),
+                "Desc".to_string(),
+                "Hash".to_string(),
+                50,
+                100,
+                &Electorate { total: 100000 },
+                action,
+            )
+        };
+        assert!(new_proposal(&mut contract, ProposalAction::SetQuorum(101)).is_err());
+        assert!(new_proposal(&mut contract, ProposalAction::SetWeights { ai: 60, dao: 60 }).is_err());
+
+        let defeated = new_proposal(&mut contract, ProposalAction::SetQuorum(40)).unwrap();
+        contract.vote_with_weight("bob".to_string(), defeated, false, 20000, 60).unwrap();
+        assert_eq!(contract.execute_proposal(defeated, 160), Ok(false));
+        assert_eq!(contract.quorum_percentage, 10);
+
+        let passed = new_proposal(&mut contract, ProposalAction::SetQuorum(40)).unwrap();
+        contract.vote_with_weight("bob".to_string(), passed, true, 20000, 60).unwrap();
+        contract.set_ai_score(passed, 0.9).unwrap();
+        assert_eq!(contract.execute_proposal(passed, 160), Ok(true));
+        assert_eq!(contract.quorum_percentage, 40);
+
+        let weights = new_proposal(&mut contract, ProposalAction::SetWeights { ai: 50, dao: 50 }).unwrap();
+        contract.vote_with_weight("bob".to_string(), weights, true, 50000, 60).unwrap();
+        contract.set_ai_score(weights, 0.9).unwrap();
+        assert_eq!(contract.execute_proposal(weights, 160), Ok(true));
+        assert_eq!((contract.ai_weight, contract.dao_weight), (50, 50));
+    }
+
+    #[test]
+    fn test_vote_weight_is_stake_at_snapshot() {
+        let mut staking = StakingContract::new();
+        let mut contract = GovernanceContract::new();
+        let id = contract.create_proposal(
+            "alice".to_string(),
+            "Test".to_string(),
+            "Desc".to_string(),
+            "Hash".to_string(),
+            50,
+            100,
+            &Electorate { total: 1000 },
+            ProposalAction::Noop,
+        ).unwrap();
+
+        staking.stake("bob".to_string(), 100, 0).unwrap();
+        assert_eq!(staking.voting_power("bob", 50), 100);
+        staking.stake("bob".to_string(), 50, 10).unwrap();
+        staking.unstake("bob".to_string(), 30, 20).unwrap();
+        assert_eq!(staking.voting_power("bob", 50), 120);
+        // stake added after the snapshot doesn't count
+        staking.stake("bob".to_string(), 1000, 60).unwrap();
+
+        contract.vote("bob".to_string(), id, true, &staking, 70).unwrap();
+        assert_eq!(contract.get_proposal(id).unwrap().for_votes, 120);
+
+        staking.stake("carol".to_string(), 500, 60).unwrap();
+        assert_eq!(
+            contract.vote("carol".to_string(), id, true, &staking, 70),
+            Err("No voting power at snapshot".to_string())
+        );
+    }
+
+    fn signed_vote(key: &ed25519_dalek::SigningKey, voter: &str, proposal_id: u64, support: bool) -> SignedVote {
+        signed_vote_for("neonet", key, voter, proposal_id, support)
+    }
+
+    fn signed_vote_for(domain: &str, key: &ed25519_dalek::SigningKey, voter: &str, proposal_id: u64, support: bool) -> SignedVote {
+        use ed25519_dalek::Signer;
+
+        let mut vote = SignedVote { voter: voter.to_string(), proposal_id, support, signature: vec![] };
+        vote.signature = key.sign(&vote.message(domain)).to_bytes().to_vec();
+        vote
+    }
+
+    #[test]
+    fn test_voting_power_cap_clamps_whales() {
+        let mut contract = GovernanceContract::new();
+        let new_proposal = |c: &mut GovernanceContract| {
+            c.create_proposal(
+                "alice".to_string(),
+                "Test".to_string(),
+                "Desc".to_string(),
+                "Hash".to_string(),
+                0,
+                100,
+                &Electorate { total: 10000 },
+                ProposalAction::Noop,
+            ).unwrap()
+        };
+        assert!(contract.set_max_voting_power(Some(VotingPowerCap::Percent(101))).is_err());
+        assert!(contract.set_max_voting_power(Some(VotingPowerCap::Absolute(0))).is_err());
+
+        contract.set_max_voting_power(Some(VotingPowerCap::Percent(20))).unwrap();
+        let id = new_proposal(&mut contract);
+        contract.vote_with_weight("whale".to_string(), id, true, 7000, 10).unwrap();
+        contract.vote_with_weight("minnow".to_string(), id, false, 1500, 10).unwrap();
+        let proposal = contract.get_proposal(id).unwrap();
+        assert_eq!((proposal.for_votes, proposal.against_votes), (2000, 1500));
+        assert_eq!(contract.votes.iter().map(|v| v.weight).collect::<Vec<_>>(), vec![2000, 1500]);
+
+        contract.set_max_voting_power(Some(VotingPowerCap::Absolute(500))).unwrap();
+        let id = new_proposal(&mut contract);
+        contract.vote_with_weight("whale".to_string(), id, true, 7000, 10).unwrap();
+        contract.vote_with_weight("minnow".to_string(), id, false, 400, 10).unwrap();
+        let proposal = contract.get_proposal(id).unwrap();
+        assert_eq!((proposal.for_votes, proposal.against_votes), (500, 400));
+
+        contract.set_max_voting_power(None).unwrap();
+        let id = new_proposal(&mut contract);
+        contract.vote_with_weight("whale".to_string(), id, true, 7000, 10).unwrap();
+        assert_eq!(contract.get_proposal(id).unwrap().for_votes, 7000);
+
+        // a proposal keeps the cap it was created under
+        contract.set_max_voting_power(Some(VotingPowerCap::Absolute(500))).unwrap();
+        let capped = new_proposal(&mut contract);
+        contract.set_max_voting_power(None).unwrap();
+        contract.vote_with_weight("whale".to_string(), capped, true, 7000, 10).unwrap();
+        assert_eq!(contract.get_proposal(capped).unwrap().for_votes, 500);
+    }
+
+    #[test]
+    fn test_percent_cap_of_huge_snapshot_does_not_overflow() {
+        assert_eq!(VotingPowerCap::Percent(50).limit(u128::MAX), u128::MAX / 2);
+        assert_eq!(VotingPowerCap::Percent(100).limit(u128::MAX), u128::MAX);
+        assert_eq!(share(u128::MAX, 10), u128::MAX / 10);
+    }
+
+    #[test]
+    fn test_signed_vote_batch() {
+        let api = cosmwasm_std::testing::MockApi::default();
+        let mut staking = StakingContract::new();
+        let mut contract = GovernanceContract::new();
+        let id = contract.create_proposal(
+            "alice".to_string(),
+            "Test".to_string(),
+            "Desc".to_string(),
+            "Hash".to_string(),
+            50,
+            100,
+            &Electorate { total: 1000 },
+            ProposalAction::Noop,
+        ).unwrap();
+
+        let bob = ed25519_dalek::SigningKey::from_bytes(&[1u8; 32]);
+        let carol = ed25519_dalek::SigningKey::from_bytes(&[2u8; 32]);
+        let dave = ed25519_dalek::SigningKey::from_bytes(&[3u8; 32]);
+        for (name, key, stake) in [("bob", &bob, 100), ("carol", &carol, 40), ("dave", &dave, 10)] {
+            staking.stake(name.to_string(), stake, 0).unwrap();
+            contract.register_vote_key(name, name.to_string(), key.verifying_key().to_bytes().to_vec()).unwrap();
+        }
+
+        // carol's vote is signed by bob's key; the whole batch is rejected
+        let forged = vec![signed_vote(&bob, "bob", id, true), signed_vote(&bob, "carol", id, true)];
+        assert_eq!(
+            contract.submit_signed_votes(forged, &api, &staking, 60),
+            Err("vote 1: Invalid vote signature".to_string())
+        );
+        assert_eq!(contract.get_proposal(id).unwrap().for_votes, 0);
+        assert!(contract.votes.is_empty());
+
+        let batch = vec![signed_vote(&bob, "bob", id, true), signed_vote(&carol, "carol", id, false)];
+        assert_eq!(contract.submit_signed_votes(batch, &api, &staking, 60).unwrap().len(), 2);
+        let proposal = contract.get_proposal(id).unwrap();
+        assert_eq!((proposal.for_votes, proposal.against_votes), (100, 40));
+
+        // a replayed vote is a duplicate, even alongside a fresh one
+        let replay = vec![signed_vote(&dave, "dave", id, true), signed_vote(&bob, "bob", id, true)];
+        assert_eq!(
+            contract.submit_signed_votes(replay, &api, &staking, 61),
+            Err("vote 1: Already voted".to_string())
+        );
+        assert_eq!(contract.get_proposal(id).unwrap().for_votes, 100);
+        assert_eq!(contract.votes.len(), 2);
+    }
+
+    #[test]
+    fn test_vote_keys_are_registered_once_by_their_voter() {
+        let mut contract = GovernanceContract::new();
+        let bob = ed25519_dalek::SigningKey::from_bytes(&[1u8; 32]).verifying_key().to_bytes().to_vec();
+        let mallory = ed25519_dalek::SigningKey::from_bytes(&[9u8; 32]).verifying_key().to_bytes().to_vec();
+
+        assert_eq!(
+            contract.register_vote_key("mallory", "bob".to_string(), mallory.clone()),
+            Err("Only the voter can register a vote key".to_string())
+        );
+        contract.register_vote_key("bob", "bob".to_string(), bob.clone()).unwrap();
+        assert_eq!(
+            contract.register_vote_key("bob", "bob".to_string(), mallory),
+            Err("Vote key already registered".to_string())
+        );
+        assert_eq!(contract.vote_keys["bob"], bob);
+    }
+
+    #[test]
+    fn test_signed_votes_are_bound_to_their_deployment() {
+        let api = cosmwasm_std::testing::MockApi::default();
+        let mut staking = StakingContract::new();
+        staking.stake("bob".to_string(), 100, 0).unwrap();
+        let mut contract = GovernanceContract::with_vote_domain("neonet-1", "gov-a");
+        let id = contract.create_proposal(
+            "alice".to_string(),
+            "Test".to_string(),
+            "Desc".to_string(),
+            "Hash".to_string(),
+            50,
+            100,
+            &Electorate { total: 1000 },
+            ProposalAction::Noop,
+        ).unwrap();
+        let bob = ed25519_dalek::SigningKey::from_bytes(&[1u8; 32]);
+        contract.register_vote_key("bob", "bob".to_string(), bob.verifying_key().to_bytes().to_vec()).unwrap();
+
+        // signed for a sibling deployment with the same proposal id
+        let elsewhere = signed_vote_for("neonet-1/gov-b", &bob, "bob", id, true);
+        assert_eq!(
+            contract.submit_signed_votes(vec![elsewhere], &api, &staking, 60),
+            Err("vote 0: Invalid vote signature".to_string())
+        );
+        let here = signed_vote_for("neonet-1/gov-a", &bob, "bob", id, true);
+        assert!(contract.submit_signed_votes(vec![here], &api, &staking, 60).is_ok());
+        assert_eq!(contract.get_proposal(id).unwrap().for_votes, 100);
     }
 }
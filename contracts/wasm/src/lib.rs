@@ -1,7 +1,7 @@
 pub mod stake;
 pub mod governance;
 
-use cosmwasm_std::{to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
 use serde::{Deserialize, Serialize};
 use cw_storage_plus::{Item, Map};
 
@@ -51,7 +51,7 @@ pub fn execute(deps: DepsMut, _env: Env, info: MessageInfo, msg: ExecuteMsg) ->
             MODELS.save(deps.storage, id, &model)?;
             id += 1;
             NEXT_ID.save(deps.storage, &id)?;
-            Ok(Response::new().set_data(to_binary(&id)?))
+            Ok(Response::new().set_data(to_json_binary(&id)?))
         },
         ExecuteMsg::UpdateModel { id, ipfs_hash, version } => {
             let mut model = MODELS.load(deps.storage, id)?;
@@ -65,7 +65,7 @@ pub fn execute(deps: DepsMut, _env: Env, info: MessageInfo, msg: ExecuteMsg) ->
 
 pub fn query(_deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::GetModel { id } => to_binary(&MODELS.load(_deps.storage, id)?),
+        QueryMsg::GetModel { id } => to_json_binary(&MODELS.load(_deps.storage, id)?),
         QueryMsg::ListModels {} => {
             let mut models: Vec<ModelInfo> = vec![];
             let all_keys: Vec<u64> = MODELS
@@ -77,7 +77,7 @@ pub fn query(_deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
                     models.push(model);
                 }
             }
-            to_binary(&models)
+            to_json_binary(&models)
         }
     }
 }
@@ -1,5 +1,6 @@
 // Staking Contract для NeoNet WASM
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct StakeInfo {
@@ -9,6 +10,31 @@ pub struct StakeInfo {
     pub reward: u128,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Delegation {
+    pub delegator: String,
+    pub validator: String,
+    pub amount: u128,
+    pub timestamp: u64,
+    // settled rewards, already net of commission
+    pub reward: u128,
+}
+
+/// A closed reward epoch. Each staker is credited with the lowest amount they
+/// had staked at any point in `[start, end]`, so stake added mid-epoch only
+/// earns from the next epoch on.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EpochSnapshot {
+    pub start: u64,
+    pub end: u64,
+    // rate in force when the epoch closed
+    pub reward_rate: u128,
+    // seconds of the epoch the contract spent paused, which earn nothing
+    #[serde(default)]
+    pub paused: u64,
+    pub stakes: HashMap<String, u128>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct StakeMsg {
     pub amount: u128,
@@ -24,8 +50,27 @@ pub struct ClaimRewardsMsg {}
 
 pub struct StakingContract {
     pub stakes: Vec<StakeInfo>,
+    // own stake plus delegations; only own stake is in `stake_history`
     pub total_staked: u128,
     pub reward_rate: u128,
+    pub delegations: Vec<Delegation>,
+    // validator -> commission in basis points; setting one registers the validator
+    pub commission_bps: HashMap<String, u16>,
+    // validator -> commission accrued from delegators' rewards
+    pub commission_earned: HashMap<String, u128>,
+    // staker -> (time, staked amount from then on), oldest first
+    pub stake_history: HashMap<String, Vec<(u64, u128)>>,
+    // only the owner may pause; a contract without one can't be paused
+    pub owner: Option<String>,
+    pub paused: bool,
+    // when the current pause began; rewards don't accrue while paused
+    pub paused_since: u64,
+    // closed epochs, indexed by epoch number
+    pub epochs: Vec<EpochSnapshot>,
+    // start of the epoch still open
+    pub epoch_start: u64,
+    // time the open epoch spent in pauses that have already ended
+    pub epoch_paused: u64,
 }
 
 impl StakingContract {
@@ -34,13 +79,63 @@ impl StakingContract {
             stakes: Vec::new(),
             total_staked: 0,
             reward_rate: 100,
+            delegations: Vec::new(),
+            commission_bps: HashMap::new(),
+            commission_earned: HashMap::new(),
+            stake_history: HashMap::new(),
+            owner: None,
+            paused: false,
+            paused_since: 0,
+            epochs: Vec::new(),
+            epoch_start: 0,
+            epoch_paused: 0,
         }
     }
 
+    pub fn with_owner(owner: String) -> Self {
+        StakingContract { owner: Some(owner), ..Self::new() }
+    }
+
+    /// Freeze or resume staking. While paused, stake/unstake/(un)delegate and
+    /// reward claims fail, and rewards stop accruing: on resume every accrual
+    /// clock is pushed forward by the length of the pause.
+    pub fn set_paused(&mut self, caller: &str, paused: bool, now: u64) -> Result<(), String> {
+        if self.owner.as_deref() != Some(caller) {
+            return Err("Unauthorized".to_string());
+        }
+        if paused == self.paused {
+            return Ok(());
+        }
+
+        if paused {
+            self.paused_since = now;
+        } else {
+            let pause = now.saturating_sub(self.paused_since);
+            self.epoch_paused += now.saturating_sub(self.paused_since.max(self.epoch_start));
+            for stake in &mut self.stakes {
+                stake.timestamp += pause;
+            }
+            for delegation in &mut self.delegations {
+                delegation.timestamp += pause;
+            }
+        }
+        self.paused = paused;
+        Ok(())
+    }
+
+    fn ensure_not_paused(&self) -> Result<(), String> {
+        if self.paused {
+            return Err("contract paused".to_string());
+        }
+        Ok(())
+    }
+
     pub fn stake(&mut self, staker: String, amount: u128, timestamp: u64) -> Result<String, String> {
+        self.ensure_not_paused()?;
         if amount == 0 {
             return Err("Amount must be greater than 0".to_string());
         }
+        self.ensure_in_order(&staker, timestamp)?;
 
         let existing_stake = self.stakes.iter_mut().find(|s| s.staker == staker);
 
@@ -56,10 +151,13 @@ impl StakingContract {
         }
 
         self.total_staked += amount;
+        self.record_history(&staker, timestamp);
         Ok(format!("Staked {} from {}", amount, staker))
     }
 
-    pub fn unstake(&mut self, staker: String, amount: u128) -> Result<String, String> {
+    pub fn unstake(&mut self, staker: String, amount: u128, timestamp: u64) -> Result<String, String> {
+        self.ensure_not_paused()?;
+        self.ensure_in_order(&staker, timestamp)?;
         let stake = self.stakes.iter_mut().find(|s| s.staker == staker)
             .ok_or_else(|| "Stake not found".to_string())?;
 
@@ -74,30 +172,214 @@ impl StakingContract {
             self.stakes.retain(|s| s.staker != staker);
         }
 
+        self.record_history(&staker, timestamp);
         Ok(format!("Unstaked {} from {}", amount, staker))
     }
 
+    // history records the amount from each change on, which a change dated
+    // before the staker's latest one would get wrong for everything after it
+    fn ensure_in_order(&self, staker: &str, timestamp: u64) -> Result<(), String> {
+        let latest = self.stake_history.get(staker).and_then(|history| history.last());
+        if latest.is_some_and(|(t, _)| timestamp < *t) {
+            return Err("Timestamp precedes the staker's last change".to_string());
+        }
+        Ok(())
+    }
+
+    fn record_history(&mut self, staker: &str, timestamp: u64) {
+        let amount = self.get_stake(staker).map_or(0, |s| s.amount);
+        self.stake_history.entry(staker.to_string()).or_default().push((timestamp, amount));
+    }
+
+    /// Amount `staker` had staked at `timestamp`, including changes made at that
+    /// instant. Delegations aren't counted: they carry no voting power.
+    pub fn staked_at(&self, staker: &str, timestamp: u64) -> u128 {
+        self.stake_history.get(staker)
+            .and_then(|history| history.iter().take_while(|(t, _)| *t <= timestamp).last())
+            .map_or(0, |(_, amount)| *amount)
+    }
+
+    /// Own stake across every staker at `timestamp`; unlike `total_staked`,
+    /// this leaves delegations out.
+    pub fn total_staked_at(&self, timestamp: u64) -> u128 {
+        self.stake_history.keys().map(|staker| self.staked_at(staker, timestamp)).sum()
+    }
+
+    fn accrued(&self, amount: u128, since: u64, current_time: u64) -> u128 {
+        let current_time = if self.paused { current_time.min(self.paused_since) } else { current_time };
+        let time_staked = current_time.saturating_sub(since);
+        linear_reward(amount, self.reward_rate, time_staked)
+    }
+
+    /// Close the open epoch at `now`, snapshotting every staker, and start the
+    /// next one. Returns the number of the epoch just closed.
+    pub fn advance_epoch(&mut self, now: u64) -> Result<u64, String> {
+        if now < self.epoch_start {
+            return Err("Epoch cannot end before it starts".to_string());
+        }
+        let start = self.epoch_start;
+        let stakes = self.stake_history.iter()
+            .map(|(staker, history)| {
+                let changes = history.iter()
+                    .filter(|(t, _)| *t > start && *t <= now)
+                    .map(|(_, amount)| *amount);
+                let lowest = changes.fold(self.staked_at(staker, start), u128::min);
+                (staker.clone(), lowest)
+            })
+            .filter(|(_, amount)| *amount > 0)
+            .collect();
+
+        let mut paused = self.epoch_paused;
+        if self.paused {
+            paused += now.saturating_sub(self.paused_since.max(start));
+        }
+
+        self.epochs.push(EpochSnapshot { start, end: now, reward_rate: self.reward_rate, paused, stakes });
+        self.epoch_start = now;
+        self.epoch_paused = 0;
+        Ok(self.epochs.len() as u64 - 1)
+    }
+
+    /// Reward `staker` earned in a closed epoch, from that epoch's snapshot.
+    pub fn rewards_for_epoch(&self, staker: &str, epoch: u64) -> Result<u128, String> {
+        let snapshot = usize::try_from(epoch).ok()
+            .and_then(|e| self.epochs.get(e))
+            .ok_or_else(|| "Epoch not closed".to_string())?;
+        let amount = snapshot.stakes.get(staker).copied().unwrap_or(0);
+        Ok(linear_reward(amount, snapshot.reward_rate, snapshot.end - snapshot.start - snapshot.paused))
+    }
+
+    // Split a gross reward into (delegator share, validator commission)
+    fn split_commission(&self, validator: &str, gross: u128) -> (u128, u128) {
+        let bps = self.commission_bps.get(validator).copied().unwrap_or(0) as u128;
+        let commission = gross * bps / 10_000;
+        (gross - commission, commission)
+    }
+
     pub fn calculate_rewards(&self, staker: &str, current_time: u64) -> u128 {
         if let Some(stake) = self.stakes.iter().find(|s| s.staker == staker) {
-            let time_staked = current_time.saturating_sub(stake.timestamp);
-            let reward = (stake.amount * self.reward_rate * time_staked as u128) / (86400 * 365 * 100);
-            stake.reward + reward
+            stake.reward + self.accrued(stake.amount, stake.timestamp, current_time)
         } else {
             0
         }
     }
 
     pub fn claim_rewards(&mut self, staker: String, current_time: u64) -> Result<u128, String> {
+        self.ensure_not_paused()?;
+        let rewards = self.calculate_rewards(&staker, current_time);
         let stake = self.stakes.iter_mut().find(|s| s.staker == staker)
             .ok_or_else(|| "Stake not found".to_string())?;
 
-        let rewards = self.calculate_rewards(&staker, current_time);
         stake.reward = 0;
         stake.timestamp = current_time;
 
         Ok(rewards)
     }
 
+    pub fn set_commission(&mut self, validator: String, commission_bps: u16) -> Result<(), String> {
+        if commission_bps > 10_000 {
+            return Err("Commission cannot exceed 10000 bps".to_string());
+        }
+        self.commission_bps.insert(validator, commission_bps);
+        Ok(())
+    }
+
+    pub fn delegate(&mut self, delegator: String, validator: String, amount: u128, now: u64) -> Result<String, String> {
+        self.ensure_not_paused()?;
+        if amount == 0 {
+            return Err("Amount must be greater than 0".to_string());
+        }
+
+        if !self.commission_bps.contains_key(&validator) {
+            return Err("Unknown validator".to_string());
+        }
+
+        // settle what the existing delegation earned at the old amount
+        self.settle_delegation(&delegator, &validator, now);
+
+        if let Some(d) = self.delegations.iter_mut().find(|d| d.delegator == delegator && d.validator == validator) {
+            d.amount += amount;
+        } else {
+            self.delegations.push(Delegation {
+                delegator: delegator.clone(),
+                validator: validator.clone(),
+                amount,
+                timestamp: now,
+                reward: 0,
+            });
+        }
+
+        self.total_staked += amount;
+        Ok(format!("Delegated {} from {} to {}", amount, delegator, validator))
+    }
+
+    /// Withdraw `amount` from a delegation. What it earned so far is settled
+    /// first, and stays claimable once the delegation is emptied.
+    pub fn undelegate(&mut self, delegator: String, validator: String, amount: u128, now: u64) -> Result<String, String> {
+        self.ensure_not_paused()?;
+        if amount == 0 {
+            return Err("Amount must be greater than 0".to_string());
+        }
+        let pos = self.delegations.iter().position(|d| d.delegator == delegator && d.validator == validator)
+            .ok_or_else(|| "Delegation not found".to_string())?;
+        if self.delegations[pos].amount < amount {
+            return Err("Insufficient delegated amount".to_string());
+        }
+
+        self.settle_delegation(&delegator, &validator, now);
+        let delegation = &mut self.delegations[pos];
+        delegation.amount -= amount;
+        if delegation.amount == 0 && delegation.reward == 0 {
+            self.delegations.remove(pos);
+        }
+
+        self.total_staked -= amount;
+        Ok(format!("Undelegated {} from {} to {}", amount, delegator, validator))
+    }
+
+    // Move accrued rewards into the delegation (net) and the validator's commission
+    fn settle_delegation(&mut self, delegator: &str, validator: &str, now: u64) {
+        let Some(pos) = self.delegations.iter().position(|d| d.delegator == delegator && d.validator == validator) else {
+            return;
+        };
+        let gross = self.accrued(self.delegations[pos].amount, self.delegations[pos].timestamp, now);
+        let (net, commission) = self.split_commission(validator, gross);
+
+        let delegation = &mut self.delegations[pos];
+        delegation.reward += net;
+        delegation.timestamp = now;
+        *self.commission_earned.entry(validator.to_string()).or_insert(0) += commission;
+    }
+
+    /// Delegator's reward from `validator`, net of commission.
+    pub fn calculate_delegation_rewards(&self, delegator: &str, validator: &str, current_time: u64) -> u128 {
+        if let Some(d) = self.delegations.iter().find(|d| d.delegator == delegator && d.validator == validator) {
+            let gross = self.accrued(d.amount, d.timestamp, current_time);
+            d.reward + self.split_commission(validator, gross).0
+        } else {
+            0
+        }
+    }
+
+    pub fn claim_delegation_rewards(&mut self, delegator: String, validator: String, current_time: u64) -> Result<u128, String> {
+        self.ensure_not_paused()?;
+        self.settle_delegation(&delegator, &validator, current_time);
+        let delegation = self.delegations.iter_mut().find(|d| d.delegator == delegator && d.validator == validator)
+            .ok_or_else(|| "Delegation not found".to_string())?;
+
+        let rewards = delegation.reward;
+        delegation.reward = 0;
+        Ok(rewards)
+    }
+
+    pub fn get_commission_earned(&self, validator: &str) -> u128 {
+        self.commission_earned.get(validator).copied().unwrap_or(0)
+    }
+
+    pub fn get_delegated_to(&self, validator: &str) -> u128 {
+        self.delegations.iter().filter(|d| d.validator == validator).map(|d| d.amount).sum()
+    }
+
     pub fn get_stake(&self, staker: &str) -> Option<&StakeInfo> {
         self.stakes.iter().find(|s| s.staker == staker)
     }
@@ -107,6 +389,11 @@ impl StakingContract {
     }
 }
 
+// reward_rate is an APR in percent
+fn linear_reward(amount: u128, reward_rate: u128, seconds: u64) -> u128 {
+    (amount * reward_rate * seconds as u128) / (86400 * 365 * 100)
+}
+
 impl Default for StakingContract {
     fn default() -> Self {
         Self::new()
@@ -129,7 +416,7 @@ mod tests {
     fn test_unstake() {
         let mut contract = StakingContract::new();
         contract.stake("alice".to_string(), 1000, 0).unwrap();
-        let result = contract.unstake("alice".to_string(), 500);
+        let result = contract.unstake("alice".to_string(), 500, 10);
         assert!(result.is_ok());
         assert_eq!(contract.get_total_staked(), 500);
     }
@@ -143,4 +430,143 @@ mod tests {
         let rewards = contract.calculate_rewards("alice", 86400);
         assert!(rewards > 0);
     }
+
+    #[test]
+    fn test_delegation_commission_split() {
+        let mut contract = StakingContract::new();
+        contract.set_commission("val".to_string(), 1000).unwrap();
+
+        // 100% APR on 365_000 over one day is 1000 gross
+        contract.delegate("bob".to_string(), "val".to_string(), 365_000, 0).unwrap();
+        assert_eq!(contract.get_delegated_to("val"), 365_000);
+        assert_eq!(contract.calculate_delegation_rewards("bob", "val", 86400), 900);
+
+        let claimed = contract.claim_delegation_rewards("bob".to_string(), "val".to_string(), 86400).unwrap();
+        assert_eq!(claimed, 900);
+        assert_eq!(contract.get_commission_earned("val"), 100);
+        assert_eq!(contract.calculate_delegation_rewards("bob", "val", 86400), 0);
+    }
+
+    #[test]
+    fn test_undelegate_settles_rewards_first() {
+        let mut contract = StakingContract::new();
+        contract.set_commission("val".to_string(), 1000).unwrap();
+        contract.delegate("bob".to_string(), "val".to_string(), 365_000, 0).unwrap();
+
+        assert_eq!(contract.undelegate("bob".to_string(), "val".to_string(), 365_001, 86400), Err("Insufficient delegated amount".to_string()));
+        assert_eq!(contract.undelegate("carol".to_string(), "val".to_string(), 1, 86400), Err("Delegation not found".to_string()));
+
+        // the first day earns on the full amount, the second on half
+        contract.undelegate("bob".to_string(), "val".to_string(), 182_500, 86400).unwrap();
+        assert_eq!(contract.get_total_staked(), 182_500);
+        assert_eq!(contract.get_commission_earned("val"), 100);
+        assert_eq!(contract.calculate_delegation_rewards("bob", "val", 2 * 86400), 1350);
+
+        contract.undelegate("bob".to_string(), "val".to_string(), 182_500, 2 * 86400).unwrap();
+        assert_eq!(contract.get_total_staked(), 0);
+        assert_eq!(contract.get_delegated_to("val"), 0);
+        assert_eq!(contract.claim_delegation_rewards("bob".to_string(), "val".to_string(), 3 * 86400), Ok(1350));
+        assert_eq!(contract.get_commission_earned("val"), 150);
+    }
+
+    #[test]
+    fn test_delegate_requires_known_validator() {
+        let mut contract = StakingContract::new();
+        assert!(contract.delegate("bob".to_string(), "nobody".to_string(), 100, 0).is_err());
+        assert!(contract.set_commission("val".to_string(), 10_001).is_err());
+    }
+
+    #[test]
+    fn test_mid_epoch_stake_waits_for_next_epoch() {
+        let mut contract = StakingContract::new();
+        contract.stake("alice".to_string(), 365_000, 0).unwrap();
+        contract.stake("bob".to_string(), 365_000, 43200).unwrap();
+
+        assert_eq!(contract.advance_epoch(86400), Ok(0));
+        assert_eq!(contract.rewards_for_epoch("alice", 0), Ok(1000));
+        assert_eq!(contract.rewards_for_epoch("bob", 0), Ok(0));
+
+        assert_eq!(contract.advance_epoch(2 * 86400), Ok(1));
+        assert_eq!(contract.rewards_for_epoch("bob", 1), Ok(1000));
+    }
+
+    #[test]
+    fn test_epoch_snapshots_are_independent() {
+        let mut contract = StakingContract::new();
+        contract.stake("alice".to_string(), 365_000, 0).unwrap();
+        contract.advance_epoch(86400).unwrap();
+
+        // unstaking and restaking inside an epoch still counts the dip
+        contract.unstake("alice".to_string(), 182_500, 100_000).unwrap();
+        contract.stake("alice".to_string(), 182_500, 150_000).unwrap();
+        contract.reward_rate = 200;
+        contract.advance_epoch(2 * 86400).unwrap();
+
+        assert_eq!(contract.rewards_for_epoch("alice", 0), Ok(1000));
+        assert_eq!(contract.rewards_for_epoch("alice", 1), Ok(1000));
+        assert_eq!(contract.epochs[0].stakes["alice"], 365_000);
+        assert_eq!(contract.epochs[1].stakes["alice"], 182_500);
+        assert!(contract.rewards_for_epoch("alice", 2).is_err());
+        assert!(contract.advance_epoch(86400).is_err());
+    }
+
+    #[test]
+    fn test_pause_blocks_staking_and_halts_rewards() {
+        let mut contract = StakingContract::with_owner("ops".to_string());
+        contract.stake("alice".to_string(), 365_000, 0).unwrap();
+        assert!(contract.set_paused("alice", true, 86400).is_err());
+        contract.set_paused("ops", true, 86400).unwrap();
+
+        let paused = Err("contract paused".to_string());
+        assert_eq!(contract.stake("alice".to_string(), 1, 86400), paused);
+        assert_eq!(contract.unstake("alice".to_string(), 1, 86400), paused);
+        assert_eq!(contract.claim_rewards("alice".to_string(), 86400), Err("contract paused".to_string()));
+        // reads still work, and the paused day earns nothing
+        assert_eq!(contract.get_total_staked(), 365_000);
+        assert_eq!(contract.calculate_rewards("alice", 2 * 86400), 1000);
+
+        contract.set_paused("ops", false, 2 * 86400).unwrap();
+        assert_eq!(contract.calculate_rewards("alice", 3 * 86400), 2000);
+        assert!(contract.stake("alice".to_string(), 1, 3 * 86400).is_ok());
+        assert_eq!(contract.claim_rewards("alice".to_string(), 3 * 86400), Ok(2000));
+    }
+
+    #[test]
+    fn test_epoch_rewards_skip_paused_time() {
+        let mut contract = StakingContract::with_owner("ops".to_string());
+        contract.stake("alice".to_string(), 365_000, 0).unwrap();
+
+        contract.set_paused("ops", true, 43200).unwrap();
+        contract.set_paused("ops", false, 86400).unwrap();
+        contract.advance_epoch(2 * 86400).unwrap();
+        assert_eq!(contract.rewards_for_epoch("alice", 0), Ok(1500));
+
+        // a pause spanning the epoch boundary is split between both epochs
+        contract.set_paused("ops", true, 3 * 86400).unwrap();
+        contract.advance_epoch(4 * 86400).unwrap();
+        contract.set_paused("ops", false, 5 * 86400).unwrap();
+        contract.advance_epoch(6 * 86400).unwrap();
+        assert_eq!(contract.epochs[1].paused, 86400);
+        assert_eq!(contract.rewards_for_epoch("alice", 1), Ok(1000));
+        assert_eq!(contract.rewards_for_epoch("alice", 2), Ok(1000));
+    }
+
+    #[test]
+    fn test_history_rejects_backdated_changes() {
+        let mut contract = StakingContract::new();
+        contract.stake("alice".to_string(), 100, 10).unwrap();
+        contract.stake("alice".to_string(), 50, 20).unwrap();
+
+        let backdated = Err("Timestamp precedes the staker's last change".to_string());
+        assert_eq!(contract.stake("alice".to_string(), 1000, 15), backdated);
+        assert_eq!(contract.unstake("alice".to_string(), 100, 5), backdated);
+        assert_eq!(contract.get_total_staked(), 150);
+        assert_eq!(contract.staked_at("alice", 15), 100);
+        assert_eq!(contract.staked_at("alice", 20), 150);
+
+        // same instant and other stakers are fine
+        assert!(contract.unstake("alice".to_string(), 50, 20).is_ok());
+        assert!(contract.stake("bob".to_string(), 10, 0).is_ok());
+        assert_eq!(contract.staked_at("alice", 20), 100);
+    }
 }
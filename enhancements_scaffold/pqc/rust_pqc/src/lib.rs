@@ -1,4 +1,3 @@
-\
 /*!
 Persistent PQC hybrid-signature implementation (Rust)
 - Saves/loads hybrid key material to key.json (hex-encoded bytes)
@@ -19,6 +18,7 @@ use rand::rngs::OsRng;
 
 // pqcrypto Dilithium (signature)
 use pqcrypto_dilithium::dilithium2;
+use pqcrypto_traits::sign::{PublicKey as _, SecretKey as _, DetachedSignature as _};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct HybridKeyJson {
@@ -78,6 +78,42 @@ pub fn load_key_json(path: &str) -> Option<(Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>)>
     Some((ed_pk, ed_sk, pqc_pk, pqc_sk))
 }
 
+/// Check that each stored public key belongs to its secret key, to detect
+/// keystore corruption. Nothing is derived for the caller: the Ed25519 public
+/// key is recomputed from the secret and compared, while a Dilithium public
+/// key can't be cheaply recovered from its secret, so `pqc_pk` is checked
+/// instead: both encodings start with the same `rho` seed, and a probe
+/// signature made with the secret must verify under it.
+pub fn check_key_pairs(ed_pk: &[u8], ed_sk: &[u8], pqc_pk: &[u8], pqc_sk: &[u8]) -> Result<(), String> {
+    if ed_sk.len() != ED_SK_LEN {
+        return Err("invalid Ed25519 secret key length".to_string());
+    }
+    let ed_secret = ed25519_dalek::SecretKey::from_bytes(ed_sk).map_err(|e| format!("invalid Ed25519 secret key: {}", e))?;
+    let ed_public = ed25519_dalek::PublicKey::from(&ed_secret);
+    if ed_public.as_bytes() != ed_pk {
+        return Err("Ed25519 public key does not match secret key".to_string());
+    }
+
+    let pqc_secret = dilithium2::SecretKey::from_bytes(pqc_sk).map_err(|_| "invalid Dilithium secret key".to_string())?;
+    let pqc_public = dilithium2::PublicKey::from_bytes(pqc_pk).map_err(|_| "invalid Dilithium public key".to_string())?;
+    if pqc_sk[..32] != pqc_pk[..32] {
+        return Err("Dilithium public key does not match secret key".to_string());
+    }
+    let probe = b"neonet keystore check";
+    let probe_sig = dilithium2::detached_sign(probe, &pqc_secret);
+    if dilithium2::verify_detached_signature(&probe_sig, probe, &pqc_public).is_err() {
+        return Err("Dilithium public key does not match secret key".to_string());
+    }
+
+    Ok(())
+}
+
+/// Load `keyjson_path` and confirm its public keys match its secret keys.
+pub fn check_key_json(keyjson_path: &str) -> Result<(), String> {
+    let (ed_pk, ed_sk, pqc_pk, pqc_sk) = load_key_json(keyjson_path).ok_or_else(|| "key file missing or unreadable".to_string())?;
+    check_key_pairs(&ed_pk, &ed_sk, &pqc_pk, &pqc_sk)
+}
+
 pub fn sign_with_persisted_keys(message: &[u8], keyjson_path: &str) -> Option<HybridSignature> {
     // load keys
     let keys = load_key_json(keyjson_path)?;
@@ -103,7 +139,8 @@ pub fn sign_with_persisted_keys(message: &[u8], keyjson_path: &str) -> Option<Hy
             sk
         }
     };
-    let pqc_sig = dilithium2::sign(message, &pqc_sk);
+    // detached, so the signature can be checked with verify_detached_signature
+    let pqc_sig = dilithium2::detached_sign(message, &pqc_sk);
 
     let hs = HybridSignature {
         algo_classical: "Ed25519".to_string(),
@@ -122,11 +159,10 @@ pub fn sign_with_persisted_keys(message: &[u8], keyjson_path: &str) -> Option<Hy
 
 pub fn verify_with_persisted_keys(message: &[u8], hs: &HybridSignature, keyjson_path: &str) -> bool {
     // load keys (we only need public components)
-    let keys = load_key_json(keyjson_path).unwrap_or_else(|| vec![vec![],vec![],vec![],vec![]]);
-    if keys.len() != 4 {
-        return false;
-    }
-    let (ed_pk_bytes, _ed_sk, pqc_pk_bytes, _pqc_sk) = (keys[0].clone(), keys[1].clone(), keys[2].clone(), keys[3].clone());
+    let (ed_pk_bytes, _ed_sk, pqc_pk_bytes, _pqc_sk) = match load_key_json(keyjson_path) {
+        Some(keys) => keys,
+        None => return false,
+    };
 
     // verify Ed25519 part
     let ed_pk = match ed25519_dalek::PublicKey::from_bytes(&ed_pk_bytes) {
@@ -158,7 +194,7 @@ pub fn verify_with_persisted_keys(message: &[u8], hs: &HybridSignature, keyjson_
         Ok(s) => s,
         Err(_) => return false,
     };
-    if dilithium2::verify(message, &pqc_sig, &pqc_pk).is_err() {
+    if dilithium2::verify_detached_signature(&pqc_sig, message, &pqc_pk).is_err() {
         return false;
     }
 
@@ -177,14 +213,52 @@ mod tests {
         let (ed_pk, ed_sk, pqc_pk, pqc_sk) = generate_hybrid_keypair_bytes();
         save_key_json(keyfile, &ed_pk, &ed_sk, &pqc_pk, &pqc_sk).expect("save key json failed");
 
-        let message = b\"hello interoperable PQC\";
-        let hs = sign_with_persisted_keys(message, keyfile).expect(\"sign failed\");
+        let message = b"hello interoperable PQC";
+        let hs = sign_with_persisted_keys(message, keyfile).expect("sign failed");
         // verify
         let ok = verify_with_persisted_keys(message, &hs, keyfile);
-        assert!(ok, \"verify_with_persisted_keys failed\");
+        assert!(ok, "verify_with_persisted_keys failed");
 
         // cleanup
         let _ = fs::remove_file(keyfile);
-        let _ = fs::remove_file(\"last_signature.json\");
+        let _ = fs::remove_file("last_signature.json");
+    }
+
+    #[test]
+    fn fresh_signature_is_detached_and_verifies() {
+        let keyfile = "test_key_detached.json";
+        let (ed_pk, ed_sk, pqc_pk, pqc_sk) = generate_hybrid_keypair_bytes();
+        save_key_json(keyfile, &ed_pk, &ed_sk, &pqc_pk, &pqc_sk).expect("save key json failed");
+
+        let message = b"detached dilithium";
+        let hs = sign_with_persisted_keys(message, keyfile).expect("sign failed");
+        // an attached signature would also carry the message
+        let pqc_sig = hex_decode(&hs.sig_pqc_hex).unwrap();
+        assert_eq!(pqc_sig.len(), dilithium2::signature_bytes());
+        assert!(verify_with_persisted_keys(message, &hs, keyfile));
+        assert!(!verify_with_persisted_keys(b"tampered", &hs, keyfile));
+
+        let _ = fs::remove_file(keyfile);
+        let _ = fs::remove_file("last_signature.json");
+    }
+
+    #[test]
+    fn loaded_secrets_match_saved_public_keys() {
+        let keyfile = "test_key_derive.json";
+        let (ed_pk, ed_sk, pqc_pk, pqc_sk) = generate_hybrid_keypair_bytes();
+        save_key_json(keyfile, &ed_pk, &ed_sk, &pqc_pk, &pqc_sk).expect("save key json failed");
+
+        let (loaded_ed_pk, loaded_ed_sk, loaded_pqc_pk, loaded_pqc_sk) = load_key_json(keyfile).unwrap();
+        assert!(check_key_pairs(&loaded_ed_pk, &loaded_ed_sk, &loaded_pqc_pk, &loaded_pqc_sk).is_ok());
+        assert!(check_key_json(keyfile).is_ok());
+
+        // a public key from another keypair is caught
+        let (other_ed_pk, _, other_pqc_pk, _) = generate_hybrid_keypair_bytes();
+        assert!(check_key_pairs(&ed_pk, &ed_sk, &other_pqc_pk, &pqc_sk).is_err());
+        assert!(check_key_pairs(&other_ed_pk, &ed_sk, &pqc_pk, &pqc_sk).is_err());
+        save_key_json(keyfile, &other_ed_pk, &ed_sk, &pqc_pk, &pqc_sk).unwrap();
+        assert!(check_key_json(keyfile).is_err());
+
+        let _ = fs::remove_file(keyfile);
     }
 }
@@ -0,0 +1,196 @@
+//! Uniform HTTP errors: every failure renders as `{"error": {"code", "message"}}`,
+//! the protocol version and genesis handshake peers use to spot incompatible
+//! nodes, and operator signatures on privileged requests.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+use crate::address_from_pubkey;
+use crate::signature::SignatureScheme;
+
+/// Semver of the node protocol; peers must share the major version.
+pub const NEONET_PROTOCOL_VERSION: &str = "1.0.0";
+/// Sent on every response, and by peers on requests to peer endpoints.
+pub const VERSION_HEADER: &str = "x-neonet-version";
+/// Capabilities this build can run with, reported by `/status`.
+pub const SUPPORTED_FEATURES: &[&str] = &["authority", "round-robin", "pow", "pqc", "checkpoints", "long-poll"];
+
+fn major(version: &str) -> Option<u64> {
+    version.split('.').next()?.trim().parse().ok()
+}
+
+/// Accept a peer's version only if its major matches ours.
+pub fn check_peer_version(version: &str) -> Result<(), ApiError> {
+    let ours = major(NEONET_PROTOCOL_VERSION).expect("NEONET_PROTOCOL_VERSION is semver");
+    match major(version) {
+        Some(theirs) if theirs == ours => Ok(()),
+        Some(_) => Err(ApiError::bad_request(format!(
+            "incompatible protocol version {}: this node speaks {}.x ({})", version, ours, NEONET_PROTOCOL_VERSION
+        ))),
+        None => Err(ApiError::bad_request(format!("malformed protocol version {:?}", version))),
+    }
+}
+
+/// Gate for peer endpoints; requests without the header (wallets, scripts) pass.
+pub fn peer_version() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>(VERSION_HEADER)
+        .and_then(|version: Option<String>| async move {
+            match version {
+                Some(v) => check_peer_version(&v).map_err(warp::reject::custom),
+                None => Ok(()),
+            }
+        })
+        .untuple_one()
+}
+
+/// Sent by peers alongside the version: the `genesis_hash` they run.
+pub const GENESIS_HEADER: &str = "x-neonet-genesis";
+
+/// Accept a peer only if it was started from the same genesis config.
+pub fn check_peer_genesis(ours: &str, theirs: &str) -> Result<(), ApiError> {
+    if theirs != ours {
+        return Err(ApiError::bad_request(format!("genesis mismatch: peer runs {}, this node runs {}", theirs, ours)));
+    }
+    Ok(())
+}
+
+/// Hex-encoded Ed25519 public key of the operator signing a privileged request.
+pub const OPERATOR_KEY_HEADER: &str = "x-neonet-operator-key";
+/// Hex-encoded Ed25519 signature over `operator_message` of the request.
+pub const OPERATOR_SIGNATURE_HEADER: &str = "x-neonet-signature";
+/// Unix seconds the operator signed the request at.
+pub const OPERATOR_TIMESTAMP_HEADER: &str = "x-neonet-timestamp";
+/// Value the operator never reuses, so a captured request can't be replayed.
+pub const OPERATOR_NONCE_HEADER: &str = "x-neonet-nonce";
+/// How far a signed request's timestamp may be from this node's clock.
+pub const OPERATOR_MAX_SKEW_SECS: i64 = 300;
+const MAX_NONCE_LEN: usize = 64;
+
+/// Operator headers of a privileged request, as sent.
+#[derive(Debug, Clone, Default)]
+pub struct OperatorHeaders {
+    pub key: Option<String>,
+    pub signature: Option<String>,
+    pub timestamp: Option<String>,
+    pub nonce: Option<String>,
+}
+
+/// Bytes an operator signs: the request line, timestamp and nonce, one per
+/// line, then the exact body. Nonces hold no newline, so the split is unambiguous.
+pub fn operator_message(method: &str, path: &str, timestamp: i64, nonce: &str, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}\n{}\n{}\n{}\n", method, path, timestamp, nonce).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+/// Nonces of operator requests accepted within the skew window; older ones
+/// are forgotten, since their timestamps no longer pass anyway.
+#[derive(Debug, Default)]
+pub struct SeenNonces {
+    // nonce -> timestamp of the request that used it
+    seen: HashMap<String, i64>,
+}
+
+impl SeenNonces {
+    // false if `nonce` was already used
+    fn insert(&mut self, nonce: &str, timestamp: i64, now: i64) -> bool {
+        self.seen.retain(|_, t| now - *t <= OPERATOR_MAX_SKEW_SECS);
+        self.seen.insert(nonce.to_string(), timestamp).is_none()
+    }
+}
+
+/// Accept a privileged request signed by one of `operators` at about `now`,
+/// with a nonce not seen before; with no operators configured every request passes.
+pub fn check_operator(
+    operators: &[String],
+    headers: &OperatorHeaders,
+    method: &str,
+    path: &str,
+    body: &[u8],
+    seen: &mut SeenNonces,
+    now: i64,
+) -> Result<(), ApiError> {
+    if operators.is_empty() {
+        return Ok(());
+    }
+    let (Some(key), Some(signature), Some(timestamp), Some(nonce)) =
+        (&headers.key, &headers.signature, &headers.timestamp, &headers.nonce)
+    else {
+        return Err(ApiError::unauthorized("operator signature required"));
+    };
+    let key = hex::decode(key.trim_start_matches("0x")).map_err(|_| ApiError::unauthorized("invalid operator key"))?;
+    let address = address_from_pubkey(&key);
+    if !operators.iter().any(|o| o.eq_ignore_ascii_case(&address)) {
+        return Err(ApiError::unauthorized("key is not an operator"));
+    }
+    let timestamp: i64 = timestamp.parse().map_err(|_| ApiError::unauthorized("invalid operator timestamp"))?;
+    if (now - timestamp).abs() > OPERATOR_MAX_SKEW_SECS {
+        return Err(ApiError::unauthorized("stale operator request"));
+    }
+    if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ApiError::unauthorized("invalid operator nonce"));
+    }
+    let signature = hex::decode(signature.trim_start_matches("0x")).map_err(|_| ApiError::unauthorized("invalid operator signature"))?;
+    let message = operator_message(method, path, timestamp, nonce, body);
+    SignatureScheme::Ed25519.verify(&key, &message, &signature).map_err(|_| ApiError::unauthorized("invalid operator signature"))?;
+    // only a verified request uses up its nonce
+    if !seen.insert(nonce, timestamp, now) {
+        return Err(ApiError::unauthorized("replayed operator request"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into() }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+}
+
+impl warp::reject::Reject for ApiError {}
+
+impl Reply for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({"error": {"code": self.status.as_u16(), "message": self.message}});
+        warp::reply::with_status(warp::reply::json(&body), self.status).into_response()
+    }
+}
+
+/// Render handler rejections and warp's own (unknown path, bad body, ...) the same way.
+pub async fn recover(err: Rejection) -> Result<Response, Infallible> {
+    let error = if let Some(e) = err.find::<ApiError>() {
+        e.clone()
+    } else if err.is_not_found() {
+        ApiError::not_found("not found")
+    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        ApiError::bad_request(e.to_string())
+    } else if err.find::<warp::reject::InvalidQuery>().is_some() {
+        ApiError::bad_request("invalid query string")
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        ApiError::new(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+    } else {
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+    };
+    Ok(error.into_response())
+}
@@ -0,0 +1,45 @@
+//! Compact block relay: a header plus transaction hashes, rebuilt by peers from their own mempools.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Block, BlockHeader, Transaction};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CompactBlock {
+    pub header: BlockHeader,
+    // sent in full: no peer has it in a mempool; the genesis block has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<Transaction>,
+    // every other transaction, in block order
+    pub tx_hashes: Vec<String>,
+}
+
+impl From<&Block> for CompactBlock {
+    fn from(block: &Block) -> Self {
+        let (coinbase, rest) = match block.transactions.split_first() {
+            Some((coinbase, rest)) => (Some(coinbase.clone()), rest),
+            None => (None, &[][..]),
+        };
+        CompactBlock {
+            header: BlockHeader::from(block),
+            coinbase,
+            tx_hashes: rest.iter().map(Transaction::hash).collect(),
+        }
+    }
+}
+
+/// Rebuild the full block from `mempool`, or return the hashes it lacks.
+/// The result is only as trustworthy as the header; validate it as usual.
+pub fn reconstruct_block(compact: &CompactBlock, mempool: &[Transaction]) -> Result<Block, Vec<String>> {
+    let local: HashMap<String, &Transaction> = mempool.iter().map(|tx| (tx.hash(), tx)).collect();
+    let missing: Vec<String> = compact.tx_hashes.iter().filter(|h| !local.contains_key(*h)).cloned().collect();
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+
+    let mut transactions: Vec<Transaction> = compact.coinbase.iter().cloned().collect();
+    transactions.extend(compact.tx_hashes.iter().map(|h| local[h].clone()));
+    Ok(compact.header.with_transactions(transactions))
+}
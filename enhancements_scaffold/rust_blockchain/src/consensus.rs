@@ -0,0 +1,136 @@
+//! Pluggable block production: who may produce the next block and how it is sealed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Block, Blockchain};
+
+pub trait Consensus {
+    /// Producer for the next block when the caller doesn't name one.
+    fn select_producer(&self, chain: &Blockchain) -> Option<String>;
+
+    fn can_produce(&self, chain: &Blockchain, producer: &str) -> bool;
+
+    /// Bind `block` to its producer `key` and fill in `nonce`/`hash`.
+    fn seal_block(&self, block: &mut Block, key: &str);
+
+    fn verify_seal(&self, block: &Block) -> bool;
+
+    /// Whether `block.validator` could produce `block` under `validators`,
+    /// the set in effect at its height.
+    fn verify_producer(&self, block: &Block, validators: &[String]) -> bool;
+}
+
+/// Engine selection, persisted with the chain so a reload keeps the same rules.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum ConsensusKind {
+    #[default]
+    Authority,
+    RoundRobin,
+    ProofOfWork { difficulty: u32 },
+}
+
+impl ConsensusKind {
+    pub fn engine(&self) -> Box<dyn Consensus> {
+        match self {
+            ConsensusKind::Authority => Box::new(Authority),
+            ConsensusKind::RoundRobin => Box::new(RoundRobin),
+            ConsensusKind::ProofOfWork { difficulty } => Box::new(ProofOfWork { difficulty: *difficulty }),
+        }
+    }
+}
+
+/// Any member of the validator set may produce; round-robin when unspecified.
+pub struct Authority;
+
+impl Consensus for Authority {
+    fn select_producer(&self, chain: &Blockchain) -> Option<String> {
+        chain.expected_validator().map(String::from)
+    }
+
+    fn can_produce(&self, chain: &Blockchain, producer: &str) -> bool {
+        chain.validators.iter().any(|v| v == producer)
+    }
+
+    fn seal_block(&self, block: &mut Block, key: &str) {
+        block.validator = key.to_string();
+        block.nonce = 0;
+        block.hash = block.compute_hash();
+    }
+
+    fn verify_seal(&self, block: &Block) -> bool {
+        block.hash == block.compute_hash()
+    }
+
+    fn verify_producer(&self, block: &Block, validators: &[String]) -> bool {
+        validators.contains(&block.validator)
+    }
+}
+
+/// Only the validator whose turn it is may produce, so the set takes turns.
+pub struct RoundRobin;
+
+impl Consensus for RoundRobin {
+    fn select_producer(&self, chain: &Blockchain) -> Option<String> {
+        chain.expected_validator().map(String::from)
+    }
+
+    fn can_produce(&self, chain: &Blockchain, producer: &str) -> bool {
+        chain.expected_validator() == Some(producer)
+    }
+
+    fn seal_block(&self, block: &mut Block, key: &str) {
+        Authority.seal_block(block, key)
+    }
+
+    fn verify_seal(&self, block: &Block) -> bool {
+        Authority.verify_seal(block)
+    }
+
+    fn verify_producer(&self, block: &Block, validators: &[String]) -> bool {
+        !validators.is_empty() && validators[block.index as usize % validators.len()] == block.validator
+    }
+}
+
+/// Hex digits in a block hash; no seal can meet a higher difficulty.
+pub const MAX_DIFFICULTY: u32 = 64;
+
+/// Anyone may produce a block whose hash has `difficulty` leading zero hex digits.
+/// Each block records the difficulty it was sealed at and is checked against that.
+pub struct ProofOfWork {
+    pub difficulty: u32,
+}
+
+fn meets_target(hash: &str, difficulty: u32) -> bool {
+    hash.starts_with(&"0".repeat(difficulty as usize))
+}
+
+impl Consensus for ProofOfWork {
+    fn select_producer(&self, _chain: &Blockchain) -> Option<String> {
+        None
+    }
+
+    fn can_produce(&self, _chain: &Blockchain, producer: &str) -> bool {
+        !producer.is_empty()
+    }
+
+    fn seal_block(&self, block: &mut Block, key: &str) {
+        block.validator = key.to_string();
+        block.difficulty = self.difficulty;
+        block.nonce = 0;
+        loop {
+            block.hash = block.compute_hash();
+            if meets_target(&block.hash, block.difficulty) {
+                return;
+            }
+            block.nonce += 1;
+        }
+    }
+
+    fn verify_seal(&self, block: &Block) -> bool {
+        block.hash == block.compute_hash() && meets_target(&block.hash, block.difficulty)
+    }
+
+    fn verify_producer(&self, block: &Block, _validators: &[String]) -> bool {
+        !block.validator.is_empty()
+    }
+}
@@ -0,0 +1,88 @@
+//! Bounded log of recent chain events, so a subscriber that reconnects can replay what it missed.
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use crate::{Block, TxType};
+
+/// Events kept for replay; older ones are dropped first.
+pub const EVENT_LOG_CAPACITY: usize = 1024;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    Block { height: u64, hash: String, tx_count: usize },
+    // a transaction mined at `height`; the coinbase is not reported
+    Transaction { hash: String, height: u64, position: usize },
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Event {
+    // starts at 1 and increases by one per event, so `since=0` replays everything kept
+    pub seq: u64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+pub struct EventLog {
+    events: VecDeque<Event>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        EventLog { events: VecDeque::new(), capacity: capacity.max(1), next_seq: 1 }
+    }
+
+    /// The log as it stands after recording every block of `chain` past
+    /// genesis, so numbering carries on where it was before a restart.
+    pub fn from_chain(chain: &[Block], capacity: usize) -> Self {
+        let mut log = Self::new(capacity);
+        for block in chain.iter().skip(1) {
+            log.record_block(block);
+        }
+        log
+    }
+
+    pub fn push(&mut self, kind: EventKind) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event { seq, kind });
+        seq
+    }
+
+    /// A block event followed by one event per transaction, in block order.
+    pub fn record_block(&mut self, block: &Block) {
+        self.push(EventKind::Block { height: block.index, hash: block.hash.clone(), tx_count: block.transactions.len() });
+        for (position, tx) in block.transactions.iter().enumerate() {
+            if tx.tx_type != TxType::Coinbase {
+                self.push(EventKind::Transaction { hash: tx.hash(), height: block.index, position });
+            }
+        }
+    }
+
+    /// Sequence number of the newest event, 0 before any.
+    pub fn latest(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    /// Every kept event after `since`, and whether any between `since` and
+    /// the oldest kept one were already dropped. A `since` past the newest
+    /// event was numbered by some other log, so it counts as missed too.
+    pub fn since(&self, since: u64) -> (Vec<Event>, bool) {
+        let missed = since > self.latest()
+            || self.events.front().is_some_and(|oldest| oldest.seq > since + 1);
+        (self.events.iter().filter(|e| e.seq > since).cloned().collect(), missed)
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(EVENT_LOG_CAPACITY)
+    }
+}
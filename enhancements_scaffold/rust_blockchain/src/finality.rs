@@ -0,0 +1,80 @@
+//! Validator-signed checkpoints; a quorum of matching signatures makes a height final.
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{address_from_pubkey, BlockHeader};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub height: u64,
+    pub state_root: String,
+    pub block_hash: String,
+}
+
+impl Checkpoint {
+    /// SHA-256 of the JSON encoding; this digest is what validators sign.
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::digest(serde_json::to_vec(self).unwrap()).into()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SignedCheckpoint {
+    pub checkpoint: Checkpoint,
+    pub validator: String,
+    // hex-encoded Ed25519 key; must derive to `validator`
+    pub public_key: String,
+    // hex-encoded Ed25519 signature over `checkpoint.digest()`
+    pub signature: String,
+}
+
+impl SignedCheckpoint {
+    pub fn verify(&self) -> Result<(), String> {
+        let key = hex::decode(self.public_key.trim_start_matches("0x")).map_err(|e| format!("invalid public key: {}", e))?;
+        let key: [u8; 32] = key.try_into().map_err(|_| "invalid public key length".to_string())?;
+        if !address_from_pubkey(&key).eq_ignore_ascii_case(&self.validator) {
+            return Err("validator does not match public key".to_string());
+        }
+        let sig = hex::decode(self.signature.trim_start_matches("0x")).map_err(|e| format!("invalid signature: {}", e))?;
+        let sig: [u8; 64] = sig.try_into().map_err(|_| "invalid signature length".to_string())?;
+        let key = VerifyingKey::from_bytes(&key).map_err(|e| format!("invalid public key: {}", e))?;
+        key.verify(&self.checkpoint.digest(), &Signature::from_bytes(&sig))
+            .map_err(|_| "invalid checkpoint signature".to_string())
+    }
+
+    /// Address of the signing key. Unlike `validator`, this can't be
+    /// respelled without changing the key.
+    pub fn signer(&self) -> Result<String, String> {
+        let key = hex::decode(self.public_key.trim_start_matches("0x")).map_err(|e| format!("invalid public key: {}", e))?;
+        Ok(address_from_pubkey(&key))
+    }
+}
+
+/// Share of a double-signing validator's balance taken, in basis points.
+pub const DOUBLE_SIGN_SLASH_BPS: u128 = 5_000;
+/// Share of the slashed amount paid to the reporter; the rest is burned.
+pub const REPORTER_REWARD_BPS: u128 = 1_000;
+
+/// Check that `a` and `b` are two different block headers at `height`, both
+/// sealed and signed by the same proposer key, and return that key's address.
+pub fn verify_double_sign(height: u64, a: &BlockHeader, b: &BlockHeader) -> Result<String, String> {
+    let signer = a.signer()?;
+    let other = b.signer()?;
+    if a.index != height || b.index != height {
+        return Err(format!("evidence is not for height {}", height));
+    }
+    if signer != other {
+        return Err("evidence is signed by different validators".to_string());
+    }
+    if a.hash == b.hash {
+        return Err("evidence must contain two different blocks".to_string());
+    }
+    Ok(signer)
+}
+
+/// Validators carry no stake, so each counts once; at least two thirds must agree.
+pub fn has_quorum(signers: usize, validators: usize) -> bool {
+    validators > 0 && signers * 3 >= validators * 2
+}
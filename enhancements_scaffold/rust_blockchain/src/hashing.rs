@@ -0,0 +1,25 @@
+//! Hash function for block hashes and merkle roots; the genesis block fixes it for the whole chain.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    // much faster than SHA-256 over large transaction sets
+    Blake3,
+}
+
+impl HashAlgo {
+    pub fn digest(&self, data: &[u8]) -> [u8; 32] {
+        match self {
+            HashAlgo::Sha256 => Sha256::digest(data).into(),
+            HashAlgo::Blake3 => blake3::hash(data).into(),
+        }
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == HashAlgo::default()
+    }
+}
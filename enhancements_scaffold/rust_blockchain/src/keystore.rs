@@ -0,0 +1,131 @@
+//! Passphrase-encrypted Ed25519 validator keys, one JSON file per address.
+
+use std::fs;
+use std::path::PathBuf;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+
+use crate::address_from_pubkey;
+
+const KEYFILE_VERSION: u16 = 1;
+
+/// On-disk form of one key. The secret is sealed with AES-256-GCM under a key
+/// derived from the passphrase by Argon2id; the address is authenticated as
+/// associated data, so a file renamed or edited to claim another address fails
+/// to unlock.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyFile {
+    pub version: u16,
+    pub address: String,
+    pub public_key: String,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// A directory of `KeyFile`s named `<address>.json`.
+#[derive(Debug)]
+pub struct Keystore {
+    dir: PathBuf,
+}
+
+impl Keystore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Keystore { dir: dir.into() }
+    }
+
+    /// Create a fresh key, store it under `passphrase` and return its address.
+    pub fn generate(&self, passphrase: &str) -> Result<String, String> {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        self.store(&SigningKey::from_bytes(&secret), passphrase)
+    }
+
+    /// Store an existing hex-encoded 32-byte secret and return its address.
+    pub fn import(&self, secret_hex: &str, passphrase: &str) -> Result<String, String> {
+        let secret = hex::decode(secret_hex.trim().trim_start_matches("0x")).map_err(|e| format!("invalid secret key: {}", e))?;
+        let secret: [u8; 32] = secret.try_into().map_err(|_| "invalid secret key length".to_string())?;
+        self.store(&SigningKey::from_bytes(&secret), passphrase)
+    }
+
+    /// Decrypt the key stored for `address`. A wrong passphrase is an error.
+    pub fn unlock(&self, address: &str, passphrase: &str) -> Result<SigningKey, String> {
+        let path = self.path(address);
+        let file = fs::read_to_string(&path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+        let file: KeyFile = serde_json::from_str(&file).map_err(|e| format!("parsing {}: {}", path.display(), e))?;
+        if file.version != KEYFILE_VERSION {
+            return Err(format!("unsupported keyfile version {}", file.version));
+        }
+        let decode = |field: &str, value: &str| hex::decode(value).map_err(|e| format!("invalid {}: {}", field, e));
+        let salt = decode("salt", &file.salt)?;
+        let nonce = decode("nonce", &file.nonce)?;
+        if nonce.len() != 12 {
+            return Err("invalid nonce length".to_string());
+        }
+        let ciphertext = decode("ciphertext", &file.ciphertext)?;
+        let secret = cipher(passphrase, &salt)?
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: file.address.as_bytes() })
+            .map_err(|_| "wrong passphrase or corrupted keyfile".to_string())?;
+        let secret: [u8; 32] = secret.try_into().map_err(|_| "invalid secret key length".to_string())?;
+        let key = SigningKey::from_bytes(&secret);
+        if !address_from_pubkey(key.verifying_key().as_bytes()).eq_ignore_ascii_case(address) {
+            return Err("keyfile does not hold the key for this address".to_string());
+        }
+        Ok(key)
+    }
+
+    /// Addresses with a stored key, sorted.
+    pub fn list(&self) -> Result<Vec<String>, String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(format!("reading {}: {}", self.dir.display(), e)),
+        };
+        let mut addresses: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+            .collect();
+        addresses.sort();
+        Ok(addresses)
+    }
+
+    fn store(&self, key: &SigningKey, passphrase: &str) -> Result<String, String> {
+        let public_key = key.verifying_key();
+        let address = address_from_pubkey(public_key.as_bytes());
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = cipher(passphrase, &salt)?
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: key.as_bytes(), aad: address.as_bytes() })
+            .map_err(|_| "encrypting key failed".to_string())?;
+        let file = KeyFile {
+            version: KEYFILE_VERSION,
+            address: address.clone(),
+            public_key: hex::encode(public_key.as_bytes()),
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        fs::create_dir_all(&self.dir).map_err(|e| format!("creating {}: {}", self.dir.display(), e))?;
+        let path = self.path(&address);
+        fs::write(&path, serde_json::to_string_pretty(&file).unwrap()).map_err(|e| format!("writing {}: {}", path.display(), e))?;
+        Ok(address)
+    }
+
+    fn path(&self, address: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", address.to_ascii_lowercase()))
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<Aes256Gcm, String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("deriving key: {}", e))?;
+    Ok(Aes256Gcm::new(&key.into()))
+}
@@ -1,16 +1,146 @@
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
 use warp::Filter;
 use chrono::Utc;
-use std::fs;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use sha2::{Digest, Sha256};
+use tiny_keccak::{Hasher, Keccak};
+
+mod api;
+mod compact;
+mod consensus;
+mod events;
+mod finality;
+mod hashing;
+mod keystore;
+mod receipts;
+mod signature;
+mod storage;
+use api::ApiError;
+use compact::CompactBlock;
+use consensus::{ConsensusKind, MAX_DIFFICULTY};
+use events::EventLog;
+use finality::{Checkpoint, SignedCheckpoint};
+use hashing::HashAlgo;
+use keystore::Keystore;
+use receipts::{ContractRuntime, ContractVm, Execution, TxReceipt};
+use signature::SignatureScheme;
+use storage::{JsonFileStore, MemoryStore, SledStore, Storage};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum TxType {
+    #[default]
+    Transfer,
+    // validator set changes; the validator address is carried in `to`
+    AddValidator,
+    RemoveValidator,
+    // block reward minted to the validator; only valid as a block's first tx
+    Coinbase,
+    // proof that a validator signed two different blocks at one height; anyone
+    // may submit it, and the validator is removed and slashed
+    ReportDoubleSign {
+        height: u64,
+        block_a: Box<BlockHeader>,
+        block_b: Box<BlockHeader>,
+    },
+    // call into the contract at `to` with `payload` as call data; the outcome
+    // is recorded as a receipt
+    ContractCall { vm: ContractVm },
+    // deploy `payload` as contract code; `to` is left empty and the runtime
+    // reports the new address in the receipt
+    ContractDeploy { vm: ContractVm },
+}
+
+/// System transactions are packed ahead of every user transaction, whatever the fees.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum TxClass {
+    System,
+    #[default]
+    User,
+}
+
+impl TxClass {
+    fn is_user(&self) -> bool {
+        *self == TxClass::User
+    }
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Transaction {
     pub from: String,
     pub to: String,
-    pub amount: u64,
+    pub amount: u128,
     pub payload: Option<String>,
+    #[serde(default)]
+    pub tx_type: TxType,
+    // hex-encoded sender key in the chain's signature scheme; must derive to `from`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    // hex-encoded signature over `signing_hash()`, checked against `public_key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    // free-form label searchable via /tx/by-tag; omitted from the hash when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    // the class and fee are omitted from the hash at their defaults, like `tag`
+    #[serde(default, skip_serializing_if = "TxClass::is_user")]
+    pub class: TxClass,
+    // paid by the sender to the block's validator through the coinbase
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub fee: u128,
+    // the sender's account nonce this must be applied at, so a mined
+    // transaction can't be replayed; omitted from the hash at zero
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub nonce: u64,
+}
+
+/// Longest accepted `Transaction::tag`, in bytes.
+pub const MAX_TAG_LEN: usize = 64;
+
+/// `0x` + hex of the last 20 bytes of keccak256(pubkey).
+pub fn address_from_pubkey(pubkey: &[u8]) -> String {
+    let mut hash = [0u8; 32];
+    let mut keccak = Keccak::v256();
+    keccak.update(pubkey);
+    keccak.finalize(&mut hash);
+    format!("0x{}", hex::encode(&hash[12..]))
+}
+
+impl Transaction {
+    /// SHA-256 of the JSON encoding; also the merkle leaf on SHA-256 chains.
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(serde_json::to_vec(self).unwrap()))
+    }
+
+    /// SHA-256 of the JSON encoding with `signature` cleared; this is what senders sign.
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let unsigned = Transaction { signature: None, ..self.clone() };
+        Sha256::digest(serde_json::to_vec(&unsigned).unwrap()).into()
+    }
+
+    /// Check that `public_key` belongs to `from` and `signature` covers
+    /// `signing_hash` under `scheme`. Every transaction but the coinbase
+    /// must carry both.
+    pub fn verify_sender(&self, scheme: SignatureScheme) -> Result<(), String> {
+        let (Some(key_hex), Some(sig_hex)) = (&self.public_key, &self.signature) else {
+            return Err("transactions must carry a public key and signature".to_string());
+        };
+        let key = hex::decode(key_hex.trim_start_matches("0x")).map_err(|e| format!("invalid public key: {}", e))?;
+        if key.len() != scheme.public_key_len() {
+            return Err(format!("invalid public key length for {:?}", scheme));
+        }
+        if !address_from_pubkey(&key).eq_ignore_ascii_case(&self.from) {
+            return Err("sender does not match public key".to_string());
+        }
+        let sig = hex::decode(sig_hex.trim_start_matches("0x")).map_err(|e| format!("invalid signature: {}", e))?;
+        scheme.verify(&key, &self.signing_hash(), &sig)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -19,142 +149,3564 @@ pub struct Block {
     pub previous_hash: String,
     pub timestamp: i64,
     pub transactions: Vec<Transaction>,
+    #[serde(default)]
+    pub merkle_root: String,
+    // account state root after applying this block
+    #[serde(default)]
+    pub state_root: String,
+    // hex bloom over the block's from/to addresses and tags; empty in blocks
+    // mined before blooms existed
+    #[serde(default)]
+    pub bloom: String,
+    // fee per transaction burned in this block; user transactions must pay at least this
+    #[serde(default)]
+    pub base_fee: u128,
+    // leading zero hex digits `hash` must have; 0 outside proof of work
+    #[serde(default)]
+    pub difficulty: u32,
     pub nonce: u64,
     pub hash: String,
     pub validator: String,
+    // hex-encoded Ed25519 key of the validator; must derive to `validator`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    // hex-encoded Ed25519 signature over the bytes of `hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    // hashes `hash` and `merkle_root`; every block uses the genesis block's
+    #[serde(default, skip_serializing_if = "HashAlgo::is_default")]
+    pub hash_algo: HashAlgo,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+impl Block {
+    /// `hash_algo` over `canonical_bytes`.
+    pub fn compute_hash(&self) -> String {
+        hex::encode(self.hash_algo.digest(&self.canonical_bytes()))
+    }
+
+    /// The exact bytes `compute_hash` hashes: the header fields in a fixed
+    /// order; transactions are covered by `merkle_root`.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut preimage = Vec::new();
+        preimage.extend(self.index.to_be_bytes());
+        preimage.extend(self.previous_hash.as_bytes());
+        preimage.extend(self.timestamp.to_be_bytes());
+        preimage.extend(self.merkle_root.as_bytes());
+        preimage.extend(self.state_root.as_bytes());
+        preimage.extend(self.bloom.as_bytes());
+        // skipped at zero so blocks from before base fees keep their hashes
+        if self.base_fee > 0 {
+            preimage.extend(self.base_fee.to_be_bytes());
+        }
+        // likewise for blocks from before difficulties were recorded
+        if self.difficulty > 0 {
+            preimage.extend(self.difficulty.to_be_bytes());
+        }
+        preimage.extend(self.nonce.to_be_bytes());
+        preimage.extend(self.validator.as_bytes());
+        preimage
+    }
+
+    /// Sign the sealed `hash`; the signature is not part of the hash itself.
+    pub fn sign(&mut self, key: &ed25519_dalek::SigningKey) {
+        use ed25519_dalek::Signer;
+        self.public_key = Some(hex::encode(key.verifying_key().as_bytes()));
+        self.signature = Some(hex::encode(key.sign(self.hash.as_bytes()).to_bytes()));
+    }
+
+    /// Check the validator's signature, if the block carries one. Whether a
+    /// block must be signed is up to the chain; see `Blockchain::validate`.
+    pub fn verify_signature(&self) -> Result<(), String> {
+        let (key_hex, sig_hex) = match (&self.public_key, &self.signature) {
+            (None, None) => return Ok(()),
+            (Some(key), Some(sig)) => (key, sig),
+            _ => return Err("signature and public key must be set together".to_string()),
+        };
+        let key = hex::decode(key_hex.trim_start_matches("0x")).map_err(|e| format!("invalid public key: {}", e))?;
+        if !address_from_pubkey(&key).eq_ignore_ascii_case(&self.validator) {
+            return Err("validator does not match public key".to_string());
+        }
+        let sig = hex::decode(sig_hex.trim_start_matches("0x")).map_err(|e| format!("invalid signature: {}", e))?;
+        SignatureScheme::Ed25519.verify(&key, self.hash.as_bytes(), &sig)
+    }
+}
+
+/// Block without its transactions, for headers-first sync.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub previous_hash: String,
+    pub timestamp: i64,
+    pub merkle_root: String,
+    pub state_root: String,
+    #[serde(default)]
+    pub bloom: String,
+    #[serde(default)]
+    pub base_fee: u128,
+    // needed to recheck proof-of-work seals, along with `nonce`
+    #[serde(default)]
+    pub difficulty: u32,
+    #[serde(default)]
+    pub nonce: u64,
+    pub hash: String,
+    pub validator: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "HashAlgo::is_default")]
+    pub hash_algo: HashAlgo,
+}
+
+impl From<&Block> for BlockHeader {
+    fn from(block: &Block) -> Self {
+        BlockHeader {
+            index: block.index,
+            previous_hash: block.previous_hash.clone(),
+            timestamp: block.timestamp,
+            merkle_root: block.merkle_root.clone(),
+            state_root: block.state_root.clone(),
+            bloom: block.bloom.clone(),
+            base_fee: block.base_fee,
+            difficulty: block.difficulty,
+            nonce: block.nonce,
+            hash: block.hash.clone(),
+            validator: block.validator.clone(),
+            public_key: block.public_key.clone(),
+            signature: block.signature.clone(),
+            hash_algo: block.hash_algo,
+        }
+    }
+}
+
+impl BlockHeader {
+    /// The full block this header seals, given its transactions.
+    pub fn with_transactions(&self, transactions: Vec<Transaction>) -> Block {
+        Block {
+            index: self.index,
+            previous_hash: self.previous_hash.clone(),
+            timestamp: self.timestamp,
+            transactions,
+            merkle_root: self.merkle_root.clone(),
+            state_root: self.state_root.clone(),
+            bloom: self.bloom.clone(),
+            base_fee: self.base_fee,
+            difficulty: self.difficulty,
+            nonce: self.nonce,
+            hash: self.hash.clone(),
+            validator: self.validator.clone(),
+            public_key: self.public_key.clone(),
+            signature: self.signature.clone(),
+            hash_algo: self.hash_algo,
+        }
+    }
+
+    /// Address of the key that signed this header, once `hash` matches the
+    /// header's fields and the signature over it checks out.
+    pub fn signer(&self) -> Result<String, String> {
+        let key_hex = self.public_key.as_ref().ok_or("header is not signed")?;
+        let block = self.with_transactions(Vec::new());
+        if block.hash != block.compute_hash() {
+            return Err("header hash does not match its contents".to_string());
+        }
+        block.verify_signature()?;
+        let key = hex::decode(key_hex.trim_start_matches("0x")).map_err(|e| format!("invalid public key: {}", e))?;
+        Ok(address_from_pubkey(&key))
+    }
+}
+
+/// Order in which a validator packs pending transactions, so the same
+/// mempool always yields the same block whatever order it arrived in:
+/// system before user, then fee (descending), then nonce (ascending), then hash.
+/// Each sender's transactions then fill the slots that ranking gave them in
+/// nonce order, so a later nonce paying more never lands ahead of an earlier one.
+pub fn canonical_order(txs: &mut [Transaction]) {
+    txs.sort_by_cached_key(|tx| (tx.class != TxClass::System, std::cmp::Reverse(tx.fee), tx.nonce, tx.hash()));
+    let mut by_sender: HashMap<String, Vec<Transaction>> = HashMap::new();
+    for tx in txs.iter() {
+        by_sender.entry(tx.from.clone()).or_default().push(tx.clone());
+    }
+    // highest nonce first, so popping yields them in sequence
+    for queue in by_sender.values_mut() {
+        queue.sort_by_cached_key(|tx| std::cmp::Reverse((tx.nonce, tx.hash())));
+    }
+    for slot in txs.iter_mut() {
+        *slot = by_sender.get_mut(&slot.from).and_then(Vec::pop).expect("every slot's sender has a queued transaction");
+    }
+}
+
+fn merkle_leaves(algo: HashAlgo, txs: &[Transaction]) -> Vec<[u8; 32]> {
+    txs.iter().map(|tx| algo.digest(&serde_json::to_vec(tx).unwrap())).collect()
+}
+
+fn merkle_parents(algo: HashAlgo, level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| algo.digest(&[pair[0], *pair.get(1).unwrap_or(&pair[0])].concat()))
+        .collect()
+}
+
+/// Merkle root under `algo` over the JSON encoding of each transaction; an
+/// odd node at any level is paired with itself. No transactions hash to
+/// `algo` of the empty string.
+pub fn merkle_root(algo: HashAlgo, txs: &[Transaction]) -> String {
+    let mut level = merkle_leaves(algo, txs);
+    if level.is_empty() {
+        return hex::encode(algo.digest(b""));
+    }
+    while level.len() > 1 {
+        level = merkle_parents(algo, &level);
+    }
+    hex::encode(level[0])
+}
+
+/// Sibling hashes from the leaf of `txs[index]` up to the root, each prefixed
+/// with `L:` or `R:` for the side the sibling sits on.
+pub fn merkle_proof(algo: HashAlgo, txs: &[Transaction], mut index: usize) -> Option<Vec<String>> {
+    let mut level = merkle_leaves(algo, txs);
+    if index >= level.len() {
+        return None;
+    }
+    let mut proof = Vec::new();
+    while level.len() > 1 {
+        let step = if index % 2 == 1 {
+            format!("L:{}", hex::encode(level[index - 1]))
+        } else {
+            format!("R:{}", hex::encode(level.get(index + 1).unwrap_or(&level[index])))
+        };
+        proof.push(step);
+        level = merkle_parents(algo, &level);
+        index /= 2;
+    }
+    Some(proof)
+}
+
+/// Check a `merkle_proof` for `tx` against the block's merkle root.
+pub fn verify_tx_inclusion(block: &Block, tx: &Transaction, proof: Vec<String>) -> bool {
+    let algo = block.hash_algo;
+    let mut node = algo.digest(&serde_json::to_vec(tx).unwrap());
+    for step in &proof {
+        let (side, sibling_hex) = match step.split_once(':') {
+            Some(parts) => parts,
+            None => return false,
+        };
+        let sibling: [u8; 32] = match hex::decode(sibling_hex).ok().and_then(|b| b.try_into().ok()) {
+            Some(sibling) => sibling,
+            None => return false,
+        };
+        node = match side {
+            "L" => algo.digest(&[sibling, node].concat()),
+            "R" => algo.digest(&[node, sibling].concat()),
+            _ => return false,
+        };
+    }
+    hex::encode(node) == block.merkle_root
+}
+
+const BLOOM_BYTES: usize = 256;
+
+// three 11-bit positions taken from the item's SHA-256
+fn bloom_bits(item: &str) -> [usize; 3] {
+    let digest = Sha256::digest(item.as_bytes());
+    [0, 2, 4].map(|i| (u16::from_be_bytes([digest[i], digest[i + 1]]) as usize) % (BLOOM_BYTES * 8))
+}
+
+/// 2048-bit bloom over every transaction's `from`, `to` and tag.
+pub fn block_bloom(txs: &[Transaction]) -> String {
+    let mut bloom = [0u8; BLOOM_BYTES];
+    let items = txs.iter().flat_map(|tx| [Some(&tx.from), Some(&tx.to), tx.tag.as_ref()]).flatten();
+    for item in items {
+        for bit in bloom_bits(item) {
+            bloom[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    hex::encode(bloom)
+}
+
+/// May give false positives, never false negatives. The empty bloom of a
+/// block mined before blooms existed rules nothing out.
+pub fn bloom_contains(bloom: &str, item: &str) -> bool {
+    if bloom.is_empty() {
+        return true;
+    }
+    let Ok(bytes) = hex::decode(bloom) else {
+        return false;
+    };
+    bytes.len() == BLOOM_BYTES && bloom_bits(item).iter().all(|bit| bytes[bit / 8] & (1 << (bit % 8)) != 0)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Account {
+    pub balance: u128,
+    pub nonce: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StateSnapshot {
+    pub height: u64,
+    // root over the full account map, even when `accounts` is a single page
+    pub state_root: String,
+    pub accounts: BTreeMap<String, Account>,
+    // last address of this page when more accounts follow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+/// Settings a block is produced under. They can change while the chain runs,
+/// so `mine_block` records them whenever they do and `validate` checks each
+/// block against those of its own height.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChainRules {
+    pub validators: Vec<String>,
+    pub block_reward: u128,
+    pub max_block_txs: usize,
+    pub max_block_bytes: usize,
+    pub min_base_fee: u128,
+    // proof-of-work difficulty blocks are sealed at; 0 on permissioned engines
+    pub difficulty: u32,
+}
+
+/// Per-validator production record.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ValidatorPerf {
+    pub blocks_produced: u64,
+    // heights where this validator was the scheduled producer but someone else produced
+    pub blocks_missed: u64,
+    pub last_produced_height: Option<u64>,
+}
+
+/// Everything two nodes must agree on before exchanging blocks. Hashed into
+/// the chain's `genesis_hash`, so a mismatch shows up as a different fingerprint.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GenesisConfig {
+    pub chain_id: String,
+    pub validators: Vec<String>,
+    pub governance: Vec<String>,
+    // the genesis block's state root, which commits to the allocation
+    pub allocation_root: String,
+    pub genesis_time: i64,
+    pub hash_algo: HashAlgo,
+    pub consensus: ConsensusKind,
+    pub signature_scheme: SignatureScheme,
+    pub block_reward: u128,
+    pub min_base_fee: u128,
+    pub max_block_txs: usize,
+    pub max_block_bytes: usize,
+}
+
+impl GenesisConfig {
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(serde_json::to_vec(self).unwrap()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Blockchain {
+    // names the network; part of the genesis hash
+    #[serde(default = "default_chain_id")]
+    pub chain_id: String,
+    // fingerprint of the genesis config, fixed when the chain is first opened
+    #[serde(default)]
+    pub genesis_hash: String,
     pub chain: Vec<Block>,
     pub pending: Vec<Transaction>,
     pub validators: Vec<String>,
+    // addresses allowed to add/remove validators
+    #[serde(default)]
+    pub governance: Vec<String>,
+    // addresses whose Ed25519 keys may sign privileged API requests; none leaves them open
+    #[serde(default)]
+    pub operators: Vec<String>,
+    #[serde(default)]
+    pub accounts: BTreeMap<String, Account>,
+    // issuance minted to the validator of every block
+    #[serde(default)]
+    pub block_reward: u128,
+    #[serde(default)]
+    pub consensus: ConsensusKind,
+    // fixed at genesis; decides the key/signature layout transactions carry
+    #[serde(default)]
+    pub signature_scheme: SignatureScheme,
+    // height -> validator -> the checkpoint it signed, for heights not yet final
+    #[serde(default)]
+    pub checkpoint_votes: BTreeMap<u64, BTreeMap<String, Checkpoint>>,
+    // latest checkpoint signed by a validator quorum; history up to it is fixed
+    #[serde(default)]
+    pub finalized: Option<Checkpoint>,
+    #[serde(default)]
+    pub validator_perf: BTreeMap<String, ValidatorPerf>,
+    // first height -> the rules blocks from there on were produced under
+    #[serde(default)]
+    pub rules: BTreeMap<u64, ChainRules>,
+    // most transactions a block takes besides its coinbase; system transactions
+    // are always all included, user ones fill what is left
+    #[serde(default = "default_max_block_txs")]
+    pub max_block_txs: usize,
+    // most bytes of encoded transactions a block takes besides its coinbase;
+    // like `max_block_txs`, never enforced against system transactions alone
+    #[serde(default = "default_max_block_bytes")]
+    pub max_block_bytes: usize,
+    // floor the base fee never adjusts below
+    #[serde(default)]
+    pub min_base_fee: u128,
+    // tx hash -> (block index, position in block); rebuilt on load
+    #[serde(skip)]
+    pub tx_index: HashMap<String, (u64, usize)>,
+    // tag -> hashes of mined txs carrying it, in chain order; rebuilt on load
+    #[serde(skip)]
+    pub tag_index: HashMap<String, Vec<String>>,
+    // where `persist` writes; unset for chains that are never saved
+    #[serde(skip)]
+    storage: Option<Box<dyn Storage>>,
+    // this node's validator key, unlocked from the keystore; signs blocks it mines as that validator
+    #[serde(skip)]
+    pub signer: Option<ed25519_dalek::SigningKey>,
+    // tx hash -> outcome of a mined contract call or deployment
+    #[serde(default)]
+    pub receipts: BTreeMap<String, TxReceipt>,
+    // executes contract calls as they are mined; without one every call fails
+    #[serde(skip)]
+    pub runtime: Option<Box<dyn ContractRuntime>>,
+}
+
+/// Timestamp of the genesis block unless `set_genesis_time` picks another;
+/// fixed so that every node builds the same genesis block.
+pub const DEFAULT_GENESIS_TIME: i64 = 0;
+
+fn default_chain_id() -> String {
+    "neonet".to_string()
+}
+
+fn default_max_block_txs() -> usize {
+    1000
+}
+
+fn default_max_block_bytes() -> usize {
+    1_000_000
+}
+
+/// Length of the JSON encoding, the size counted against `max_block_bytes`.
+fn encoded_len(tx: &Transaction) -> usize {
+    serde_json::to_vec(tx).unwrap().len()
 }
 
 impl Blockchain {
     pub fn new(validators: Vec<String>) -> Self {
         let mut bc = Blockchain {
+            chain_id: default_chain_id(),
+            genesis_hash: String::new(),
             chain: vec![],
             pending: vec![],
             validators,
+            governance: vec![],
+            operators: vec![],
+            accounts: BTreeMap::new(),
+            block_reward: 0,
+            consensus: ConsensusKind::default(),
+            signature_scheme: SignatureScheme::default(),
+            checkpoint_votes: BTreeMap::new(),
+            finalized: None,
+            validator_perf: BTreeMap::new(),
+            rules: BTreeMap::new(),
+            max_block_txs: default_max_block_txs(),
+            max_block_bytes: default_max_block_bytes(),
+            min_base_fee: 0,
+            tx_index: HashMap::new(),
+            tag_index: HashMap::new(),
+            storage: None,
+            signer: None,
+            receipts: BTreeMap::new(),
+            runtime: None,
         };
         bc.chain.push(bc.genesis());
         bc
     }
 
+    /// Hash function of this chain, recorded in its genesis block.
+    pub fn hash_algo(&self) -> HashAlgo {
+        self.chain[0].hash_algo
+    }
+
+    /// Pick the chain's hash function; only possible before the first block is mined.
+    pub fn set_hash_algo(&mut self, algo: HashAlgo) -> Result<(), String> {
+        if self.chain.len() > 1 {
+            return Err("the hash algorithm is fixed once blocks are mined".to_string());
+        }
+        let genesis = &mut self.chain[0];
+        genesis.hash_algo = algo;
+        genesis.merkle_root = merkle_root(algo, &genesis.transactions);
+        genesis.hash = genesis.compute_hash();
+        Ok(())
+    }
+
+    /// Date the genesis block; only possible before the first block is mined.
+    pub fn set_genesis_time(&mut self, timestamp: i64) -> Result<(), String> {
+        if self.chain.len() > 1 {
+            return Err("the genesis time is fixed once blocks are mined".to_string());
+        }
+        let genesis = &mut self.chain[0];
+        genesis.timestamp = timestamp;
+        genesis.hash = genesis.compute_hash();
+        Ok(())
+    }
+
+    /// Fund accounts in the genesis state; only possible before the first block is mined.
+    pub fn set_genesis_allocation(&mut self, alloc: BTreeMap<String, u128>) -> Result<(), String> {
+        if self.chain.len() > 1 {
+            return Err("the genesis allocation is fixed once blocks are mined".to_string());
+        }
+        self.accounts = alloc.into_iter().map(|(address, balance)| (address, Account { balance, nonce: 0 })).collect();
+        let state_root = self.state_root();
+        let genesis = &mut self.chain[0];
+        genesis.state_root = state_root;
+        genesis.hash = genesis.compute_hash();
+        Ok(())
+    }
+
+    /// Retarget a proof-of-work chain from the next block on. Mined blocks
+    /// carry the difficulty they were sealed at, so history stays valid.
+    pub fn set_difficulty(&mut self, difficulty: u32) -> Result<(), String> {
+        let ConsensusKind::ProofOfWork { difficulty: current } = &mut self.consensus else {
+            return Err("difficulty applies only to proof-of-work chains".to_string());
+        };
+        if difficulty > MAX_DIFFICULTY {
+            return Err(format!("difficulty cannot exceed {}, the length of a block hash", MAX_DIFFICULTY));
+        }
+        *current = difficulty;
+        Ok(())
+    }
+
+    /// The current genesis parameters. Validators and the other settings can
+    /// change once blocks are mined, so this matches the chain's real genesis
+    /// only until then; `genesis_hash` keeps the fingerprint taken at that point.
+    pub fn genesis_config(&self) -> GenesisConfig {
+        GenesisConfig {
+            chain_id: self.chain_id.clone(),
+            validators: self.validators.clone(),
+            governance: self.governance.clone(),
+            allocation_root: self.chain[0].state_root.clone(),
+            genesis_time: self.chain[0].timestamp,
+            hash_algo: self.hash_algo(),
+            consensus: self.consensus.clone(),
+            signature_scheme: self.signature_scheme,
+            block_reward: self.block_reward,
+            min_base_fee: self.min_base_fee,
+            max_block_txs: self.max_block_txs,
+            max_block_bytes: self.max_block_bytes,
+        }
+    }
+
+    /// Network fingerprint: the recorded genesis hash, or that of the current
+    /// config for a chain that has not been opened yet.
+    pub fn genesis_hash(&self) -> String {
+        if self.genesis_hash.is_empty() {
+            self.genesis_config().hash()
+        } else {
+            self.genesis_hash.clone()
+        }
+    }
+
+    pub fn with_governance(validators: Vec<String>, governance: Vec<String>) -> Self {
+        let mut bc = Blockchain::new(validators);
+        bc.governance = governance;
+        bc
+    }
+
     pub fn genesis(&self) -> Block {
-        Block {
+        let mut genesis = Block {
             index: 0,
             previous_hash: String::from("0"),
-            timestamp: Utc::now().timestamp(),
+            timestamp: DEFAULT_GENESIS_TIME,
             transactions: vec![],
+            merkle_root: merkle_root(HashAlgo::default(), &[]),
+            state_root: self.state_root(),
+            bloom: block_bloom(&[]),
+            base_fee: 0,
+            difficulty: 0,
             nonce: 0,
-            hash: String::from("genesis_hash"),
+            hash: String::new(),
             validator: String::from("genesis"),
-        }
+            public_key: None,
+            signature: None,
+            hash_algo: HashAlgo::default(),
+        };
+        genesis.hash = genesis.compute_hash();
+        genesis
     }
 
-    pub fn add_transaction(&mut self, tx: Transaction) {
+    pub fn add_transaction(&mut self, tx: Transaction) -> Result<(), String> {
+        if tx.tag.as_ref().is_some_and(|t| t.len() > MAX_TAG_LEN) {
+            return Err(format!("tag exceeds {} bytes", MAX_TAG_LEN));
+        }
+        tx.verify_sender(self.signature_scheme)?;
+        self.check_system(&tx, &self.validators)?;
+        self.check_kind(&tx)?;
+        let base_fee = self.next_base_fee();
+        if tx.class == TxClass::User && tx.fee < base_fee {
+            return Err(format!("fee below the current base fee of {}", base_fee));
+        }
+        // mining never creates the sender's account, so one without can't be included
+        if !self.accounts.contains_key(&tx.from) {
+            return Err(format!("{} has no account", tx.from));
+        }
+        let expected = self.next_nonce(&tx.from);
+        if tx.nonce < expected {
+            return Err(format!("stale nonce {}: {} has already used it, next is {}", tx.nonce, tx.from, expected));
+        }
+        if tx.nonce > expected {
+            return Err(format!("nonce {} is out of order: next for {} is {}", tx.nonce, tx.from, expected));
+        }
+        if Self::cost(&tx) > 0 && Self::cost(&tx) > self.available_balance(&tx.from) {
+            return Err("insufficient balance".to_string());
+        }
         self.pending.push(tx);
+        Ok(())
+    }
+
+    /// Nonce the next transaction from `address` must carry: its account
+    /// nonce plus what it already has in the mempool.
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        let confirmed = self.accounts.get(address).map_or(0, |a| a.nonce);
+        confirmed + self.pending.iter().filter(|p| p.from == address).count() as u64
+    }
+
+    /// Confirmed balance less what `address` already spends in the mempool.
+    /// Pending inflows don't count, since canonical ordering may apply them
+    /// after the sender's next transfer.
+    pub fn available_balance(&self, address: &str) -> u128 {
+        let balance = self.accounts.get(address).map_or(0, |a| a.balance);
+        let outgoing = self.pending.iter()
+            .filter(|p| p.from == address)
+            .fold(0u128, |sum, p| sum.saturating_add(Self::cost(p)));
+        balance.saturating_sub(outgoing)
+    }
+
+    // who may send system transactions and what they may carry, given the
+    // validator set they are mined under; `from` is already tied to the signing key
+    fn check_system(&self, tx: &Transaction, validators: &[String]) -> Result<(), String> {
+        if tx.class != TxClass::System {
+            return Ok(());
+        }
+        if !matches!(tx.tx_type, TxType::AddValidator | TxType::RemoveValidator) {
+            return Err("only validator set changes can be system transactions".to_string());
+        }
+        if !self.governance.contains(&tx.from) && !validators.contains(&tx.from) {
+            return Err("system transactions must be signed by a governance or validator key".to_string());
+        }
+        Ok(())
+    }
+
+    // rules specific to each transaction type, on top of the envelope checks in `add_transaction`
+    fn check_kind(&self, tx: &Transaction) -> Result<(), String> {
+        match &tx.tx_type {
+            TxType::Transfer => {
+                if tx.to.is_empty() {
+                    return Err("transfers need a recipient".to_string());
+                }
+            }
+            TxType::AddValidator | TxType::RemoveValidator => {
+                if tx.to.is_empty() {
+                    return Err("validator changes need a validator address".to_string());
+                }
+                if tx.amount != 0 {
+                    return Err("validator changes carry no amount".to_string());
+                }
+            }
+            TxType::Coinbase => return Err("coinbase transactions are created by the miner".to_string()),
+            TxType::ReportDoubleSign { height, block_a, block_b } => {
+                let offender = finality::verify_double_sign(*height, block_a, block_b)?;
+                if !self.validators.iter().any(|v| v.eq_ignore_ascii_case(&offender)) {
+                    return Err("reported signer is not a validator".to_string());
+                }
+                if self.pending.iter().any(|p| Self::double_signer(p).is_some_and(|o| o.eq_ignore_ascii_case(&offender))) {
+                    return Err("validator already has a pending double-sign report".to_string());
+                }
+            }
+            TxType::ContractCall { .. } | TxType::ContractDeploy { .. } => {
+                if tx.payload.is_none() {
+                    return Err("contract transactions need a payload".to_string());
+                }
+                if tx.amount != 0 {
+                    return Err("contract transactions carry no amount".to_string());
+                }
+                if matches!(tx.tx_type, TxType::ContractDeploy { .. }) && !tx.to.is_empty() {
+                    return Err("deployments take no recipient".to_string());
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub fn mine_block(&mut self, validator: String) -> Option<Block> {
-        if !self.validators.contains(&validator) {
+    /// Round-robin leader for the next height: `validators[height % len]`.
+    /// `None` without validators.
+    pub fn expected_validator(&self) -> Option<&str> {
+        if self.validators.is_empty() {
             return None;
         }
+        Some(&self.validators[self.chain.len() % self.validators.len()])
+    }
+
+    /// Whether `validator` may produce the next block. A validator the engine
+    /// turns away only because it is not its turn gets a distinct error.
+    pub fn check_producer(&self, validator: &str) -> Result<(), String> {
+        let engine = self.consensus.engine();
+        if engine.can_produce(self, validator) {
+            return Ok(());
+        }
+        match engine.select_producer(self) {
+            Some(leader) if self.validators.iter().any(|v| v == validator) => {
+                Err(format!("out of turn: height {} belongs to {}", self.chain.len(), leader))
+            }
+            _ => Err("invalid validator".to_string()),
+        }
+    }
+
+    /// Produce the next block from all pending transactions. Fails for a
+    /// producer the engine rejects, or if crediting the block would overflow
+    /// a balance; the pending transactions are kept in that case.
+    pub fn mine_block(&mut self, validator: String) -> Result<Block, String> {
+        self.check_producer(&validator)?;
+        let signer = self.signer.clone()
+            .filter(|key| address_from_pubkey(key.verifying_key().as_bytes()).eq_ignore_ascii_case(&validator));
+        if signer.is_none() && self.requires_signed_blocks() {
+            return Err(format!("no key to sign blocks as {}", validator));
+        }
+        let engine = self.consensus.engine();
         let index = (self.chain.len()) as u64;
+        // the slot owner under this engine, if it schedules producers
+        let scheduled = engine.select_producer(self);
         let previous_hash = self.chain.last().unwrap().hash.clone();
         let timestamp = Utc::now().timestamp();
-        let transactions = self.pending.drain(..).collect::<Vec<_>>();
-        // simple nonce and hash (NOT cryptographically secure) for scaffold
-        let nonce = 0u64;
-        let hash = format!("hash:{}:{}:{}", index, previous_hash, timestamp);
-        let block = Block {
+        let rules = self.current_rules();
+        let base_fee = Self::base_fee_after(self.chain.last().unwrap(), &rules);
+        // user transactions priced out by a rising base fee wait in the mempool
+        let (mut transactions, mut deferred): (Vec<_>, Vec<_>) = self.pending.drain(..)
+            .partition(|tx| tx.class == TxClass::System || tx.fee >= base_fee);
+        canonical_order(&mut transactions);
+        // a sender's transactions go in nonce sequence; later ones wait behind
+        // a deferred one, and stale ones are dropped
+        let mut next_nonce: BTreeMap<String, u64> = BTreeMap::new();
+        let mut in_sequence = Vec::with_capacity(transactions.len());
+        for tx in transactions {
+            let next = next_nonce.entry(tx.from.clone())
+                .or_insert_with(|| self.accounts.get(&tx.from).map_or(0, |a| a.nonce));
+            match tx.nonce.cmp(next) {
+                std::cmp::Ordering::Equal => {
+                    *next += 1;
+                    in_sequence.push(tx);
+                }
+                std::cmp::Ordering::Greater => deferred.push(tx),
+                std::cmp::Ordering::Less => {}
+            }
+        }
+        let mut transactions = in_sequence;
+        let system = transactions.iter().take_while(|tx| tx.class == TxClass::System).count();
+        deferred.extend(transactions.split_off(transactions.len().min(rules.max_block_txs.max(system))));
+        let mut bytes = 0;
+        let fits = transactions.iter()
+            .position(|tx| {
+                bytes += encoded_len(tx);
+                tx.class == TxClass::User && bytes > rules.max_block_bytes
+            })
+            .unwrap_or(transactions.len());
+        deferred.extend(transactions.split_off(fits));
+        // a sender an earlier transfer left unable to pay a fee would fail the whole
+        // block on every attempt; evict that transaction and the rest of its sender's queue
+        let evicted = self.unpayable(&transactions);
+        let keep = |tx: &Transaction| evicted.get(&tx.from).is_none_or(|nonce| tx.nonce < *nonce);
+        transactions.retain(keep);
+        deferred.retain(keep);
+        transactions.insert(0, Transaction {
+            from: String::from("coinbase"),
+            to: validator.clone(),
+            amount: Self::expected_coinbase(&rules, base_fee, &transactions),
+            // the height keeps otherwise identical coinbases at distinct hashes
+            payload: Some(format!("height:{}", index)),
+            tx_type: TxType::Coinbase,
+            public_key: None,
+            signature: None,
+            tag: None,
+            class: TxClass::System,
+            fee: 0,
+            nonce: 0,
+        });
+        let hash_algo = self.hash_algo();
+        let merkle_root = merkle_root(hash_algo, &transactions);
+        let bloom = block_bloom(&transactions);
+        let mut accounts = self.accounts.clone();
+        if let Err(e) = Self::apply_transfers(&mut accounts, &transactions) {
+            let mut pending = transactions.split_off(1);
+            pending.extend(deferred);
+            self.restore_pending(pending);
+            return Err(e);
+        }
+        self.accounts = accounts;
+        self.restore_pending(deferred);
+        let state_root = self.state_root();
+        let mut block = Block {
             index,
             previous_hash,
             timestamp,
             transactions,
-            nonce,
-            hash: hash.clone(),
-            validator,
+            merkle_root,
+            state_root,
+            bloom,
+            base_fee,
+            difficulty: 0,
+            nonce: 0,
+            hash: String::new(),
+            validator: String::new(),
+            public_key: None,
+            signature: None,
+            hash_algo,
         };
+        engine.seal_block(&mut block, &validator);
+        if let Some(key) = &signer {
+            block.sign(key);
+        }
+        self.record_production(&validator, scheduled, index);
+        self.record_rules(index);
+        self.execute_contract_calls(&block);
+        self.index_transactions(&block);
         self.chain.push(block.clone());
-        Some(block)
+        // validator set changes take effect from the next height
+        self.apply_validator_changes(&block.transactions);
+        Ok(block)
     }
 
-    pub fn to_file(&self, path: &str) -> Result<(), std::io::Error> {
-        let s = serde_json::to_string_pretty(self).unwrap();
-        fs::write(path, s)
+    // put unmined transactions back in nonce order, the order `revalidate_pending`
+    // resubmits them in; the sort is stable, so senders keep their arrival order
+    fn restore_pending(&mut self, mut txs: Vec<Transaction>) {
+        txs.sort_by_key(|tx| tx.nonce);
+        self.pending = txs;
     }
 
-    pub fn from_file(path: &str) -> Option<Blockchain> {
-        if !Path::new(path).exists() {
-            return None;
+    /// Mine the block at `height`, or return it unchanged if a concurrent
+    /// request already mined that height. Heights past the tip are an error.
+    pub fn mine_block_at(&mut self, validator: String, height: u64) -> Result<Block, String> {
+        if let Some(existing) = self.chain.get(height as usize) {
+            return Ok(existing.clone());
         }
-        let s = fs::read_to_string(path).ok()?;
-        serde_json::from_str(&s).ok()
+        if height != self.chain.len() as u64 {
+            return Err("height is ahead of the chain tip".to_string());
+        }
+        self.mine_block(validator)
     }
-}
-
-#[tokio::main]
-async fn main() {
-    // config: validators and persistence file
-    let validators = vec![String::from("validator-1")];
-    let persist_file = "neonet_chain.json";
 
-    // load existing or create new
-    let bc = if let Some(loaded) = Blockchain::from_file(persist_file) {
-        loaded
-    } else {
-        Blockchain::new(validators.clone())
-    };
-    let state = Arc::new(Mutex::new(bc));
+    fn execute_contract_calls(&mut self, block: &Block) {
+        for tx in &block.transactions {
+            let (TxType::ContractCall { vm } | TxType::ContractDeploy { vm }) = tx.tx_type else {
+                continue;
+            };
+            let execution = match self.runtime.as_mut() {
+                Some(runtime) => runtime.execute(vm, tx),
+                None => Execution { output: "no contract runtime configured".to_string(), ..Execution::default() },
+            };
+            let hash = tx.hash();
+            self.receipts.insert(hash.clone(), TxReceipt::new(hash, block.index, execution));
+        }
+    }
 
-    // POST /tx -> submit transaction
-    let state_filter = warp::any().map(move || Arc::clone(&state));
-    let submit = warp::path("tx")
-        .and(warp::post())
-        .and(warp::body::json())
-        .and(state_filter.clone())
-        .and_then(|tx: Transaction, state: Arc<Mutex<Blockchain>>| async move {
-            let mut s = state.lock().unwrap();
-            s.add_transaction(tx);
-            // persist
-            let _ = s.to_file(persist_file);
-            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({"status":"ok"})))
-        });
+    /// Receipt of a mined contract call or deployment; `None` for other or unknown transactions.
+    pub fn receipt(&self, hash: &str) -> Option<&TxReceipt> {
+        self.receipts.get(hash)
+    }
 
-    // POST /mine -> mine a block with validator in JSON { "validator": "validator-1" }
-    let mine = warp::path("mine")
-        .and(warp::post())
-        .and(warp::body::json())
-        .and(state_filter.clone())
-        .and_then(|body: serde_json::Value, state: Arc<Mutex<Blockchain>>| async move {
-            let validator = body.get("validator").and_then(|v| v.as_str()).unwrap_or("").to_string();
-            let mut s = state.lock().unwrap();
-            match s.mine_block(validator.clone()) {
-                Some(b) => {
-                    let _ = s.to_file(persist_file);
-                    Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({"status":"mined","block":b})))
-                },
-                None => Ok::<_, warp::Rejection>(warp::reply::with_status(warp::reply::json(&serde_json::json!({"error":"invalid validator"})), warp::http::StatusCode::UNAUTHORIZED))
+    fn index_transactions(&mut self, block: &Block) {
+        for (position, tx) in block.transactions.iter().enumerate() {
+            let hash = tx.hash();
+            if let Some(tag) = &tx.tag {
+                self.tag_index.entry(tag.clone()).or_default().push(hash.clone());
             }
-        });
+            self.tx_index.insert(hash, (block.index, position));
+        }
+    }
 
-    // GET /chain -> return full chain
-    let get_chain = warp::path("chain")
-        .and(warp::get())
-        .and(state_filter.clone())
-        .map(|state: Arc<Mutex<Blockchain>>| {
-            let s = state.lock().unwrap();
-            warp::reply::json(&*s)
-        });
+    /// Blocks on top of and including the one holding `hash`; 0 while the
+    /// transaction is still pending, `None` if it is unknown.
+    pub fn confirmations(&self, hash: &str) -> Option<u64> {
+        if let Some((_, block_index, _)) = self.find_transaction(hash) {
+            let tip = self.chain.len() as u64 - 1;
+            return Some(tip - block_index + 1);
+        }
+        self.pending.iter().any(|tx| tx.hash() == hash).then_some(0)
+    }
 
-    // health
-    let health = warp::path("health").and(warp::get()).map(|| warp::reply::json(&serde_json::json!({"status":"ok"})));
+    /// Indices of blocks with a transaction from or to `address`. Blocks whose
+    /// bloom rules the address out are skipped without scanning.
+    pub fn blocks_touching(&self, address: &str) -> Vec<u64> {
+        self.chain.iter()
+            .filter(|b| bloom_contains(&b.bloom, address))
+            .filter(|b| b.transactions.iter().any(|tx| tx.from == address || tx.to == address))
+            .map(|b| b.index)
+            .collect()
+    }
+
+    /// Whether blocks must carry their validator's signature: always on
+    /// permissioned engines, and on proof of work once this node has a key.
+    pub fn requires_signed_blocks(&self) -> bool {
+        self.signer.is_some() || !matches!(self.consensus, ConsensusKind::ProofOfWork { .. })
+    }
 
-    let routes = submit.or(mine).or(get_chain).or(health);
+    /// Rules the next block is produced under.
+    pub fn current_rules(&self) -> ChainRules {
+        ChainRules {
+            validators: self.validators.clone(),
+            block_reward: self.block_reward,
+            max_block_txs: self.max_block_txs,
+            max_block_bytes: self.max_block_bytes,
+            min_base_fee: self.min_base_fee,
+            difficulty: match self.consensus {
+                ConsensusKind::ProofOfWork { difficulty } => difficulty,
+                _ => 0,
+            },
+        }
+    }
 
-    println!("Starting Rust blockchain HTTP API on 127.0.0.1:3030");
-    warp::serve(routes).run(([127,0,0,1], 3030)).await;
+    /// Rules block `height` was produced under; `None` for blocks mined
+    /// before rules were recorded.
+    pub fn rules_at(&self, height: u64) -> Option<&ChainRules> {
+        self.rules.range(..=height).next_back().map(|(_, rules)| rules)
+    }
+
+    fn record_rules(&mut self, height: u64) {
+        let rules = self.current_rules();
+        if self.rules.values().next_back() != Some(&rules) {
+            self.rules.insert(height, rules);
+        }
+    }
+
+    fn record_production(&mut self, producer: &str, scheduled: Option<String>, height: u64) {
+        let perf = self.validator_perf.entry(producer.to_string()).or_default();
+        perf.blocks_produced += 1;
+        perf.last_produced_height = Some(height);
+        if let Some(scheduled) = scheduled.filter(|s| s != producer) {
+            self.validator_perf.entry(scheduled).or_default().blocks_missed += 1;
+        }
+    }
+
+    /// Production record for `address`; `None` if it has never been a
+    /// validator or had a slot.
+    pub fn validator_stats(&self, address: &str) -> Option<ValidatorPerf> {
+        match self.validator_perf.get(address) {
+            Some(perf) => Some(perf.clone()),
+            None => self.validators.iter().any(|v| v == address).then(ValidatorPerf::default),
+        }
+    }
+
+    /// Hashes of mined transactions tagged `tag`, oldest first.
+    pub fn transactions_by_tag(&self, tag: &str) -> Vec<String> {
+        self.tag_index.get(tag).cloned().unwrap_or_default()
+    }
+
+    /// Mined transaction by hash, with its block index and position in that block.
+    pub fn find_transaction(&self, hash: &str) -> Option<(&Transaction, u64, usize)> {
+        let (index, position) = *self.tx_index.get(hash)?;
+        let tx = self.chain.get(index as usize)?.transactions.get(position)?;
+        Some((tx, index, position))
+    }
+
+    pub fn header(&self, index: u64) -> Option<BlockHeader> {
+        self.chain.get(index as usize).map(BlockHeader::from)
+    }
+
+    /// Headers for heights `from..=to`, clamped to the current tip.
+    pub fn headers_range(&self, from: u64, to: u64) -> Vec<BlockHeader> {
+        self.chain
+            .iter()
+            .skip(from as usize)
+            .take(to.saturating_sub(from).saturating_add(1) as usize)
+            .map(BlockHeader::from)
+            .collect()
+    }
+
+    /// Reward under `rules` plus the priority fees in `txs`; the `base_fee`
+    /// part of each fee is burned.
+    pub fn expected_coinbase(rules: &ChainRules, base_fee: u128, txs: &[Transaction]) -> u128 {
+        txs.iter().fold(rules.block_reward, |sum, tx| sum.saturating_add(tx.fee.saturating_sub(base_fee)))
+    }
+
+    /// Base fee of the next block, EIP-1559 style: the target is half of
+    /// `max_block_txs`, and each block moves the fee up to 1/8 toward how far
+    /// its parent was over or under target, never below `min_base_fee`.
+    pub fn next_base_fee(&self) -> u128 {
+        let parent = self.chain.last().unwrap();
+        Self::base_fee_after(parent, &self.current_rules())
+    }
+
+    // base fee of the block after `parent` when it is produced under `rules`
+    fn base_fee_after(parent: &Block, rules: &ChainRules) -> u128 {
+        let target = (rules.max_block_txs / 2).max(1) as u128;
+        // everything but the coinbase; the genesis block has none
+        let used = parent.transactions.len().saturating_sub(1) as u128;
+        let base = parent.base_fee;
+        let next = if used > target {
+            base + (base * (used - target) / target / 8).max(1)
+        } else {
+            base - base * (target - used) / target / 8
+        };
+        next.max(rules.min_base_fee)
+    }
+
+    // address of the key a verified report accuses; reports are checked on submission
+    fn double_signer(tx: &Transaction) -> Option<String> {
+        match &tx.tx_type {
+            TxType::ReportDoubleSign { block_a, .. } => {
+                let key = hex::decode(block_a.public_key.as_ref()?.trim_start_matches("0x")).ok()?;
+                Some(address_from_pubkey(&key))
+            }
+            _ => None,
+        }
+    }
+
+    // what a pending transaction takes from its sender's balance
+    fn cost(tx: &Transaction) -> u128 {
+        let amount = if tx.tx_type == TxType::Transfer { tx.amount } else { 0 };
+        amount.saturating_add(tx.fee)
+    }
+
+    /// Check heights, hash links, merkle roots, block limits, coinbase issuance,
+    /// consensus seals and producers of every mined block.
+    pub fn validate(&self) -> Result<(), String> {
+        let engine = self.consensus.engine();
+        let hash_algo = self.hash_algo();
+        // blocks mined before rules were recorded are held to the current ones
+        let current = self.current_rules();
+        if self.chain[0].index != 0 {
+            return Err(format!("genesis block has index {}", self.chain[0].index));
+        }
+        if self.chain[0].hash != self.chain[0].compute_hash() {
+            return Err("genesis block has an invalid hash".to_string());
+        }
+        for pair in self.chain.windows(2) {
+            let (prev, block) = (&pair[0], &pair[1]);
+            if block.index != prev.index + 1 {
+                return Err(format!("block {} follows block {}", block.index, prev.index));
+            }
+            let recorded = self.rules_at(block.index);
+            let rules = recorded.unwrap_or(&current);
+            if block.previous_hash != prev.hash {
+                return Err(format!("block {} does not link to its parent", block.index));
+            }
+            if block.hash_algo != hash_algo {
+                return Err(format!("block {} uses a different hash algorithm than the genesis block", block.index));
+            }
+            if block.merkle_root != merkle_root(hash_algo, &block.transactions) {
+                return Err(format!("block {} has an invalid merkle root", block.index));
+            }
+            // blocks from before blooms carry none, and their hash commits to that
+            if !block.bloom.is_empty() && block.bloom != block_bloom(&block.transactions) {
+                return Err(format!("block {} has an invalid bloom", block.index));
+            }
+            let (coinbase, rest) = match block.transactions.split_first() {
+                Some((tx, rest)) if tx.tx_type == TxType::Coinbase => (tx, rest),
+                _ => return Err(format!("block {} is missing its coinbase", block.index)),
+            };
+            if rest.iter().any(|tx| tx.tx_type == TxType::Coinbase) {
+                return Err(format!("block {} has more than one coinbase", block.index));
+            }
+            if let Some(err) = rest.iter().find_map(|tx| tx.verify_sender(self.signature_scheme).err()) {
+                return Err(format!("block {} has an unsigned or forged transaction: {}", block.index, err));
+            }
+            if let Some(err) = rest.iter().find_map(|tx| self.check_system(tx, &rules.validators).err()) {
+                return Err(format!("block {} has an invalid system transaction: {}", block.index, err));
+            }
+            let system = rest.iter().filter(|tx| tx.class == TxClass::System);
+            if rest.len() > rules.max_block_txs.max(system.clone().count()) {
+                return Err(format!("block {} has too many transactions", block.index));
+            }
+            let bytes: usize = rest.iter().map(encoded_len).sum();
+            if bytes > rules.max_block_bytes.max(system.map(encoded_len).sum()) {
+                return Err(format!("block {} exceeds the block size limit", block.index));
+            }
+            if block.base_fee != Self::base_fee_after(prev, rules) {
+                return Err(format!("block {} has an invalid base fee", block.index));
+            }
+            if rest.iter().any(|tx| tx.class == TxClass::User && tx.fee < block.base_fee) {
+                return Err(format!("block {} includes a transaction below its base fee", block.index));
+            }
+            if coinbase.to != block.validator || coinbase.amount != Self::expected_coinbase(rules, block.base_fee, rest) {
+                return Err(format!("block {} has an invalid coinbase", block.index));
+            }
+            if recorded.is_some_and(|rules| block.difficulty != rules.difficulty) {
+                return Err(format!("block {} has an invalid difficulty", block.index));
+            }
+            if !engine.verify_seal(block) {
+                return Err(format!("block {} has an invalid seal", block.index));
+            }
+            if recorded.is_some_and(|rules| !engine.verify_producer(block, &rules.validators)) {
+                return Err(format!("block {} was produced by {}, who could not produce it", block.index, block.validator));
+            }
+            // blocks from before rules were recorded may predate signing
+            if block.signature.is_none() && self.requires_signed_blocks() && recorded.is_some() {
+                return Err(format!("block {} is unsigned", block.index));
+            }
+            if block.verify_signature().is_err() {
+                return Err(format!("block {} has an invalid signature", block.index));
+            }
+        }
+        if let Some(checkpoint) = &self.finalized {
+            if !self.matches_chain(checkpoint) {
+                return Err(format!("block {} conflicts with the finalized checkpoint", checkpoint.height));
+            }
+        }
+        Ok(())
+    }
+
+    fn matches_chain(&self, checkpoint: &Checkpoint) -> bool {
+        self.chain.get(checkpoint.height as usize)
+            .is_some_and(|b| b.hash == checkpoint.block_hash && b.state_root == checkpoint.state_root)
+    }
+
+    /// Record a validator's checkpoint signature. Returns `true` when this
+    /// signature completes a quorum and the checkpoint becomes final.
+    pub fn submit_checkpoint(&mut self, signed: SignedCheckpoint) -> Result<bool, String> {
+        signed.verify()?;
+        if !self.validators.iter().any(|v| v.eq_ignore_ascii_case(&signed.validator)) {
+            return Err("signer is not a validator".to_string());
+        }
+        let height = signed.checkpoint.height;
+        if self.finalized.as_ref().is_some_and(|f| height <= f.height) {
+            return Err(format!("height {} is already final", height));
+        }
+        // addresses compare case-insensitively, so one validator is one key however it's spelled
+        let voter = signed.validator.to_ascii_lowercase();
+        let votes = self.checkpoint_votes.entry(height).or_default();
+        match votes.get(&voter) {
+            Some(previous) if *previous != signed.checkpoint => {
+                return Err("validator already signed a different checkpoint at this height".to_string());
+            }
+            Some(_) => return Ok(false),
+            None => {}
+        }
+        votes.insert(voter, signed.checkpoint.clone());
+
+        let signers = votes.values().filter(|c| **c == signed.checkpoint).count();
+        if !finality::has_quorum(signers, self.validators.len()) {
+            return Ok(false);
+        }
+        if !self.matches_chain(&signed.checkpoint) {
+            return Err("quorum checkpoint conflicts with the local chain".to_string());
+        }
+        self.checkpoint_votes = self.checkpoint_votes.split_off(&(height + 1));
+        self.finalized = Some(signed.checkpoint);
+        Ok(true)
+    }
+
+    fn apply_transfers(accounts: &mut BTreeMap<String, Account>, txs: &[Transaction]) -> Result<(), String> {
+        for tx in txs {
+            if tx.tx_type == TxType::Coinbase {
+                Self::credit(accounts, &tx.to, tx.amount)?;
+                continue;
+            }
+            // the coinbase already counts this fee, so one the sender can't pay fails the block
+            let sender = accounts.get_mut(&tx.from)
+                .ok_or_else(|| format!("{} is sent from {}, which has no account", tx.hash(), tx.from))?;
+            if tx.nonce != sender.nonce {
+                return Err(format!("{} has nonce {}, expected {}", tx.hash(), tx.nonce, sender.nonce));
+            }
+            if sender.balance < tx.fee {
+                return Err(format!("{} cannot pay its fee", tx.hash()));
+            }
+            sender.balance -= tx.fee;
+            // every included transaction uses its nonce, even a transfer ignored below
+            sender.nonce += 1;
+            if let Some(offender) = Self::double_signer(tx) {
+                // an offender without an account has nothing to slash
+                let slashed = accounts.iter_mut()
+                    .find(|(address, _)| address.eq_ignore_ascii_case(&offender))
+                    .map_or(0, |(_, account)| {
+                        let slashed = account.balance * finality::DOUBLE_SIGN_SLASH_BPS / 10_000;
+                        account.balance -= slashed;
+                        slashed
+                    });
+                Self::credit(accounts, &tx.from, slashed * finality::REPORTER_REWARD_BPS / 10_000)?;
+                continue;
+            }
+            if tx.tx_type != TxType::Transfer {
+                continue;
+            }
+            // transfers the sender can't cover are ignored
+            if sender.balance < tx.amount {
+                continue;
+            }
+            sender.balance -= tx.amount;
+            Self::credit(accounts, &tx.to, tx.amount)?;
+        }
+        Ok(())
+    }
+
+    // sender -> nonce of its first transaction in `txs` whose fee it can't pay
+    // once those before it are applied, or that it sends without an account
+    fn unpayable(&self, txs: &[Transaction]) -> BTreeMap<String, u64> {
+        let mut accounts = self.accounts.clone();
+        let mut evicted = BTreeMap::new();
+        for tx in txs {
+            if evicted.contains_key(&tx.from) {
+                continue;
+            }
+            if accounts.get(&tx.from).is_none_or(|a| a.balance < tx.fee) {
+                evicted.insert(tx.from.clone(), tx.nonce);
+                continue;
+            }
+            // an overflowing credit fails the block regardless; `mine_block` reports it
+            if Self::apply_transfers(&mut accounts, std::slice::from_ref(tx)).is_err() {
+                break;
+            }
+        }
+        evicted
+    }
+
+    fn credit(accounts: &mut BTreeMap<String, Account>, address: &str, amount: u128) -> Result<(), String> {
+        let account = accounts.entry(address.to_string()).or_default();
+        account.balance = account.balance.checked_add(amount)
+            .ok_or_else(|| format!("balance overflow crediting {}", address))?;
+        Ok(())
+    }
+
+    /// SHA-256 over the address-ordered account map.
+    pub fn state_root(&self) -> String {
+        let mut hasher = Sha256::new();
+        for (address, account) in &self.accounts {
+            hasher.update(address.as_bytes());
+            hasher.update(account.balance.to_be_bytes());
+            hasher.update(account.nonce.to_be_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    pub fn snapshot_state(&self) -> StateSnapshot {
+        self.snapshot_page(None, usize::MAX)
+    }
+
+    /// Up to `limit` accounts with addresses strictly after `after`. A limit
+    /// of 0 counts as 1, so every page moves the cursor forward.
+    pub fn snapshot_page(&self, after: Option<&str>, limit: usize) -> StateSnapshot {
+        let limit = limit.max(1);
+        let mut remaining = self.accounts.iter().filter(|(a, _)| after.is_none_or(|after| a.as_str() > after));
+        let accounts: BTreeMap<String, Account> = remaining
+            .by_ref()
+            .take(limit)
+            .map(|(a, acc)| (a.clone(), acc.clone()))
+            .collect();
+        let next = if remaining.next().is_some() {
+            accounts.keys().next_back().cloned()
+        } else {
+            None
+        };
+        StateSnapshot {
+            height: self.chain.len() as u64 - 1,
+            state_root: self.state_root(),
+            accounts,
+            next,
+        }
+    }
+
+    fn apply_validator_changes(&mut self, txs: &[Transaction]) {
+        for tx in txs {
+            if let Some(offender) = Self::double_signer(tx) {
+                self.validators.retain(|v| !v.eq_ignore_ascii_case(&offender));
+                continue;
+            }
+            if !matches!(tx.tx_type, TxType::AddValidator | TxType::RemoveValidator) || !self.governance.contains(&tx.from) {
+                continue;
+            }
+            match tx.tx_type {
+                TxType::AddValidator => {
+                    if !self.validators.contains(&tx.to) {
+                        self.validators.push(tx.to.clone());
+                    }
+                }
+                TxType::RemoveValidator => self.validators.retain(|v| v != &tx.to),
+                TxType::Transfer
+                | TxType::Coinbase
+                | TxType::ReportDoubleSign { .. }
+                | TxType::ContractCall { .. }
+                | TxType::ContractDeploy { .. } => {}
+            }
+        }
+    }
+
+    /// Restore the chain saved in `storage`, or start from `fresh()` if it
+    /// holds none; `persist` then writes back to `storage`. The saved mempool
+    /// is re-validated against the restored state.
+    pub fn open(storage: Box<dyn Storage>, fresh: impl FnOnce() -> Blockchain) -> Result<Blockchain, String> {
+        let mut bc = match storage.load()? {
+            Some(mut bc) => {
+                for block in bc.chain.clone() {
+                    bc.index_transactions(&block);
+                }
+                bc.revalidate_pending();
+                bc
+            }
+            None => fresh(),
+        };
+        // chains saved before the fingerprint existed take it from their current config
+        bc.genesis_hash = bc.genesis_hash();
+        bc.storage = Some(storage);
+        Ok(bc)
+    }
+
+    /// Resubmit every pending transaction in order, dropping those already
+    /// mined or no longer accepted. Returns the dropped hashes with the reason.
+    pub fn revalidate_pending(&mut self) -> Vec<(String, String)> {
+        let mut dropped = vec![];
+        let mut pending = std::mem::take(&mut self.pending);
+        // mempools saved before it was kept in nonce order would otherwise lose their later nonces
+        pending.sort_by_key(|tx| tx.nonce);
+        for tx in pending {
+            let hash = tx.hash();
+            let result = if self.tx_index.contains_key(&hash) {
+                Err("already mined".to_string())
+            } else {
+                self.add_transaction(tx)
+            };
+            if let Err(e) = result {
+                dropped.push((hash, e));
+            }
+        }
+        dropped
+    }
+
+    pub fn persist(&mut self) -> Result<(), String> {
+        let Some(mut storage) = self.storage.take() else {
+            return Ok(());
+        };
+        let result = storage.save(self);
+        self.storage = Some(storage);
+        result
+    }
+}
+
+fn tx_lookup_reply(bc: &Blockchain, hash: &str) -> Result<warp::reply::Json, ApiError> {
+    let (tx, block_index, position) = bc.find_transaction(hash)
+        .ok_or_else(|| ApiError::not_found("transaction not found"))?;
+    Ok(warp::reply::json(&serde_json::json!({"hash": hash, "block_index": block_index, "position": position, "transaction": tx})))
+}
+
+fn confirmations_reply(bc: &Blockchain, hash: &str) -> Result<warp::reply::Json, ApiError> {
+    let confirmations = bc.confirmations(hash)
+        .ok_or_else(|| ApiError::not_found("transaction not found"))?;
+    Ok(warp::reply::json(&serde_json::json!({"hash": hash, "confirmations": confirmations})))
+}
+
+const WAIT_FOR_BLOCK_DEFAULT_MS: u64 = 30_000;
+const WAIT_FOR_BLOCK_MAX_MS: u64 = 120_000;
+
+/// All HTTP routes; failures reject with `ApiError` and render through `api::recover`.
+fn routes(state: Arc<Mutex<Blockchain>>) -> impl Filter<Extract = (impl warp::Reply,), Error = std::convert::Infallible> + Clone {
+    // numbered from the chain, so a restarted node doesn't reuse sequence numbers
+    let events = EventLog::from_chain(&state.lock().unwrap().chain, events::EVENT_LOG_CAPACITY);
+    // POST /tx -> submit transaction
+    let state_filter = warp::any().map(move || Arc::clone(&state));
+    // woken on every mined block, for /wait-for-block and /events
+    let new_block = Arc::new(Notify::new());
+    let new_block_filter = warp::any().map(move || Arc::clone(&new_block));
+    let events = Arc::new(Mutex::new(events));
+    let events_filter = warp::any().map(move || Arc::clone(&events));
+    // JSON body of a privileged request, once `api::check_operator` accepts its signature
+    let seen_nonces = Arc::new(Mutex::new(api::SeenNonces::default()));
+    let operator_headers = warp::header::optional::<String>(api::OPERATOR_KEY_HEADER)
+        .and(warp::header::optional::<String>(api::OPERATOR_SIGNATURE_HEADER))
+        .and(warp::header::optional::<String>(api::OPERATOR_TIMESTAMP_HEADER))
+        .and(warp::header::optional::<String>(api::OPERATOR_NONCE_HEADER))
+        .map(|key, signature, timestamp, nonce| api::OperatorHeaders { key, signature, timestamp, nonce });
+    let operator_body = operator_headers
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(warp::body::bytes())
+        .and(state_filter.clone())
+        .and(warp::any().map(move || Arc::clone(&seen_nonces)))
+        .and_then(|headers: api::OperatorHeaders, method: warp::http::Method, path: warp::path::FullPath, body: warp::hyper::body::Bytes,
+                   state: Arc<Mutex<Blockchain>>, seen: Arc<Mutex<api::SeenNonces>>| async move {
+            let operators = state.lock().unwrap().operators.clone();
+            let now = Utc::now().timestamp();
+            api::check_operator(&operators, &headers, method.as_str(), path.as_str(), &body, &mut seen.lock().unwrap(), now)
+                .map_err(warp::reject::custom)?;
+            serde_json::from_slice::<serde_json::Value>(&body).map_err(|e| warp::reject::custom(ApiError::bad_request(e.to_string())))
+        });
+    let submit = warp::path("tx")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(state_filter.clone())
+        .and_then(move |tx: Transaction, state: Arc<Mutex<Blockchain>>| async move {
+            let mut s = state.lock().unwrap();
+            s.add_transaction(tx).map_err(|e| warp::reject::custom(ApiError::bad_request(e)))?;
+            // persist
+            let _ = s.persist();
+            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({"status":"ok"})))
+        });
+
+    // POST /mine -> mine a block with validator in JSON { "validator": "validator-1", "height": 5 }
+    // `height` defaults to the tip seen on arrival; retries for a mined height return that block.
+    // Operator-signed once the chain has operators.
+    let mine = warp::path("mine")
+        .and(warp::post())
+        .and(operator_body.clone())
+        .and(state_filter.clone())
+        .and(new_block_filter.clone())
+        .and(events_filter.clone())
+        .and_then(move |body: serde_json::Value, state: Arc<Mutex<Blockchain>>, new_block: Arc<Notify>, events: Arc<Mutex<EventLog>>| async move {
+            let validator = match body.get("validator").and_then(|v| v.as_str()) {
+                Some(v) => v.to_string(),
+                None => {
+                    let s = state.lock().unwrap();
+                    s.consensus.engine().select_producer(&s).unwrap_or_default()
+                }
+            };
+            let height = match body.get("height").and_then(|h| h.as_u64()) {
+                Some(h) => h,
+                None => state.lock().unwrap().chain.len() as u64,
+            };
+            let mut s = state.lock().unwrap();
+            if height > s.chain.len() as u64 {
+                return Err(warp::reject::custom(ApiError::bad_request("height is ahead of the chain tip")));
+            }
+            if height == s.chain.len() as u64 {
+                s.check_producer(&validator).map_err(|e| warp::reject::custom(ApiError::unauthorized(e)))?;
+            }
+            let fresh = height == s.chain.len() as u64;
+            let b = s.mine_block_at(validator, height).map_err(|e| warp::reject::custom(ApiError::bad_request(e)))?;
+            let _ = s.persist();
+            // a retry for an already mined height is not a new event
+            if fresh {
+                events.lock().unwrap().record_block(&b);
+            }
+            new_block.notify_waiters();
+            Ok(warp::reply::json(&serde_json::json!({"status":"mined","block":b})))
+        });
+
+    // GET /next-validator -> producer expected at the next height; null when the engine schedules none
+    let next_validator = warp::path("next-validator")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .map(|state: Arc<Mutex<Blockchain>>| {
+            let s = state.lock().unwrap();
+            warp::reply::json(&serde_json::json!({
+                "height": s.chain.len(),
+                "validator": s.consensus.engine().select_producer(&s),
+            }))
+        });
+
+    // POST /difficulty -> retarget proof of work with JSON { "difficulty": 3 }; operator-signed like /mine
+    let set_difficulty = warp::path("difficulty")
+        .and(warp::post())
+        .and(operator_body.clone())
+        .and(state_filter.clone())
+        .and_then(|body: serde_json::Value, state: Arc<Mutex<Blockchain>>| async move {
+            let difficulty = body.get("difficulty").and_then(|d| d.as_u64()).and_then(|d| u32::try_from(d).ok())
+                .ok_or_else(|| warp::reject::custom(ApiError::bad_request("difficulty must be a non-negative integer")))?;
+            let mut s = state.lock().unwrap();
+            s.set_difficulty(difficulty).map_err(|e| warp::reject::custom(ApiError::bad_request(e)))?;
+            let _ = s.persist();
+            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({"status":"ok","consensus":s.consensus})))
+        });
+
+    // GET /wait-for-block?current=<height>&timeout_ms=<ms> -> latest height once it passes `current`,
+    // or the unchanged height when the timeout elapses first
+    let wait_for_block = warp::path("wait-for-block")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(state_filter.clone())
+        .and(new_block_filter.clone())
+        .and_then(|q: HashMap<String, String>, state: Arc<Mutex<Blockchain>>, new_block: Arc<Notify>| async move {
+            let current: u64 = q.get("current").and_then(|c| c.parse().ok())
+                .ok_or_else(|| warp::reject::custom(ApiError::bad_request("current height is required")))?;
+            let timeout_ms = q.get("timeout_ms").and_then(|t| t.parse().ok()).unwrap_or(WAIT_FOR_BLOCK_DEFAULT_MS);
+            let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout_ms.min(WAIT_FOR_BLOCK_MAX_MS));
+            loop {
+                // register before reading the height so a block mined in between still wakes us
+                let notified = new_block.notified();
+                let height = state.lock().unwrap().chain.len() as u64 - 1;
+                if height > current {
+                    return Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({"height": height, "timed_out": false})));
+                }
+                if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                    return Ok(warp::reply::json(&serde_json::json!({"height": height, "timed_out": true})));
+                }
+            }
+        });
+
+    // GET /events?since=<seq>&timeout_ms=<ms> -> recent events after `since`, waiting like
+    // /wait-for-block when there are none yet; pass the last `seq` received to resume without gaps
+    let get_events = warp::path("events")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(events_filter)
+        .and(new_block_filter)
+        .and_then(|q: HashMap<String, String>, events: Arc<Mutex<EventLog>>, new_block: Arc<Notify>| async move {
+            let since: u64 = q.get("since").and_then(|s| s.parse().ok()).unwrap_or(0);
+            let timeout_ms = q.get("timeout_ms").and_then(|t| t.parse().ok()).unwrap_or(WAIT_FOR_BLOCK_DEFAULT_MS);
+            let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout_ms.min(WAIT_FOR_BLOCK_MAX_MS));
+            loop {
+                let notified = new_block.notified();
+                let (batch, missed, latest) = {
+                    let log = events.lock().unwrap();
+                    let (batch, missed) = log.since(since);
+                    (batch, missed, log.latest())
+                };
+                let timed_out = batch.is_empty() && tokio::time::timeout_at(deadline, notified).await.is_err();
+                if !batch.is_empty() || timed_out {
+                    // `missed`: events after `since` were already evicted from the log
+                    return Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
+                        "events": batch, "latest": latest, "missed": missed, "timed_out": timed_out,
+                    })));
+                }
+            }
+        });
+
+    // GET /tx/{hash} -> mined transaction with its block index and position
+    let get_tx = warp::path!("tx" / String)
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(|hash: String, state: Arc<Mutex<Blockchain>>| async move {
+            let s = state.lock().unwrap();
+            tx_lookup_reply(&s, &hash).map_err(warp::reject::custom)
+        });
+
+    // GET /receipt/{hash} -> execution outcome of a mined contract call
+    let get_receipt = warp::path!("receipt" / String)
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(|hash: String, state: Arc<Mutex<Blockchain>>| async move {
+            let s = state.lock().unwrap();
+            s.receipt(&hash)
+                .map(warp::reply::json)
+                .ok_or_else(|| warp::reject::custom(ApiError::not_found("receipt not found")))
+        });
+
+    // GET /tx/{hash}/confirmations -> depth of the containing block, 0 while pending
+    let get_confirmations = warp::path!("tx" / String / "confirmations")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(|hash: String, state: Arc<Mutex<Blockchain>>| async move {
+            let s = state.lock().unwrap();
+            confirmations_reply(&s, &hash).map_err(warp::reject::custom)
+        });
+
+    // GET /tx/by-tag/{tag} -> hashes of mined transactions with that tag
+    let get_by_tag = warp::path!("tx" / "by-tag" / String)
+        .and(warp::get())
+        .and(state_filter.clone())
+        .map(|tag: String, state: Arc<Mutex<Blockchain>>| {
+            let s = state.lock().unwrap();
+            warp::reply::json(&s.transactions_by_tag(&tag))
+        });
+
+    // peers name their genesis hash; a different one means a different network
+    let peer_genesis = warp::header::optional::<String>(api::GENESIS_HEADER)
+        .and(state_filter.clone())
+        .and_then(|theirs: Option<String>, state: Arc<Mutex<Blockchain>>| async move {
+            match theirs {
+                Some(theirs) => {
+                    let ours = state.lock().unwrap().genesis_hash();
+                    api::check_peer_genesis(&ours, &theirs).map_err(warp::reject::custom)
+                }
+                None => Ok(()),
+            }
+        })
+        .untuple_one();
+
+    // POST /checkpoint -> validator-signed checkpoint; a 2/3 quorum finalizes its height
+    let submit_checkpoint = warp::path("checkpoint")
+        .and(warp::post())
+        .and(api::peer_version())
+        .and(peer_genesis.clone())
+        .and(warp::body::json())
+        .and(state_filter.clone())
+        .and_then(move |signed: SignedCheckpoint, state: Arc<Mutex<Blockchain>>| async move {
+            let mut s = state.lock().unwrap();
+            let finalized = s.submit_checkpoint(signed).map_err(|e| warp::reject::custom(ApiError::bad_request(e)))?;
+            let _ = s.persist();
+            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({"status":"ok","finalized":finalized,"final":s.finalized})))
+        });
+
+    // GET /validator/{address} -> blocks produced/missed and last produced height
+    let get_validator = warp::path!("validator" / String)
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(|address: String, state: Arc<Mutex<Blockchain>>| async move {
+            let s = state.lock().unwrap();
+            let perf = s.validator_stats(&address).ok_or_else(|| warp::reject::custom(ApiError::not_found("unknown validator")))?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&perf))
+        });
+
+    // GET /chain -> return full chain
+    let get_chain = warp::path("chain")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .map(|state: Arc<Mutex<Blockchain>>| {
+            let s = state.lock().unwrap();
+            warp::reply::json(&*s)
+        });
+
+    // GET /state?after=<address>&limit=<n> -> account map with state root
+    let get_state = warp::path("state")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(state_filter.clone())
+        .map(|q: HashMap<String, String>, state: Arc<Mutex<Blockchain>>| {
+            let limit = q.get("limit").and_then(|l| l.parse().ok()).unwrap_or(usize::MAX);
+            let s = state.lock().unwrap();
+            warp::reply::json(&s.snapshot_page(q.get("after").map(|a| a.as_str()), limit))
+        });
+
+    // GET /balance/{address} -> confirmed balance and nonce, and what is left after pending spends
+    let get_balance = warp::path!("balance" / String)
+        .and(warp::get())
+        .and(state_filter.clone())
+        .map(|address: String, state: Arc<Mutex<Blockchain>>| {
+            let s = state.lock().unwrap();
+            let account = s.accounts.get(&address).cloned().unwrap_or_default();
+            warp::reply::json(&serde_json::json!({
+                "address": address,
+                "balance": account.balance,
+                "nonce": account.nonce,
+                "next_nonce": s.next_nonce(&address),
+                "available": s.available_balance(&address),
+            }))
+        });
+
+    // GET /headers?from=<height>&to=<height> -> block headers without bodies
+    let get_headers = warp::path("headers")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(state_filter.clone())
+        .map(|q: HashMap<String, String>, state: Arc<Mutex<Blockchain>>| {
+            let from = q.get("from").and_then(|f| f.parse().ok()).unwrap_or(0);
+            let to = q.get("to").and_then(|t| t.parse().ok()).unwrap_or(u64::MAX);
+            let s = state.lock().unwrap();
+            warp::reply::json(&s.headers_range(from, to))
+        });
+
+    // GET /block/{height} -> one full block
+    let get_block = warp::path!("block" / u64)
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(|height: u64, state: Arc<Mutex<Blockchain>>| async move {
+            let s = state.lock().unwrap();
+            let block = s.chain.get(height as usize).ok_or_else(|| warp::reject::custom(ApiError::not_found("block not found")))?;
+            Ok::<_, warp::Rejection>(warp::reply::json(block))
+        });
+
+    // GET /chain/head -> the latest block
+    let get_head = warp::path!("chain" / "head")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(|state: Arc<Mutex<Blockchain>>| async move {
+            let s = state.lock().unwrap();
+            let block = s.chain.last().ok_or_else(|| warp::reject::custom(ApiError::not_found("chain is empty")))?;
+            Ok::<_, warp::Rejection>(warp::reply::json(block))
+        });
+
+    // GET /block/{height}/compact -> header, coinbase and the hashes of the other transactions
+    let get_compact = warp::path!("block" / u64 / "compact")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(|height: u64, state: Arc<Mutex<Blockchain>>| async move {
+            let compact = state.lock().unwrap().chain.get(height as usize).map(CompactBlock::from)
+                .ok_or_else(|| warp::reject::custom(ApiError::not_found("block not found")))?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&compact))
+        });
+
+    // GET /block/{height}/raw -> the block's canonical bytes; hashing them gives its `hash`
+    let get_raw = warp::path!("block" / u64 / "raw")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(|height: u64, state: Arc<Mutex<Blockchain>>| async move {
+            let s = state.lock().unwrap();
+            let block = s.chain.get(height as usize).ok_or_else(|| warp::reject::custom(ApiError::not_found("block not found")))?;
+            Ok::<_, warp::Rejection>(warp::reply::with_header(block.canonical_bytes(), "content-type", "application/octet-stream"))
+        });
+
+    // POST /block/reconstruct -> rebuild a peer's compact block from the local mempool,
+    // or list the transaction hashes this node is missing
+    let reconstruct = warp::path!("block" / "reconstruct")
+        .and(warp::post())
+        .and(api::peer_version())
+        .and(peer_genesis)
+        .and(warp::body::json())
+        .and(state_filter.clone())
+        .map(|compact: CompactBlock, state: Arc<Mutex<Blockchain>>| {
+            let s = state.lock().unwrap();
+            match compact::reconstruct_block(&compact, &s.pending) {
+                Ok(block) => warp::reply::json(&serde_json::json!({"complete": true, "block": block})),
+                Err(missing) => warp::reply::json(&serde_json::json!({"complete": false, "missing": missing})),
+            }
+        });
+
+    // GET /status -> protocol version, supported features and the active configuration
+    let status = warp::path("status")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .map(|state: Arc<Mutex<Blockchain>>| {
+            let s = state.lock().unwrap();
+            warp::reply::json(&serde_json::json!({
+                "protocol_version": api::NEONET_PROTOCOL_VERSION,
+                "features": api::SUPPORTED_FEATURES,
+                "chain_id": s.chain_id,
+                "genesis_hash": s.genesis_hash(),
+                "consensus": s.consensus,
+                "signature_scheme": s.signature_scheme,
+                "height": s.chain.len() as u64 - 1,
+                "base_fee": s.next_base_fee(),
+            }))
+        });
+
+    // GET /validate -> {"valid": true}, or the first problem found in the stored chain
+    let validate = warp::path("validate")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .map(|state: Arc<Mutex<Blockchain>>| {
+            let s = state.lock().unwrap();
+            let body = match s.validate() {
+                Ok(()) => serde_json::json!({"valid": true}),
+                Err(e) => serde_json::json!({"valid": false, "error": e}),
+            };
+            warp::reply::json(&body)
+        });
+
+    // health
+    let health = warp::path("health").and(warp::get()).map(|| warp::reply::json(&serde_json::json!({"status":"ok"})));
+
+    submit.or(mine).or(next_validator).or(set_difficulty).or(get_tx).or(get_receipt).or(get_confirmations).or(get_by_tag).or(submit_checkpoint).or(get_validator).or(get_head).or(get_chain).or(get_state).or(get_balance).or(get_headers).or(get_block).or(get_compact).or(get_raw).or(reconstruct).or(wait_for_block).or(get_events).or(validate).or(status).or(health)
+        .recover(api::recover)
+        .with(warp::reply::with::header(api::VERSION_HEADER, api::NEONET_PROTOCOL_VERSION))
+}
+
+#[tokio::main]
+async fn main() {
+    // NEONET_KEYSTORE (default ./keystore) holds validator keys sealed with NEONET_KEY_PASSPHRASE
+    let keystore = Keystore::new(std::env::var("NEONET_KEYSTORE").unwrap_or_else(|_| "keystore".to_string()));
+    let passphrase = || std::env::var("NEONET_KEY_PASSPHRASE").expect("NEONET_KEY_PASSPHRASE must be set");
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        // `keygen` -> new key; `import <secret-hex>` -> store an existing one; `list` -> stored addresses
+        Some("keygen") => {
+            println!("{}", keystore.generate(&passphrase()).expect("generating key"));
+            return;
+        }
+        Some("import") => {
+            let secret = args.get(2).expect("usage: import <secret-hex>");
+            println!("{}", keystore.import(secret, &passphrase()).expect("importing key"));
+            return;
+        }
+        Some("list") => {
+            for address in keystore.list().expect("reading keystore") {
+                println!("{}", address);
+            }
+            return;
+        }
+        Some(other) => panic!("unknown command {}; expected keygen, import or list", other),
+        None => {}
+    }
+
+    // NEONET_VALIDATOR_KEY=<address> signs blocks mined as that validator; a
+    // fresh chain starts with it as the only validator. Without it this node
+    // can only produce proof-of-work blocks.
+    let signer = std::env::var("NEONET_VALIDATOR_KEY").ok()
+        .map(|address| keystore.unlock(&address, &passphrase()).expect("unlocking validator key"));
+
+    // config: validators, governance and persistence backend
+    let validators = vec![signer.as_ref()
+        .map_or_else(|| String::from("validator-1"), |key| address_from_pubkey(key.verifying_key().as_bytes()))];
+    let governance = vec![String::from("governance-1")];
+    let block_reward = 50;
+    // NEONET_MIN_BASE_FEE sets the floor the dynamic base fee can't fall below
+    let min_base_fee = std::env::var("NEONET_MIN_BASE_FEE").ok().and_then(|f| f.parse().ok()).unwrap_or(0);
+    // validators take turns by default; NEONET_CONSENSUS=authority lets any of them
+    // produce, and NEONET_CONSENSUS=pow switches to proof of work
+    let consensus = match std::env::var("NEONET_CONSENSUS").as_deref() {
+        Ok("pow") => ConsensusKind::ProofOfWork {
+            difficulty: std::env::var("NEONET_POW_DIFFICULTY").ok().and_then(|d| d.parse().ok())
+                .filter(|d| *d <= MAX_DIFFICULTY)
+                .unwrap_or(4),
+        },
+        Ok("authority") => ConsensusKind::Authority,
+        _ => ConsensusKind::RoundRobin,
+    };
+    // NEONET_SIGNATURES=hybrid requires Ed25519 + Dilithium3 on signed transactions
+    let signature_scheme = match std::env::var("NEONET_SIGNATURES").as_deref() {
+        Ok("hybrid") => SignatureScheme::Hybrid,
+        _ => SignatureScheme::Ed25519,
+    };
+
+    // NEONET_STORAGE=sled|memory replaces the default neonet_chain.json
+    let storage: Box<dyn Storage> = match std::env::var("NEONET_STORAGE").as_deref() {
+        Ok("sled") => Box::new(SledStore::open("neonet_chain.sled").expect("opening neonet_chain.sled")),
+        Ok("memory") => Box::new(MemoryStore::default()),
+        _ => Box::new(JsonFileStore::new("neonet_chain.json")),
+    };
+
+    // load existing or create new
+    let mut bc = Blockchain::open(storage, || {
+        let mut bc = Blockchain::with_governance(validators.clone(), governance.clone());
+        // NEONET_CHAIN_ID names the network (default "neonet"); nodes on different ids never peer
+        if let Ok(chain_id) = std::env::var("NEONET_CHAIN_ID") {
+            bc.chain_id = chain_id;
+        }
+        bc.block_reward = block_reward;
+        bc.min_base_fee = min_base_fee;
+        bc.consensus = consensus;
+        bc.signature_scheme = signature_scheme;
+        // NEONET_OPERATORS lists the comma-separated addresses allowed to sign /mine and /difficulty
+        if let Ok(operators) = std::env::var("NEONET_OPERATORS") {
+            bc.operators = operators.split(',').map(str::trim).filter(|o| !o.is_empty()).map(String::from).collect();
+        }
+        // NEONET_GENESIS_ALLOC names a JSON file of {"address": balance} funded at genesis
+        if let Ok(path) = std::env::var("NEONET_GENESIS_ALLOC") {
+            let alloc = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("reading {}: {}", path, e));
+            let alloc = serde_json::from_str(&alloc).unwrap_or_else(|e| panic!("parsing {}: {}", path, e));
+            bc.set_genesis_allocation(alloc).expect("fresh chain");
+        }
+        // NEONET_GENESIS_TIME dates the genesis block (unix seconds); every node of a network must agree
+        if let Ok(time) = std::env::var("NEONET_GENESIS_TIME") {
+            let time = time.parse().unwrap_or_else(|e| panic!("parsing NEONET_GENESIS_TIME: {}", e));
+            bc.set_genesis_time(time).expect("fresh chain");
+        }
+        // NEONET_HASH=blake3 hashes blocks and merkle roots with Blake3 instead of SHA-256
+        if std::env::var("NEONET_HASH").as_deref() == Ok("blake3") {
+            bc.set_hash_algo(HashAlgo::Blake3).expect("fresh chain");
+        }
+        bc
+    }).expect("loading chain");
+    bc.signer = signer;
+    let state = Arc::new(Mutex::new(bc));
+
+    println!("Starting Rust blockchain HTTP API on 127.0.0.1:3030");
+    warp::serve(routes(state)).run(([127,0,0,1], 3030)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validator_tx(from: &str, validator: &str, tx_type: TxType) -> Transaction {
+        Transaction {
+            from: from.to_string(),
+            to: validator.to_string(),
+            amount: 0,
+            payload: None,
+            public_key: None,
+            signature: None,
+            tx_type,
+            tag: None,
+            class: TxClass::User,
+            fee: 0,
+            nonce: 0,
+        }
+    }
+
+    #[test]
+    fn authorized_add_validator_takes_effect_next_height() {
+        let mut bc = Blockchain::with_governance(vec![addr("v1")], vec![addr("gov")]);
+        bc.accounts.insert(addr("gov"), Account { balance: 0, nonce: 0 });
+        bc.add_transaction(signed(validator_tx(&addr("gov"), &addr("v2"), TxType::AddValidator))).unwrap();
+        assert!(mine(&mut bc, "v2").is_err());
+        assert!(mine(&mut bc, "v1").is_ok());
+        assert_eq!(bc.validators, vec![addr("v1"), addr("v2")]);
+        assert!(mine(&mut bc, "v2").is_ok());
+
+        bc.add_transaction(signed(Transaction { nonce: 1, ..validator_tx(&addr("gov"), &addr("v1"), TxType::RemoveValidator) })).unwrap();
+        mine(&mut bc, "v2").unwrap();
+        assert!(mine(&mut bc, "v1").is_err());
+    }
+
+    // have the named validator claim `block`, redoing its coinbase, roots, hash and signature
+    fn reassign(block: &mut Block, name: &str) {
+        block.validator = addr(name);
+        block.transactions[0].to = addr(name);
+        block.merkle_root = merkle_root(block.hash_algo, &block.transactions);
+        block.bloom = block_bloom(&block.transactions);
+        block.hash = block.compute_hash();
+        block.sign(&key(name));
+    }
+
+    #[test]
+    fn producers_are_checked_against_the_validator_set_of_their_height() {
+        let mut bc = Blockchain::with_governance(vec![addr("v1"), addr("v2")], vec![addr("gov")]);
+        bc.accounts.insert(addr("gov"), Account { balance: 0, nonce: 0 });
+        mine(&mut bc, "v2").unwrap();
+        bc.add_transaction(signed(validator_tx(&addr("gov"), &addr("v2"), TxType::RemoveValidator))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        mine(&mut bc, "v1").unwrap();
+        // v2's block predates its removal
+        assert_eq!(bc.validate(), Ok(()));
+
+        reassign(&mut bc.chain[3], "v2");
+        assert_eq!(bc.validate(), Err(format!("block 3 was produced by {}, who could not produce it", addr("v2"))));
+        // chains saved before rules were recorded can't be checked this way
+        bc.rules.clear();
+        assert!(bc.validate().is_ok());
+    }
+
+    fn transfer(from: &str, to: &str, amount: u128) -> Transaction {
+        Transaction {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            payload: None,
+            public_key: None,
+            signature: None,
+            tx_type: TxType::Transfer,
+            tag: None,
+            class: TxClass::User,
+            fee: 0,
+            nonce: 0,
+        }
+    }
+
+    // test accounts are the addresses of keys derived from their names
+    const ACCOUNTS: [&str; 15] = [
+        "alice", "bob", "carol", "dave", "erin", "frank", "gov", "mallory", "nobody", "outsider", "reporter", "whale",
+        "v1", "v2", "v3",
+    ];
+
+    fn key(name: &str) -> ed25519_dalek::SigningKey {
+        ed25519_dalek::SigningKey::from_bytes(&Sha256::digest(name.as_bytes()).into())
+    }
+
+    fn addr(name: &str) -> String {
+        address_from_pubkey(key(name).verifying_key().as_bytes())
+    }
+
+    // mine as the named validator, signing with its key as a node holding it would
+    fn mine(bc: &mut Blockchain, name: &str) -> Result<Block, String> {
+        bc.signer = Some(key(name));
+        bc.mine_block(addr(name))
+    }
+
+    // signs as whichever test account `tx.from` is; other senders are left as they are
+    fn signed(mut tx: Transaction) -> Transaction {
+        if tx.signature.is_none() {
+            if let Some(name) = ACCOUNTS.iter().find(|n| addr(n) == tx.from) {
+                sign_classical(&mut tx, &key(name));
+            }
+        }
+        tx
+    }
+
+    #[test]
+    fn state_snapshot_matches_mined_transfers() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 100, nonce: 0 });
+        let genesis_root = bc.state_root();
+
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 30))).unwrap();
+        bc.add_transaction(signed(Transaction { nonce: 1, ..transfer(&addr("alice"), &addr("carol"), 20) })).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        bc.add_transaction(signed(transfer(&addr("bob"), &addr("carol"), 10))).unwrap();
+        // overdraft is refused at submission and ignored if it reaches a block,
+        // though it still uses up its nonce
+        assert_eq!(bc.add_transaction(signed(transfer(&addr("carol"), &addr("dave"), 1000))), Err("insufficient balance".to_string()));
+        bc.pending.push(signed(transfer(&addr("carol"), &addr("dave"), 1000)));
+        mine(&mut bc, "v1").unwrap();
+
+        let snap = bc.snapshot_state();
+        assert_eq!(snap.height, 2);
+        assert_eq!(snap.accounts[&addr("alice")], Account { balance: 50, nonce: 2 });
+        assert_eq!(snap.accounts[&addr("bob")], Account { balance: 20, nonce: 1 });
+        assert_eq!(snap.accounts[&addr("carol")], Account { balance: 30, nonce: 1 });
+        assert_eq!(snap.accounts.values().map(|a| a.balance).sum::<u128>(), 100);
+        assert_eq!(snap.state_root, bc.state_root());
+        assert_ne!(snap.state_root, genesis_root);
+        assert!(snap.next.is_none());
+
+        // the root is a function of the account map alone
+        let mut restored = Blockchain::new(vec![addr("v1")]);
+        restored.accounts = snap.accounts.clone();
+        assert_eq!(restored.state_root(), snap.state_root);
+    }
+
+    #[test]
+    fn state_snapshot_paginates_by_address() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        for a in ["a", "b", "c", "d", "e"] {
+            bc.accounts.insert(a.into(), Account { balance: 1, nonce: 0 });
+        }
+
+        let first = bc.snapshot_page(None, 2);
+        assert_eq!(first.accounts.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(first.next.as_deref(), Some("b"));
+        let second = bc.snapshot_page(first.next.as_deref(), 2);
+        assert_eq!(second.accounts.keys().collect::<Vec<_>>(), vec!["c", "d"]);
+        let last = bc.snapshot_page(second.next.as_deref(), 2);
+        assert_eq!(last.accounts.keys().collect::<Vec<_>>(), vec!["e"]);
+        assert!(last.next.is_none());
+        assert_eq!(last.state_root, first.state_root);
+
+        // a zero limit still moves the cursor on instead of ending the walk
+        let zero = bc.snapshot_page(None, 0);
+        assert_eq!(zero.accounts.keys().collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(zero.next.as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn unauthorized_validator_change_is_ignored() {
+        let mut bc = Blockchain::with_governance(vec![addr("v1")], vec![addr("gov")]);
+        bc.accounts.insert(addr("mallory"), Account { balance: 0, nonce: 0 });
+        bc.add_transaction(signed(validator_tx(&addr("mallory"), &addr("mallory"), TxType::AddValidator))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        assert_eq!(bc.validators, vec![addr("v1")]);
+        assert!(bc.mine_block(addr("mallory")).is_err());
+    }
+
+    #[test]
+    fn address_from_pubkey_is_stable() {
+        // keccak256 of 32 zero bytes is 0x290decd9...0ef3e563
+        assert_eq!(address_from_pubkey(&[0u8; 32]), "0x88386fc84ba6bc95484008f6362f93160ef3e563");
+    }
+
+    #[test]
+    fn transaction_from_must_match_public_key() {
+        let mut tx = signed(transfer(&addr("alice"), &addr("bob"), 0));
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 0, nonce: 0 });
+        bc.accounts.insert(addr("mallory"), Account { balance: 0, nonce: 0 });
+        assert!(bc.add_transaction(tx.clone()).is_ok());
+
+        tx.from = addr("mallory");
+        assert_eq!(bc.add_transaction(tx), Err("sender does not match public key".to_string()));
+        assert_eq!(bc.pending.len(), 1);
+
+        let stranger = signed(transfer(&addr("carol"), &addr("bob"), 0));
+        assert_eq!(bc.add_transaction(stranger), Err(format!("{} has no account", addr("carol"))));
+    }
+
+    #[test]
+    fn unsigned_transfer_is_rejected() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        let unsigned = transfer(&addr("alice"), &addr("bob"), 4);
+        let missing = Err("transactions must carry a public key and signature".to_string());
+        assert_eq!(bc.add_transaction(unsigned.clone()), missing);
+        let keyless = Transaction { public_key: None, ..signed(unsigned.clone()) };
+        assert_eq!(bc.add_transaction(keyless), missing);
+        assert!(bc.pending.is_empty());
+
+        // nor can a producer slip one into a block
+        bc.pending.push(unsigned);
+        mine(&mut bc, "v1").unwrap();
+        assert!(bc.validate().unwrap_err().starts_with("block 1 has an unsigned or forged transaction"));
+    }
+
+    fn sign_classical(tx: &mut Transaction, key: &ed25519_dalek::SigningKey) {
+        use ed25519_dalek::Signer;
+
+        let public = key.verifying_key().to_bytes();
+        tx.from = address_from_pubkey(&public);
+        tx.public_key = Some(hex::encode(public));
+        tx.signature = Some(hex::encode(key.sign(&tx.signing_hash()).to_bytes()));
+    }
+
+    fn sign_hybrid(tx: &mut Transaction, key: &ed25519_dalek::SigningKey) {
+        use ed25519_dalek::Signer;
+        use pqcrypto_dilithium::dilithium3;
+        use pqcrypto_traits::sign::{DetachedSignature, PublicKey};
+
+        let (pq_public, pq_secret) = dilithium3::keypair();
+        let public = [key.verifying_key().to_bytes().as_slice(), pq_public.as_bytes()].concat();
+        tx.from = address_from_pubkey(&public);
+        tx.public_key = Some(hex::encode(&public));
+        let digest = tx.signing_hash();
+        let signature = [
+            key.sign(&digest).to_bytes().as_slice(),
+            dilithium3::detached_sign(&digest, &pq_secret).as_bytes(),
+        ].concat();
+        tx.signature = Some(hex::encode(signature));
+    }
+
+    #[test]
+    fn hybrid_chain_requires_hybrid_signatures() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[9u8; 32]);
+        let mut classical = transfer("", &addr("bob"), 0);
+        sign_classical(&mut classical, &key);
+        let mut hybrid = transfer("", &addr("bob"), 0);
+        sign_hybrid(&mut hybrid, &key);
+
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(classical.from.clone(), Account { balance: 0, nonce: 0 });
+        assert!(bc.add_transaction(classical.clone()).is_ok());
+
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(hybrid.from.clone(), Account { balance: 0, nonce: 0 });
+        bc.signature_scheme = SignatureScheme::Hybrid;
+        assert_eq!(bc.add_transaction(signed(classical)), Err("invalid public key length for Hybrid".to_string()));
+        assert!(bc.add_transaction(hybrid.clone()).is_ok());
+
+        hybrid.amount = 1;
+        assert_eq!(bc.add_transaction(signed(hybrid)), Err("invalid signature".to_string()));
+        assert_eq!(bc.pending.len(), 1);
+    }
+
+    #[test]
+    fn coinbase_issues_block_reward_to_validator() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.block_reward = 50;
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("v1"), 5))).unwrap();
+
+        let block = mine(&mut bc, "v1").unwrap();
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(block.transactions[0].tx_type, TxType::Coinbase);
+        assert_eq!(block.merkle_root, merkle_root(block.hash_algo, &block.transactions));
+        mine(&mut bc, "v1").unwrap();
+
+        assert_eq!(bc.accounts[&addr("v1")].balance, 105);
+        assert!(bc.validate().is_ok());
+    }
+
+    #[test]
+    fn merkle_proof_verifies_inclusion() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 1))).unwrap();
+        bc.add_transaction(signed(Transaction { nonce: 1, ..transfer(&addr("alice"), &addr("carol"), 2) })).unwrap();
+        let block = mine(&mut bc, "v1").unwrap();
+        assert_eq!(block.transactions.len(), 3);
+
+        let proof = merkle_proof(block.hash_algo, &block.transactions, 2).unwrap();
+        assert_eq!(proof.len(), 2);
+        assert!(verify_tx_inclusion(&block, &block.transactions[2], proof.clone()));
+        assert!(!verify_tx_inclusion(&block, &block.transactions[1], proof));
+        assert!(merkle_proof(block.hash_algo, &block.transactions, 3).is_none());
+        assert_eq!(bc.chain[0].merkle_root, hex::encode(Sha256::digest(b"")));
+    }
+
+    #[test]
+    fn inflated_coinbase_is_rejected() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.block_reward = 50;
+        mine(&mut bc, "v1").unwrap();
+
+        let block = &mut bc.chain[1];
+        block.transactions[0].amount += 1;
+        block.merkle_root = merkle_root(block.hash_algo, &block.transactions);
+        assert_eq!(bc.validate(), Err("block 1 has an invalid coinbase".to_string()));
+
+        assert!(bc.add_transaction(signed(Transaction { tx_type: TxType::Coinbase, ..transfer("x", "x", 1) })).is_err());
+    }
+
+    #[test]
+    fn header_chain_links_and_matches_blocks() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.block_reward = 10;
+        bc.accounts.insert(addr("alice"), Account { balance: 100, nonce: 0 });
+        for (nonce, amount) in [1, 2, 3].into_iter().enumerate() {
+            bc.add_transaction(signed(Transaction { nonce: nonce as u64, ..transfer(&addr("alice"), &addr("bob"), amount) })).unwrap();
+            mine(&mut bc, "v1").unwrap();
+        }
+
+        let headers = bc.headers_range(0, 10);
+        assert_eq!(headers.len(), 4);
+        for pair in headers.windows(2) {
+            assert_eq!(pair[1].previous_hash, pair[0].hash);
+            assert_eq!(pair[1].index, pair[0].index + 1);
+        }
+        for (header, block) in headers.iter().zip(&bc.chain) {
+            assert_eq!(header.hash, block.hash);
+            assert_eq!(header.merkle_root, merkle_root(block.hash_algo, &block.transactions));
+        }
+        assert_eq!(headers[3].state_root, bc.state_root());
+        assert_eq!(bc.header(2), Some(headers[2].clone()));
+        assert_eq!(bc.headers_range(1, 2), headers[1..=2].to_vec());
+        assert!(bc.header(4).is_none());
+    }
+
+    #[test]
+    fn concurrent_mine_at_same_height_appends_once() {
+        let state = Arc::new(Mutex::new(Blockchain { signer: Some(key("v1")), ..Blockchain::new(vec![addr("v1")]) }));
+        let height = state.lock().unwrap().chain.len() as u64;
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let (state, barrier) = (Arc::clone(&state), Arc::clone(&barrier));
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    state.lock().unwrap().mine_block_at(addr("v1"), height).unwrap()
+                })
+            })
+            .collect();
+        let blocks: Vec<Block> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let bc = state.lock().unwrap();
+        assert_eq!(bc.chain.len(), 2);
+        assert_eq!(blocks[0].hash, blocks[1].hash);
+        assert_eq!(blocks[0].index, height);
+        assert!(bc.validate().is_ok());
+    }
+
+    #[test]
+    fn mine_block_at_rejects_gaps() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        assert!(bc.mine_block_at(addr("v1"), 5).is_err());
+        assert_eq!(bc.chain.len(), 1);
+    }
+
+    fn mine_and_validate(consensus: ConsensusKind) -> Blockchain {
+        let mut bc = Blockchain::new(vec![addr("v1"), addr("v2")]);
+        bc.consensus = consensus;
+        bc.block_reward = 5;
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 3))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        mine(&mut bc, "v2").unwrap();
+        assert_eq!(bc.validate(), Ok(()));
+        bc.signer = None;
+        bc
+    }
+
+    #[test]
+    fn permissioned_blocks_must_be_signed() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        assert_eq!(bc.mine_block(addr("v1")).unwrap_err(), format!("no key to sign blocks as {}", addr("v1")));
+        mine(&mut bc, "v1").unwrap();
+        assert!(bc.validate().is_ok());
+
+        let block = &mut bc.chain[1];
+        (block.public_key, block.signature) = (None, None);
+        assert_eq!(bc.validate(), Err("block 1 is unsigned".to_string()));
+
+        // proof-of-work blocks need no signature until the node holds a key
+        let mut bc = Blockchain::new(vec![]);
+        bc.consensus = ConsensusKind::ProofOfWork { difficulty: 1 };
+        bc.mine_block(addr("outsider")).unwrap();
+        assert!(bc.validate().is_ok());
+        bc.signer = Some(key("v1"));
+        assert_eq!(bc.validate(), Err("block 1 is unsigned".to_string()));
+    }
+
+    #[test]
+    fn chain_validates_under_authority() {
+        let mut bc = mine_and_validate(ConsensusKind::Authority);
+        assert!(bc.mine_block(addr("outsider")).is_err());
+        assert_eq!(bc.consensus.engine().select_producer(&bc), Some(addr("v2")));
+
+        bc.chain[2].nonce = 7;
+        assert_eq!(bc.validate(), Err("block 2 has an invalid seal".to_string()));
+    }
+
+    #[tokio::test]
+    async fn round_robin_cycles_validators_and_rejects_out_of_turn() {
+        let mut bc = Blockchain::new(vec![addr("v1"), addr("v2"), addr("v3")]);
+        bc.consensus = ConsensusKind::RoundRobin;
+        // height 1 falls to validators[1 % 3]
+        for leader in ["v2", "v3", "v1"] {
+            assert_eq!(bc.expected_validator(), Some(addr(leader).as_str()));
+            assert_eq!(mine(&mut bc, leader).unwrap().validator, addr(leader));
+        }
+        assert_eq!(mine(&mut bc, "v1").unwrap_err(), format!("out of turn: height 4 belongs to {}", addr("v2")));
+        assert_eq!(bc.chain.len(), 4);
+        assert_eq!(bc.mine_block(addr("outsider")).unwrap_err(), "invalid validator");
+        assert!(bc.validate().is_ok());
+        // a validator can't take another's slot by rewriting the block either
+        let mut forked = bc.chain.clone();
+        reassign(&mut forked[3], "v3");
+        let honest = std::mem::replace(&mut bc.chain, forked);
+        assert_eq!(bc.validate(), Err(format!("block 3 was produced by {}, who could not produce it", addr("v3"))));
+        bc.chain = honest;
+
+        let api = routes(Arc::new(Mutex::new(bc)));
+        let next = warp::test::request().path("/next-validator").reply(&api).await;
+        let next: serde_json::Value = serde_json::from_slice(next.body()).unwrap();
+        assert_eq!(next, serde_json::json!({"height": 4, "validator": &addr("v2")}));
+        let early = warp::test::request().method("POST").path("/mine")
+            .json(&serde_json::json!({"validator": &addr("v3")}))
+            .reply(&api).await;
+        assert_eq!(early.status(), 401);
+    }
+
+    #[test]
+    fn chain_validates_under_proof_of_work() {
+        let mut bc = mine_and_validate(ConsensusKind::ProofOfWork { difficulty: 2 });
+        assert!(bc.chain[1..].iter().all(|b| b.hash.starts_with("00")));
+        assert!(bc.mine_block(addr("outsider")).is_ok());
+        assert!(bc.validate().is_ok());
+
+        // a block sealed without doing the work is rejected
+        let block = bc.chain.last_mut().unwrap();
+        while block.hash.starts_with("00") {
+            block.nonce += 1;
+            block.hash = block.compute_hash();
+        }
+        assert_eq!(bc.validate(), Err("block 3 has an invalid seal".to_string()));
+
+        // nor is one claiming less work than the schedule asked for at its height
+        let block = bc.chain.last_mut().unwrap();
+        block.difficulty = 1;
+        block.hash = block.compute_hash();
+        while !block.hash.starts_with('0') {
+            block.nonce += 1;
+            block.hash = block.compute_hash();
+        }
+        assert_eq!(bc.validate(), Err("block 3 has an invalid difficulty".to_string()));
+    }
+
+    #[test]
+    fn block_hash_covers_nonce_and_genesis_is_hashed() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        let genesis = &bc.chain[0];
+        assert_eq!(genesis.hash, genesis.compute_hash());
+        assert_eq!(genesis.hash.len(), 64);
+        // independently built chains agree on their genesis block unless dated apart
+        assert_eq!(Blockchain::new(vec![addr("v1")]).chain[0].hash, genesis.hash);
+        let mut dated = Blockchain::new(vec![addr("v1")]);
+        dated.set_genesis_time(1_700_000_000).unwrap();
+        assert_ne!(dated.chain[0].hash, genesis.hash);
+        assert_eq!(dated.chain[0].hash, dated.chain[0].compute_hash());
+
+        let block = mine(&mut bc, "v1").unwrap();
+        assert_eq!(block.previous_hash, bc.chain[0].hash);
+        let renonced = Block { nonce: block.nonce + 1, ..block.clone() };
+        assert_ne!(renonced.compute_hash(), block.compute_hash());
+
+        assert!(bc.set_genesis_time(1).is_err());
+        bc.chain[0].timestamp += 1;
+        assert_eq!(bc.validate().unwrap_err(), "genesis block has an invalid hash");
+
+        bc = Blockchain::new(vec![addr("v1")]);
+        bc.set_hash_algo(HashAlgo::Blake3).unwrap();
+        assert_eq!(bc.chain[0].hash, bc.chain[0].compute_hash());
+        assert!(mine(&mut bc, "v1").is_ok() && bc.validate().is_ok());
+    }
+
+    #[test]
+    fn blake3_chain_validates_and_rejects_mixed_hashing() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.set_hash_algo(HashAlgo::Blake3).unwrap();
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 1))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        mine(&mut bc, "v1").unwrap();
+        assert!(bc.validate().is_ok());
+        assert!(bc.set_hash_algo(HashAlgo::Sha256).is_err());
+
+        let block = &bc.chain[1];
+        assert_eq!(block.hash_algo, HashAlgo::Blake3);
+        assert_eq!(block.merkle_root, merkle_root(HashAlgo::Blake3, &block.transactions));
+        assert_ne!(block.merkle_root, merkle_root(HashAlgo::Sha256, &block.transactions));
+        assert_eq!(BlockHeader::from(block).hash_algo, HashAlgo::Blake3);
+
+        // block 2 resealed under SHA-256 is consistent on its own, but not with the chain
+        let block = &mut bc.chain[2];
+        block.hash_algo = HashAlgo::Sha256;
+        block.merkle_root = merkle_root(HashAlgo::Sha256, &block.transactions);
+        block.hash = block.compute_hash();
+        assert_eq!(bc.validate(), Err("block 2 uses a different hash algorithm than the genesis block".to_string()));
+    }
+
+    #[tokio::test]
+    async fn difficulty_route_retargets_proof_of_work() {
+        let mut bc = mine_and_validate(ConsensusKind::ProofOfWork { difficulty: 0 });
+        // mined blocks keep the difficulty they were sealed at
+        let unmet = bc.chain[1..].iter().map(|b| b.hash.chars().take_while(|c| *c == '0').count()).min().unwrap() as u32 + 1;
+        bc.set_difficulty(unmet).unwrap();
+        assert!(bc.validate().is_ok());
+        assert_eq!(
+            bc.set_difficulty(MAX_DIFFICULTY + 1),
+            Err(format!("difficulty cannot exceed {}, the length of a block hash", MAX_DIFFICULTY))
+        );
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+        let post = |path: &str, body: serde_json::Value| warp::test::request().method("POST").path(path).json(&body);
+
+        let res = post("/difficulty", serde_json::json!({"difficulty": 0})).reply(&api).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(post("/difficulty", serde_json::json!({"difficulty": -1})).reply(&api).await.status(), 400);
+        assert_eq!(post("/difficulty", serde_json::json!({"difficulty": 65})).reply(&api).await.status(), 400);
+
+        let mut fresh = Blockchain::new(vec![addr("v1")]);
+        fresh.consensus = ConsensusKind::ProofOfWork { difficulty: 0 };
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || fresh).unwrap())));
+        let res = post("/difficulty", serde_json::json!({"difficulty": 2})).reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["consensus"], serde_json::json!({"ProofOfWork": {"difficulty": 2}}));
+        let mined = post("/mine", serde_json::json!({"validator": "anyone"})).reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(mined.body()).unwrap();
+        assert!(body["block"]["hash"].as_str().unwrap().starts_with("00"));
+        assert_eq!(body["block"]["difficulty"], 2);
+
+        let mut authority = Blockchain::new(vec![addr("v1")]);
+        assert_eq!(authority.set_difficulty(1), Err("difficulty applies only to proof-of-work chains".to_string()));
+    }
+
+    #[tokio::test]
+    async fn compact_block_rebuilds_from_mempool() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        let txs = [
+            transfer(&addr("alice"), &addr("bob"), 1),
+            Transaction { nonce: 1, ..transfer(&addr("alice"), &addr("carol"), 2) },
+            Transaction { nonce: 2, ..transfer(&addr("alice"), &addr("dave"), 3) },
+        ].map(signed);
+        for tx in &txs {
+            bc.add_transaction(tx.clone()).unwrap();
+        }
+        mine(&mut bc, "v1").unwrap();
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+
+        let res = warp::test::request().path("/block/1/compact").reply(&api).await;
+        let compact: CompactBlock = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(compact.tx_hashes.len(), 3);
+        assert_eq!(warp::test::request().path("/block/9/compact").reply(&api).await.status(), 404);
+
+        // the genesis block has no coinbase, and asking for it leaves the node serving
+        let res = warp::test::request().path("/block/0/compact").reply(&api).await;
+        assert_eq!(res.status(), 200);
+        let genesis: CompactBlock = serde_json::from_slice(res.body()).unwrap();
+        assert!(genesis.coinbase.is_none() && genesis.tx_hashes.is_empty());
+        let rebuilt = compact::reconstruct_block(&genesis, &[]).unwrap();
+        assert_eq!(rebuilt.hash, rebuilt.compute_hash());
+        assert_eq!(warp::test::request().path("/block/1/compact").reply(&api).await.status(), 200);
+
+        // a peer's mempool holds the same transactions in its own order, plus others
+        let mut mempool = vec![transfer(&addr("erin"), &addr("frank"), 4)];
+        mempool.extend(txs.iter().rev().cloned());
+        let block = compact::reconstruct_block(&compact, &mempool).unwrap();
+        assert_eq!(block.hash, block.compute_hash());
+        assert_eq!(block.merkle_root, merkle_root(block.hash_algo, &block.transactions));
+        assert_eq!(block.transactions.iter().skip(1).map(Transaction::hash).collect::<Vec<_>>(), compact.tx_hashes);
+
+        let partial = [txs[0].clone(), txs[2].clone()];
+        let missing = compact::reconstruct_block(&compact, &partial).unwrap_err();
+        assert_eq!(missing, vec![txs[1].hash()]);
+
+        // this node mined those transactions, so its own mempool can't rebuild the block
+        let res = warp::test::request().method("POST").path("/block/reconstruct").json(&compact).reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["complete"], false);
+        assert_eq!(body["missing"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn mined_transaction_is_locatable_by_hash() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        mine(&mut bc, "v1").unwrap();
+        let tx = signed(transfer(&addr("alice"), &addr("bob"), 4));
+        bc.add_transaction(tx.clone()).unwrap();
+        mine(&mut bc, "v1").unwrap();
+
+        let (found, block_index, position) = bc.find_transaction(&tx.hash()).unwrap();
+        assert_eq!((found.hash(), block_index, position), (tx.hash(), 2, 1));
+        // coinbases of different heights don't collide
+        let coinbase = &bc.chain[1].transactions[0];
+        assert_eq!(bc.find_transaction(&coinbase.hash()).unwrap().1, 1);
+
+        assert!(tx_lookup_reply(&bc, &tx.hash()).is_ok());
+        assert!(matches!(tx_lookup_reply(&bc, "deadbeef"), Err(e) if e.status == warp::http::StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn transactions_are_searchable_by_tag() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        let first = signed(Transaction { tag: Some("invoice-7".into()), ..transfer(&addr("alice"), &addr("bob"), 1) });
+        let second = signed(Transaction { tag: Some("invoice-7".into()), nonce: 2, ..transfer(&addr("alice"), &addr("carol"), 2) });
+        bc.add_transaction(first.clone()).unwrap();
+        bc.add_transaction(signed(Transaction { nonce: 1, ..transfer(&addr("alice"), &addr("dave"), 3) })).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        bc.add_transaction(second.clone()).unwrap();
+        mine(&mut bc, "v1").unwrap();
+
+        assert_eq!(bc.transactions_by_tag("invoice-7"), vec![first.hash(), second.hash()]);
+        assert!(bc.transactions_by_tag("unknown").is_empty());
+
+        let long = Transaction { tag: Some("x".repeat(MAX_TAG_LEN + 1)), ..transfer(&addr("alice"), &addr("bob"), 1) };
+        assert!(bc.add_transaction(signed(long)).is_err());
+    }
+
+    fn sign_checkpoint(seed: u8, checkpoint: &Checkpoint) -> SignedCheckpoint {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key = SigningKey::from_bytes(&[seed; 32]);
+        let public = key.verifying_key().to_bytes();
+        SignedCheckpoint {
+            checkpoint: checkpoint.clone(),
+            validator: address_from_pubkey(&public),
+            public_key: hex::encode(public),
+            signature: hex::encode(key.sign(&checkpoint.digest()).to_bytes()),
+        }
+    }
+
+    fn checkpoint_chain() -> (Blockchain, Checkpoint) {
+        let validators = (1..=3).map(|seed| sign_checkpoint(seed, &Checkpoint { height: 0, state_root: String::new(), block_hash: String::new() }).validator).collect::<Vec<_>>();
+        let mut bc = Blockchain::new(validators.clone());
+        bc.signer = Some(ed25519_dalek::SigningKey::from_bytes(&[1; 32]));
+        bc.mine_block(validators[0].clone()).unwrap();
+        bc.signer = Some(ed25519_dalek::SigningKey::from_bytes(&[2; 32]));
+        let block = bc.mine_block(validators[1].clone()).unwrap();
+        let checkpoint = Checkpoint { height: block.index, state_root: block.state_root, block_hash: block.hash };
+        (bc, checkpoint)
+    }
+
+    #[test]
+    fn checkpoint_quorum_finalizes_height() {
+        let (mut bc, checkpoint) = checkpoint_chain();
+
+        assert_eq!(bc.submit_checkpoint(sign_checkpoint(1, &checkpoint)), Ok(false));
+        // the same validator under another casing is not a second vote
+        let recased = SignedCheckpoint { validator: bc.validators[0].to_ascii_uppercase(), ..sign_checkpoint(1, &checkpoint) };
+        assert_eq!(bc.submit_checkpoint(recased), Ok(false));
+        assert_eq!(bc.checkpoint_votes[&checkpoint.height].len(), 1);
+        assert!(bc.finalized.is_none());
+        assert_eq!(bc.submit_checkpoint(sign_checkpoint(2, &checkpoint)), Ok(true));
+        assert_eq!(bc.finalized, Some(checkpoint.clone()));
+        assert!(bc.validate().is_ok());
+
+        // nothing at or below the final height can be re-decided
+        let earlier = Checkpoint { height: 1, ..checkpoint.clone() };
+        assert!(bc.submit_checkpoint(sign_checkpoint(3, &earlier)).is_err());
+        bc.chain[2].hash = "rewritten".to_string();
+        assert!(bc.validate().is_err());
+    }
+
+    // `header` resealed under its own fields and signed by key `seed`
+    fn sign_header(seed: u8, header: &BlockHeader) -> BlockHeader {
+        let mut block = header.with_transactions(Vec::new());
+        block.hash = block.compute_hash();
+        block.sign(&ed25519_dalek::SigningKey::from_bytes(&[seed; 32]));
+        BlockHeader::from(&block)
+    }
+
+    #[test]
+    fn double_sign_report_slashes_and_removes_validator() {
+        let (mut bc, checkpoint) = checkpoint_chain();
+        let offender = bc.validators[0].clone();
+        bc.accounts.insert(offender.clone(), Account { balance: 1000, nonce: 0 });
+        bc.accounts.insert(addr("reporter"), Account { balance: 0, nonce: 0 });
+        let header = BlockHeader { validator: offender.clone(), ..BlockHeader::from(&bc.chain[checkpoint.height as usize]) };
+        let fork = BlockHeader { state_root: "fork".into(), ..header.clone() };
+        let evidence = |a: BlockHeader, b: BlockHeader| Transaction {
+            tx_type: TxType::ReportDoubleSign { height: checkpoint.height, block_a: Box::new(a), block_b: Box::new(b) },
+            ..transfer(&addr("reporter"), "", 0)
+        };
+        let report = |a: &BlockHeader, b: &BlockHeader| evidence(sign_header(1, a), sign_header(1, b));
+
+        assert_eq!(bc.add_transaction(signed(report(&header, &header))), Err("evidence must contain two different blocks".to_string()));
+        let mixed = evidence(sign_header(1, &header), sign_header(2, &BlockHeader { validator: bc.validators[1].clone(), ..fork.clone() }));
+        assert_eq!(bc.add_transaction(signed(mixed)), Err("evidence is signed by different validators".to_string()));
+        // the proposer's signature covers the header, so it can't be edited after signing
+        let forged = evidence(sign_header(1, &header), BlockHeader { state_root: "fork".into(), ..sign_header(1, &header) });
+        assert_eq!(bc.add_transaction(signed(forged)), Err("header hash does not match its contents".to_string()));
+        let unsigned = evidence(sign_header(1, &header), BlockHeader { public_key: None, signature: None, ..sign_header(1, &fork) });
+        assert_eq!(bc.add_transaction(signed(unsigned)), Err("header is not signed".to_string()));
+        let elsewhere = BlockHeader { index: checkpoint.height + 1, ..fork.clone() };
+        assert_eq!(
+            bc.add_transaction(signed(report(&header, &elsewhere))),
+            Err(format!("evidence is not for height {}", checkpoint.height))
+        );
+
+        // the offender is whoever holds the key, however the evidence spells its address
+        let recased = |h: &BlockHeader| BlockHeader { validator: offender.to_ascii_uppercase(), ..h.clone() };
+        bc.add_transaction(signed(report(&recased(&header), &recased(&fork)))).unwrap();
+        assert_eq!(
+            bc.add_transaction(signed(Transaction { nonce: 1, ..report(&fork, &header) })),
+            Err("validator already has a pending double-sign report".to_string())
+        );
+        let producer = bc.validators[1].clone();
+        let accounts = bc.accounts.len();
+        bc.mine_block(producer).unwrap();
+
+        assert!(!bc.validators.contains(&offender));
+        assert_eq!(bc.accounts[&offender].balance, 500);
+        // the slashed stake moves between existing accounts
+        assert_eq!(bc.accounts.len(), accounts);
+        assert_eq!(bc.accounts[&addr("reporter")].balance, 50);
+        assert!(bc.validate().is_ok());
+        assert_eq!(
+            bc.add_transaction(signed(Transaction { nonce: 1, ..report(&header, &fork) })),
+            Err("reported signer is not a validator".to_string())
+        );
+    }
+
+    #[test]
+    fn conflicting_checkpoints_do_not_finalize() {
+        let (mut bc, checkpoint) = checkpoint_chain();
+        let fork = Checkpoint { block_hash: "fork".to_string(), ..checkpoint.clone() };
+
+        assert_eq!(bc.submit_checkpoint(sign_checkpoint(1, &checkpoint)), Ok(false));
+        assert_eq!(bc.submit_checkpoint(sign_checkpoint(2, &fork)), Ok(false));
+        assert!(bc.finalized.is_none());
+        // a validator can't switch sides
+        assert!(bc.submit_checkpoint(sign_checkpoint(2, &checkpoint)).is_err());
+
+        let mut forged = sign_checkpoint(3, &checkpoint);
+        forged.checkpoint = fork;
+        assert!(bc.submit_checkpoint(forged).is_err());
+        assert!(bc.submit_checkpoint(sign_checkpoint(9, &checkpoint)).is_err());
+    }
+
+    #[test]
+    fn amounts_beyond_u64_transfer() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        let large = u64::MAX as u128 + 10;
+        bc.accounts.insert(addr("alice"), Account { balance: large, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), u64::MAX as u128 + 1))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+
+        assert_eq!(bc.accounts[&addr("bob")].balance, u64::MAX as u128 + 1);
+        assert_eq!(bc.accounts[&addr("alice")].balance, 9);
+        assert!(bc.validate().is_ok());
+    }
+
+    #[test]
+    fn overflowing_block_is_rejected() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.accounts.insert(addr("bob"), Account { balance: u128::MAX - 5, nonce: 0 });
+        let root = bc.state_root();
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 10))).unwrap();
+
+        assert_eq!(mine(&mut bc, "v1").unwrap_err(), format!("balance overflow crediting {}", addr("bob")));
+        assert_eq!(bc.chain.len(), 1);
+        assert_eq!(bc.state_root(), root);
+        assert_eq!(bc.pending.len(), 1);
+    }
+
+    #[test]
+    fn confirmations_grow_with_chain() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        let tx = signed(transfer(&addr("alice"), &addr("bob"), 4));
+        bc.add_transaction(tx.clone()).unwrap();
+        assert_eq!(bc.confirmations(&tx.hash()), Some(0));
+
+        mine(&mut bc, "v1").unwrap();
+        assert_eq!(bc.confirmations(&tx.hash()), Some(1));
+        mine(&mut bc, "v1").unwrap();
+        mine(&mut bc, "v1").unwrap();
+        assert_eq!(bc.confirmations(&tx.hash()), Some(3));
+
+        assert_eq!(bc.confirmations("deadbeef"), None);
+        assert!(matches!(confirmations_reply(&bc, "deadbeef"), Err(e) if e.status == warp::http::StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn bloom_narrows_blocks_touching_address() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        let people = ["alice", "bob", "carol", "dave", "erin"].map(addr);
+        for name in &people {
+            bc.accounts.insert(name.clone(), Account { balance: 100, nonce: 0 });
+        }
+        for (i, pair) in people.windows(2).enumerate() {
+            let tx = Transaction { tag: Some(format!("batch-{}", i)), ..transfer(&pair[0], &pair[1], 1) };
+            bc.add_transaction(signed(tx)).unwrap();
+            mine(&mut bc, "v1").unwrap();
+        }
+        assert!(bc.validate().is_ok());
+
+        for name in people.iter().map(String::as_str).chain([&addr("v1"), "coinbase", &addr("nobody")]) {
+            let touching: Vec<u64> = bc.chain.iter()
+                .filter(|b| b.transactions.iter().any(|tx| tx.from == name || tx.to == name))
+                .map(|b| b.index)
+                .collect();
+            let candidates: Vec<u64> = bc.chain.iter()
+                .filter(|b| bloom_contains(&b.bloom, name))
+                .map(|b| b.index)
+                .collect();
+            assert!(touching.iter().all(|i| candidates.contains(i)), "false negative for {}", name);
+            assert_eq!(bc.blocks_touching(name), touching);
+        }
+        assert_eq!(bc.blocks_touching(&addr("carol")), vec![2, 3]);
+        assert!(bloom_contains(&bc.chain[1].bloom, "batch-0"));
+
+        bc.chain[2].bloom = bc.chain[1].bloom.clone();
+        assert_eq!(bc.validate(), Err("block 2 has an invalid bloom".to_string()));
+    }
+
+    #[test]
+    fn blocks_mined_before_blooms_still_load_and_validate() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 100, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 1))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        mine(&mut bc, "v1").unwrap();
+        // as an older node saved them: no bloom field, hashed and linked without one
+        let mut saved = serde_json::to_value(&bc).unwrap();
+        for block in saved["chain"].as_array_mut().unwrap() {
+            block.as_object_mut().unwrap().remove("bloom");
+        }
+        let mut old: Blockchain = serde_json::from_value(saved).unwrap();
+        for i in 0..old.chain.len() {
+            if i > 0 {
+                old.chain[i].previous_hash = old.chain[i - 1].hash.clone();
+            }
+            let block = &mut old.chain[i];
+            block.hash = block.compute_hash();
+            if i > 0 {
+                block.sign(&key("v1"));
+            }
+        }
+
+        assert_eq!(old.validate(), Ok(()));
+        assert_eq!(old.blocks_touching(&addr("bob")), vec![1]);
+        assert!(bloom_contains(&old.chain[2].bloom, &addr("nobody")));
+    }
+
+    #[test]
+    fn mempool_order_is_canonical() {
+        let txs = [
+            transfer(&addr("alice"), &addr("bob"), 1),
+            validator_tx(&addr("gov"), &addr("v2"), TxType::AddValidator),
+            transfer(&addr("carol"), &addr("dave"), 2),
+            Transaction { tag: Some("t".into()), nonce: 1, ..transfer(&addr("alice"), &addr("bob"), 1) },
+        ];
+        let assemble = |order: &[usize]| {
+            let mut bc = Blockchain::with_governance(vec![addr("v1")], vec![addr("gov")]);
+            bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+            bc.accounts.insert(addr("carol"), Account { balance: 10, nonce: 0 });
+            bc.accounts.insert(addr("gov"), Account { balance: 0, nonce: 0 });
+            for &i in order {
+                bc.add_transaction(signed(txs[i].clone())).unwrap();
+            }
+            mine(&mut bc, "v1").unwrap().transactions
+        };
+
+        // a sender's own transactions still arrive in nonce order
+        let first = assemble(&[0, 1, 2, 3]);
+        let second = assemble(&[2, 1, 0, 3]);
+        let hashes = |block: &[Transaction]| block.iter().map(Transaction::hash).collect::<Vec<_>>();
+        assert_eq!(hashes(&first), hashes(&second));
+        assert_eq!(merkle_root(HashAlgo::Sha256, &first), merkle_root(HashAlgo::Sha256, &second));
+    }
+
+    #[test]
+    fn later_nonce_paying_more_is_mined_in_sequence() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 100, nonce: 0 });
+        let first = signed(Transaction { fee: 1, ..transfer(&addr("alice"), &addr("bob"), 1) });
+        let second = signed(Transaction { fee: 5, nonce: 1, ..transfer(&addr("alice"), &addr("bob"), 1) });
+        bc.add_transaction(first.clone()).unwrap();
+        bc.add_transaction(second.clone()).unwrap();
+
+        let block = mine(&mut bc, "v1").unwrap();
+        assert_eq!(block.transactions[1..].iter().map(Transaction::hash).collect::<Vec<_>>(), vec![first.hash(), second.hash()]);
+        assert!(bc.pending.is_empty());
+
+        // a mempool saved out of order still resubmits every transaction
+        let third = signed(Transaction { nonce: 2, ..transfer(&addr("alice"), &addr("bob"), 1) });
+        let fourth = signed(Transaction { nonce: 3, ..transfer(&addr("alice"), &addr("bob"), 1) });
+        bc.pending = vec![fourth, third];
+        assert!(bc.revalidate_pending().is_empty());
+        assert_eq!(bc.pending.iter().map(|tx| tx.nonce).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn validator_stats_track_produced_and_missed_slots() {
+        let mut bc = Blockchain::new(vec![addr("v1"), addr("v2")]);
+        assert_eq!(bc.validator_stats(&addr("v1")), Some(ValidatorPerf::default()));
+        assert_eq!(bc.validator_stats(&addr("nobody")), None);
+
+        // height 1 is v2's slot, height 2 is v1's
+        mine(&mut bc, "v2").unwrap();
+        mine(&mut bc, "v1").unwrap();
+        // v1 takes v2's slot at height 3
+        mine(&mut bc, "v1").unwrap();
+
+        let v1 = bc.validator_stats(&addr("v1")).unwrap();
+        assert_eq!((v1.blocks_produced, v1.blocks_missed, v1.last_produced_height), (2, 0, Some(3)));
+        let v2 = bc.validator_stats(&addr("v2")).unwrap();
+        assert_eq!((v2.blocks_produced, v2.blocks_missed, v2.last_produced_height), (1, 1, Some(1)));
+    }
+
+    #[test]
+    fn system_transactions_precede_higher_fee_user_transactions() {
+        let gov_key = ed25519_dalek::SigningKey::from_bytes(&[9; 32]);
+        let mut system = Transaction { class: TxClass::System, ..validator_tx("", &addr("v2"), TxType::AddValidator) };
+        sign_classical(&mut system, &gov_key);
+        let mut bc = Blockchain::with_governance(vec![addr("v1")], vec![system.from.clone()]);
+        bc.max_block_txs = 3;
+        bc.accounts.insert(addr("whale"), Account { balance: 1000, nonce: 0 });
+        bc.accounts.insert(system.from.clone(), Account { balance: 0, nonce: 0 });
+        for (nonce, fee) in (10..15).rev().enumerate() {
+            bc.add_transaction(signed(Transaction { fee, nonce: nonce as u64, ..transfer(&addr("whale"), &addr("bob"), 1) })).unwrap();
+        }
+        bc.add_transaction(system.clone()).unwrap();
+
+        let block = mine(&mut bc, "v1").unwrap();
+        let fees = block.transactions[2..].iter().map(|tx| tx.fee).collect::<Vec<_>>();
+        assert_eq!(block.transactions[1].hash(), system.hash());
+        assert_eq!(fees, vec![14, 13]);
+        assert_eq!(block.transactions[0].amount, 27);
+        assert_eq!(bc.pending.len(), 3);
+        assert_eq!(bc.accounts[&addr("whale")].balance, 1000 - 2 - 27);
+        assert!(bc.validators.contains(&addr("v2")));
+        assert!(bc.validate().is_ok());
+
+        let mut unsigned = system.clone();
+        unsigned.signature = None;
+        assert_eq!(bc.add_transaction(unsigned), Err("transactions must carry a public key and signature".to_string()));
+        let mut outsider = system.clone();
+        sign_classical(&mut outsider, &ed25519_dalek::SigningKey::from_bytes(&[10; 32]));
+        assert!(bc.add_transaction(outsider.clone()).is_err());
+        let mut transfer = Transaction { class: TxClass::System, ..transfer("", &addr("bob"), 0) };
+        sign_classical(&mut transfer, &gov_key);
+        assert!(bc.add_transaction(transfer.clone()).is_err());
+
+        // nor does a block that carries either get past validation
+        let block = &mut bc.chain[1];
+        block.transactions[1] = outsider;
+        reassign(block, "v1");
+        assert_eq!(
+            bc.validate(),
+            Err("block 1 has an invalid system transaction: system transactions must be signed by a governance or validator key".to_string())
+        );
+        let block = &mut bc.chain[1];
+        block.transactions[1] = transfer;
+        reassign(block, "v1");
+        assert_eq!(
+            bc.validate(),
+            Err("block 1 has an invalid system transaction: only validator set changes can be system transactions".to_string())
+        );
+    }
+
+    #[test]
+    fn transaction_that_cannot_pay_its_fee_is_evicted_instead_of_failing_the_block() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(Transaction { fee: 2, ..transfer(&addr("alice"), &addr("bob"), 8) })).unwrap();
+        // left in the mempool past admission, as a slashing can leave them: the
+        // transfer ahead of them spends everything they would pay with
+        let unpayable = signed(Transaction { fee: 1, nonce: 1, ..transfer(&addr("alice"), &addr("bob"), 0) });
+        let queued = signed(Transaction { fee: 1, nonce: 2, ..transfer(&addr("alice"), &addr("bob"), 0) });
+        // and one from a sender without an account, which mining won't create
+        let stranger = signed(transfer(&addr("carol"), &addr("bob"), 0));
+        bc.pending.extend([unpayable.clone(), queued.clone(), stranger]);
+
+        let block = mine(&mut bc, "v1").unwrap();
+        assert_eq!(block.transactions.len(), 2);
+        assert!(bc.pending.is_empty());
+        assert_eq!(bc.accounts[&addr("alice")], Account { balance: 0, nonce: 1 });
+        assert!(!bc.accounts.contains_key(&addr("carol")));
+        assert!(bc.validate().is_ok());
+        assert!(mine(&mut bc, "v1").is_ok());
+    }
+
+    #[test]
+    fn base_fee_tracks_block_fullness_and_is_burned() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.max_block_txs = 4;
+        bc.min_base_fee = 16;
+        bc.accounts.insert(addr("alice"), Account { balance: 10_000, nonce: 0 });
+        let fill = |bc: &mut Blockchain, fee: u128| {
+            for i in 0..4 {
+                let nonce = bc.next_nonce(&addr("alice"));
+                bc.add_transaction(signed(Transaction { fee, payload: Some(i.to_string()), nonce, ..transfer(&addr("alice"), &addr("bob"), 1) })).unwrap();
+            }
+        };
+
+        assert_eq!(bc.next_base_fee(), 16);
+        fill(&mut bc, 20);
+        let full = mine(&mut bc, "v1").unwrap();
+        assert_eq!(full.base_fee, 16);
+        // the validator keeps only the priority fee; 4 * 16 is burned
+        assert_eq!(full.transactions[0].amount, 4 * 4);
+        assert_eq!(bc.accounts[&addr("alice")].balance, 10_000 - 4 - 4 * 20);
+
+        // twice the target of 2: up by 1/8
+        assert_eq!(bc.next_base_fee(), 18);
+        assert_eq!(
+            bc.add_transaction(signed(Transaction { fee: 17, nonce: 4, ..transfer(&addr("alice"), &addr("bob"), 1) })),
+            Err("fee below the current base fee of 18".to_string())
+        );
+        fill(&mut bc, 18);
+        mine(&mut bc, "v1").unwrap();
+        assert_eq!(bc.next_base_fee(), 20);
+
+        // empty: down by 1/8, floored at min_base_fee
+        let empty = mine(&mut bc, "v1").unwrap();
+        assert_eq!(empty.base_fee, 20);
+        assert_eq!(bc.next_base_fee(), 18);
+        assert!(bc.validate().is_ok());
+
+        // raising the floor applies from the next block on; mined ones keep theirs
+        bc.min_base_fee = 30;
+        assert_eq!(bc.next_base_fee(), 30);
+        assert!(bc.validate().is_ok());
+
+        bc.chain[3].base_fee = 19;
+        assert_eq!(bc.validate(), Err("block 3 has an invalid base fee".to_string()));
+    }
+
+    #[test]
+    fn validate_rejects_blocks_over_the_count_and_size_limits() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 100, nonce: 0 });
+        for i in 0..3 {
+            bc.add_transaction(signed(Transaction { payload: Some(i.to_string()), nonce: i, ..transfer(&addr("alice"), &addr("bob"), 1) })).unwrap();
+        }
+        let block = mine(&mut bc, "v1").unwrap();
+        assert!(bc.validate().is_ok());
+
+        // blocks are held to the limits and reward of their own height, so
+        // changing them later leaves history valid
+        let bytes: usize = block.transactions[1..].iter().map(encoded_len).sum();
+        bc.max_block_txs = 2;
+        bc.max_block_bytes = bytes - 1;
+        bc.block_reward = 7;
+        assert!(bc.validate().is_ok());
+
+        // but a block over the limits recorded for its height is rejected
+        let recorded = bc.rules.get_mut(&1).unwrap();
+        recorded.max_block_txs = 2;
+        assert_eq!(bc.validate(), Err("block 1 has too many transactions".to_string()));
+        let recorded = bc.rules.get_mut(&1).unwrap();
+        recorded.max_block_txs = 3;
+        recorded.max_block_bytes = bytes - 1;
+        assert_eq!(bc.validate(), Err("block 1 exceeds the block size limit".to_string()));
+        bc.rules.get_mut(&1).unwrap().max_block_bytes = bytes;
+        assert!(bc.validate().is_ok());
+        bc.max_block_txs = 3;
+
+        // mining defers whatever no longer fits
+        bc.max_block_bytes = bytes / 2;
+        for i in 0..3 {
+            bc.add_transaction(signed(Transaction { fee: 1, payload: Some(i.to_string()), nonce: 3 + i, ..transfer(&addr("alice"), &addr("carol"), 1) })).unwrap();
+        }
+        let block = mine(&mut bc, "v1").unwrap();
+        assert_eq!((block.transactions.len(), bc.pending.len()), (2, 2));
+        assert!(encoded_len(&block.transactions[1]) <= bc.max_block_bytes);
+    }
+
+    #[tokio::test]
+    async fn route_errors_share_structured_shape() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+
+        let mine = warp::test::request().method("POST").path("/mine")
+            .json(&serde_json::json!({"validator": &addr("mallory")}))
+            .reply(&api).await;
+        let overspend = warp::test::request().method("POST").path("/tx")
+            .json(&signed(transfer(&addr("alice"), &addr("bob"), 11)))
+            .reply(&api).await;
+        let missing = warp::test::request().path("/tx/deadbeef").reply(&api).await;
+
+        let body = |res: &warp::http::Response<warp::hyper::body::Bytes>| serde_json::from_slice::<serde_json::Value>(res.body()).unwrap();
+        assert_eq!(mine.status(), 401);
+        assert_eq!(body(&mine), serde_json::json!({"error": {"code": 401, "message": "invalid validator"}}));
+        assert_eq!(overspend.status(), 400);
+        assert_eq!(body(&overspend), serde_json::json!({"error": {"code": 400, "message": "insufficient balance"}}));
+        assert_eq!(missing.status(), 404);
+        assert_eq!(body(&missing)["error"]["code"], 404);
+    }
+
+    #[test]
+    fn nonces_sequence_transactions_per_sender() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        for nonce in 0..3 {
+            assert_eq!(bc.next_nonce(&addr("alice")), nonce);
+            bc.add_transaction(signed(Transaction { nonce, ..transfer(&addr("alice"), &addr("bob"), 1) })).unwrap();
+        }
+        assert_eq!(
+            bc.add_transaction(signed(Transaction { nonce: 4, ..transfer(&addr("alice"), &addr("bob"), 1) })),
+            Err(format!("nonce 4 is out of order: next for {} is 3", addr("alice")))
+        );
+        let block = mine(&mut bc, "v1").unwrap();
+        assert_eq!(block.transactions[1..].iter().map(|tx| tx.nonce).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(bc.accounts[&addr("alice")], Account { balance: 7, nonce: 3 });
+
+        // a gap left in the mempool holds back the sender's later transactions
+        bc.pending.push(signed(Transaction { nonce: 4, ..transfer(&addr("alice"), &addr("bob"), 1) }));
+        mine(&mut bc, "v1").unwrap();
+        assert_eq!(bc.pending.len(), 1);
+        assert_eq!(bc.accounts[&addr("alice")].nonce, 3);
+        assert!(bc.validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn replayed_transaction_is_rejected() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        let tx = signed(transfer(&addr("alice"), &addr("bob"), 4));
+        bc.add_transaction(tx.clone()).unwrap();
+        mine(&mut bc, "v1").unwrap();
+
+        let stale = format!("stale nonce 0: {} has already used it, next is 1", addr("alice"));
+        assert_eq!(bc.add_transaction(tx.clone()), Err(stale.clone()));
+        // slipped into the mempool anyway, it is dropped rather than applied twice
+        bc.pending.push(tx.clone());
+        let block = mine(&mut bc, "v1").unwrap();
+        assert_eq!(block.transactions.len(), 1);
+        assert!(bc.pending.is_empty());
+        assert_eq!(bc.accounts[&addr("bob")].balance, 4);
+
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+        let replay = warp::test::request().method("POST").path("/tx").json(&tx).reply(&api).await;
+        assert_eq!(replay.status(), 400);
+        let body: serde_json::Value = serde_json::from_slice(replay.body()).unwrap();
+        assert_eq!(body["error"]["message"], stale);
+    }
+
+    #[tokio::test]
+    async fn privileged_routes_require_an_operator_signature() {
+        use ed25519_dalek::Signer;
+
+        let operator = ed25519_dalek::SigningKey::from_bytes(&[11; 32]);
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.operators = vec![address_from_pubkey(operator.verifying_key().as_bytes())];
+        bc.signer = Some(key("v1"));
+        let api = routes(Arc::new(Mutex::new(bc)));
+
+        let body = serde_json::json!({"validator": addr("v1")}).to_string();
+        let body = body.as_bytes();
+        let now = Utc::now().timestamp();
+        let signed_request = |key: &ed25519_dalek::SigningKey, path: &str, timestamp: i64, nonce: &str, signed: &[u8]| {
+            let message = api::operator_message("POST", path, timestamp, nonce, signed);
+            warp::test::request().method("POST").path("/mine")
+                .header(api::OPERATOR_KEY_HEADER, hex::encode(key.verifying_key().as_bytes()))
+                .header(api::OPERATOR_SIGNATURE_HEADER, hex::encode(key.sign(&message).to_bytes()))
+                .header(api::OPERATOR_TIMESTAMP_HEADER, timestamp.to_string())
+                .header(api::OPERATOR_NONCE_HEADER, nonce)
+                .body(body)
+        };
+        let mine = |key: &ed25519_dalek::SigningKey, signed: &[u8]| signed_request(key, "/mine", now, "n1", signed);
+
+        let unsigned = warp::test::request().method("POST").path("/mine").body(body).reply(&api).await;
+        assert_eq!(unsigned.status(), 401);
+        let message: serde_json::Value = serde_json::from_slice(unsigned.body()).unwrap();
+        assert_eq!(message["error"]["message"], "operator signature required");
+        let outsider = ed25519_dalek::SigningKey::from_bytes(&[12; 32]);
+        assert_eq!(mine(&outsider, body).reply(&api).await.status(), 401);
+        assert_eq!(mine(&operator, serde_json::json!({"validator": addr("v2")}).to_string().as_bytes()).reply(&api).await.status(), 401);
+        // impersonating the operator: its public key with someone else's signature
+        let forged = mine(&outsider, body)
+            .header(api::OPERATOR_KEY_HEADER, hex::encode(operator.verifying_key().as_bytes()))
+            .reply(&api).await;
+        assert_eq!(forged.status(), 401);
+
+        // signed for another route, or too long ago
+        assert_eq!(signed_request(&operator, "/difficulty", now, "n1", body).reply(&api).await.status(), 401);
+        let stale = signed_request(&operator, "/mine", now - api::OPERATOR_MAX_SKEW_SECS - 1, "n1", body).reply(&api).await;
+        let message: serde_json::Value = serde_json::from_slice(stale.body()).unwrap();
+        assert_eq!(message["error"]["message"], "stale operator request");
+
+        let res = mine(&operator, body).reply(&api).await;
+        assert_eq!(res.status(), 200);
+        let mined: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(mined["block"]["index"], 1);
+        assert_eq!(warp::test::request().path("/chain").reply(&api).await.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn replayed_operator_request_is_rejected() {
+        use ed25519_dalek::Signer;
+
+        let operator = ed25519_dalek::SigningKey::from_bytes(&[11; 32]);
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.operators = vec![address_from_pubkey(operator.verifying_key().as_bytes())];
+        bc.signer = Some(key("v1"));
+        let api = routes(Arc::new(Mutex::new(bc)));
+
+        let body = serde_json::json!({"validator": addr("v1")}).to_string();
+        let now = Utc::now().timestamp();
+        let message = api::operator_message("POST", "/mine", now, "abc123", body.as_bytes());
+        let captured = || warp::test::request().method("POST").path("/mine")
+            .header(api::OPERATOR_KEY_HEADER, hex::encode(operator.verifying_key().as_bytes()))
+            .header(api::OPERATOR_SIGNATURE_HEADER, hex::encode(operator.sign(&message).to_bytes()))
+            .header(api::OPERATOR_TIMESTAMP_HEADER, now.to_string())
+            .header(api::OPERATOR_NONCE_HEADER, "abc123")
+            .body(body.clone());
+
+        assert_eq!(captured().reply(&api).await.status(), 200);
+        let replay = captured().reply(&api).await;
+        assert_eq!(replay.status(), 401);
+        let message: serde_json::Value = serde_json::from_slice(replay.body()).unwrap();
+        assert_eq!(message["error"]["message"], "replayed operator request");
+        let chain: serde_json::Value = serde_json::from_slice(warp::test::request().path("/chain").reply(&api).await.body()).unwrap();
+        assert_eq!(chain["chain"].as_array().map(Vec::len), Some(2));
+    }
+
+    // runs calls whose payload is "ok", and fails the rest
+    #[derive(Debug)]
+    struct EchoRuntime;
+
+    impl ContractRuntime for EchoRuntime {
+        fn execute(&mut self, vm: ContractVm, tx: &Transaction) -> Execution {
+            match tx.payload.as_deref() {
+                Some("ok") => Execution { success: true, gas_used: 21, output: format!("{:?}:{}", vm, tx.to), events: vec!["called".to_string()] },
+                _ => Execution { gas_used: 5, output: "execution reverted".to_string(), ..Execution::default() },
+            }
+        }
+    }
+
+    #[test]
+    fn transaction_kinds_round_trip_and_follow_their_rules() {
+        let contract = |tx_type, to: &str| Transaction { payload: Some("00".to_string()), tx_type, ..transfer(&addr("alice"), to, 0) };
+        let kinds = [
+            transfer(&addr("alice"), &addr("bob"), 1),
+            validator_tx(&addr("gov"), &addr("v2"), TxType::AddValidator),
+            Transaction { nonce: 1, ..validator_tx(&addr("gov"), &addr("v1"), TxType::RemoveValidator) },
+            Transaction { nonce: 1, ..contract(TxType::ContractCall { vm: ContractVm::Evm }, "0xcontract") },
+            Transaction { nonce: 2, ..contract(TxType::ContractDeploy { vm: ContractVm::Wasm }, "") },
+        ];
+        for tx in &kinds {
+            let json = serde_json::to_string(tx).unwrap();
+            let back: Transaction = serde_json::from_str(&json).unwrap();
+            assert_eq!((back.tx_type.clone(), back.hash()), (tx.tx_type.clone(), tx.hash()));
+        }
+        // plain transfers predate `tx_type` and still parse without it
+        let legacy: Transaction = serde_json::from_str(r#"{"from":"alice","to":"bob","amount":1,"payload":null}"#).unwrap();
+        assert_eq!(legacy.tx_type, TxType::Transfer);
+
+        let mut bc = Blockchain::with_governance(vec![addr("v1")], vec![addr("gov")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.accounts.insert(addr("gov"), Account { balance: 0, nonce: 0 });
+        for tx in kinds {
+            bc.add_transaction(signed(tx)).unwrap();
+        }
+        let rejected = [
+            (transfer(&addr("alice"), "", 1), "transfers need a recipient"),
+            (Transaction { amount: 5, ..validator_tx(&addr("gov"), &addr("v3"), TxType::AddValidator) }, "validator changes carry no amount"),
+            (validator_tx(&addr("gov"), "", TxType::RemoveValidator), "validator changes need a validator address"),
+            (Transaction { tx_type: TxType::Coinbase, ..transfer(&addr("alice"), &addr("alice"), 1) }, "coinbase transactions are created by the miner"),
+            (Transaction { amount: 1, ..contract(TxType::ContractCall { vm: ContractVm::Evm }, "0xcontract") }, "contract transactions carry no amount"),
+            (contract(TxType::ContractDeploy { vm: ContractVm::Wasm }, "0xcontract"), "deployments take no recipient"),
+        ];
+        for (tx, error) in rejected {
+            assert_eq!(bc.add_transaction(signed(tx)), Err(error.to_string()));
+        }
+
+        let block = mine(&mut bc, "v1").unwrap();
+        let deploy = block.transactions.iter().find(|tx| matches!(tx.tx_type, TxType::ContractDeploy { .. })).unwrap();
+        assert!(!bc.receipt(&deploy.hash()).unwrap().status);
+        assert_eq!(bc.validators, vec![addr("v2")]);
+    }
+
+    #[tokio::test]
+    async fn contract_calls_produce_receipts() {
+        let call = |payload: &str| Transaction {
+            payload: Some(payload.to_string()),
+            tx_type: TxType::ContractCall { vm: ContractVm::Wasm },
+            ..transfer(&addr("alice"), "0xcontract", 0)
+        };
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 0, nonce: 0 });
+        bc.runtime = Some(Box::new(EchoRuntime));
+        assert_eq!(bc.add_transaction(signed(Transaction { payload: None, ..call("ok") })), Err("contract transactions need a payload".to_string()));
+        let (ok, bad) = (signed(call("ok")), signed(Transaction { nonce: 1, ..call("boom") }));
+        bc.add_transaction(ok.clone()).unwrap();
+        bc.add_transaction(bad.clone()).unwrap();
+        bc.add_transaction(signed(Transaction { nonce: 2, ..transfer(&addr("alice"), &addr("bob"), 0) })).unwrap();
+        mine(&mut bc, "v1").unwrap();
+
+        assert_eq!(bc.receipt(&ok.hash()), Some(&TxReceipt {
+            tx_hash: ok.hash(),
+            block_index: 1,
+            status: true,
+            gas_used: 21,
+            output: "Wasm:0xcontract".to_string(),
+            events: vec!["called".to_string()],
+        }));
+        let failed = bc.receipt(&bad.hash()).unwrap();
+        assert!(!failed.status);
+        assert_eq!((failed.gas_used, failed.output.as_str()), (5, "execution reverted"));
+        assert!(bc.receipt(&transfer(&addr("alice"), &addr("bob"), 0).hash()).is_none());
+
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+        let found = warp::test::request().path(&format!("/receipt/{}", bad.hash())).reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(found.body()).unwrap();
+        assert_eq!((body["status"].as_bool(), body["output"].as_str()), (Some(false), Some("execution reverted")));
+        let missing = warp::test::request().path("/receipt/deadbeef").reply(&api).await;
+        assert_eq!(missing.status(), 404);
+
+        // without a runtime the call is still mined, as a failure
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 0, nonce: 0 });
+        bc.add_transaction(ok.clone()).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        assert_eq!(bc.receipt(&ok.hash()).unwrap().output, "no contract runtime configured");
+    }
+
+    #[tokio::test]
+    async fn validate_route_reports_tampering() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        for _ in 0..3 {
+            mine(&mut bc, "v1").unwrap();
+        }
+        let state = Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap()));
+        let api = routes(Arc::clone(&state));
+        let validate = || async { serde_json::from_slice::<serde_json::Value>(warp::test::request().path("/validate").reply(&api).await.body()).unwrap() };
+        assert_eq!(validate().await, serde_json::json!({"valid": true}));
+
+        state.lock().unwrap().chain[2].timestamp += 1;
+        assert_eq!(validate().await, serde_json::json!({"valid": false, "error": "block 2 has an invalid seal"}));
+
+        // a block resealed at a skipped height is caught by its index
+        let mut s = state.lock().unwrap();
+        s.chain.truncate(3);
+        let block = &mut s.chain[2];
+        block.index = 5;
+        block.hash = block.compute_hash();
+        assert_eq!(s.validate(), Err("block 5 follows block 1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn blocks_are_readable_by_height_and_head() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 3))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        mine(&mut bc, "v1").unwrap();
+        let hashes: Vec<String> = bc.chain.iter().map(|b| b.hash.clone()).collect();
+        let api = routes(Arc::new(Mutex::new(bc)));
+
+        let res = warp::test::request().path("/block/1").reply(&api).await;
+        let block: Block = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(block.hash, hashes[1]);
+        assert_eq!(block.transactions[1].hash(), signed(transfer(&addr("alice"), &addr("bob"), 3)).hash());
+
+        let res = warp::test::request().path("/block/3").reply(&api).await;
+        assert_eq!(res.status(), 404);
+        let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["error"]["message"], "block not found");
+
+        let res = warp::test::request().path("/chain/head").reply(&api).await;
+        let head: Block = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(head.index, 2);
+        assert_eq!(head.hash, hashes[2]);
+    }
+
+    #[tokio::test]
+    async fn raw_block_bytes_rehash_to_the_block_hash() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.set_hash_algo(HashAlgo::Blake3).unwrap();
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 3))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        let hashes: Vec<String> = bc.chain.iter().map(|b| b.hash.clone()).collect();
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+
+        for (height, hash) in hashes.iter().enumerate() {
+            let res = warp::test::request().path(&format!("/block/{}/raw", height)).reply(&api).await;
+            assert_eq!(res.headers()["content-type"], "application/octet-stream");
+            assert_eq!(&blake3::hash(res.body()).to_hex().to_string(), hash);
+        }
+        assert_eq!(warp::test::request().path("/block/9/raw").reply(&api).await.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn genesis_allocation_funds_accounts_and_bounds_spending() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.signer = Some(key("v1"));
+        bc.set_genesis_allocation(BTreeMap::from([(addr("alice"), 100)])).unwrap();
+        assert_eq!(bc.chain[0].state_root, bc.state_root());
+        assert_eq!(bc.chain[0].hash, bc.chain[0].compute_hash());
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+        let balance = |address: String| {
+            let api = api.clone();
+            async move { serde_json::from_slice::<serde_json::Value>(warp::test::request().path(&format!("/balance/{}", address)).reply(&api).await.body()).unwrap() }
+        };
+        let submit = |tx: Transaction| warp::test::request().method("POST").path("/tx").json(&signed(tx)).reply(&api);
+
+        assert_eq!(submit(transfer(&addr("alice"), &addr("bob"), 60)).await.status(), 200);
+        let alice = balance(addr("alice")).await;
+        assert_eq!((alice["balance"].as_u64(), alice["available"].as_u64()), (Some(100), Some(40)));
+        // affordable against the confirmed balance, not once the pending spend is counted
+        let overdraft = submit(transfer(&addr("alice"), &addr("carol"), 50)).await;
+        assert_eq!(overdraft.status(), 400);
+
+        warp::test::request().method("POST").path("/mine").json(&serde_json::json!({"validator": &addr("v1")})).reply(&api).await;
+        let bob = balance(addr("bob")).await;
+        assert_eq!((bob["balance"].as_u64(), bob["available"].as_u64()), (Some(60), Some(60)));
+        assert_eq!(balance(addr("nobody")).await["balance"], 0);
+    }
+
+    #[tokio::test]
+    async fn wait_for_block_wakes_on_mine_and_times_out() {
+        let bc = Blockchain { signer: Some(key("v1")), ..Blockchain::new(vec![addr("v1")]) };
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+        let body = |res: &warp::http::Response<warp::hyper::body::Bytes>| serde_json::from_slice::<serde_json::Value>(res.body()).unwrap();
+
+        let idle = warp::test::request().path("/wait-for-block?current=0&timeout_ms=50").reply(&api).await;
+        assert_eq!(body(&idle), serde_json::json!({"height": 0, "timed_out": true}));
+
+        let waiter = tokio::spawn({
+            let api = api.clone();
+            async move { warp::test::request().path("/wait-for-block?current=0&timeout_ms=5000").reply(&api).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        let mined = warp::test::request().method("POST").path("/mine")
+            .json(&serde_json::json!({"validator": &addr("v1")}))
+            .reply(&api).await;
+        assert_eq!(mined.status(), 200);
+        let woken = tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert_eq!(body(&woken), serde_json::json!({"height": 1, "timed_out": false}));
+
+        let behind = warp::test::request().path("/wait-for-block?current=0").reply(&api).await;
+        assert_eq!(body(&behind)["height"], 1);
+        let missing = warp::test::request().path("/wait-for-block").reply(&api).await;
+        assert_eq!(missing.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn events_replay_missed_then_continue_live() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.signer = Some(key("v1"));
+        bc.accounts.insert(addr("alice"), Account { balance: 100, nonce: 0 });
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+        let body = |res: &warp::http::Response<warp::hyper::body::Bytes>| serde_json::from_slice::<serde_json::Value>(res.body()).unwrap();
+        let seqs = |v: &serde_json::Value| v["events"].as_array().unwrap().iter().map(|e| e["seq"].as_u64().unwrap()).collect::<Vec<_>>();
+        let mine = || warp::test::request().method("POST").path("/mine").json(&serde_json::json!({"validator": &addr("v1")}));
+
+        let first = body(&warp::test::request().path("/events?since=0&timeout_ms=50").reply(&api).await);
+        assert_eq!((seqs(&first), first["timed_out"].clone()), (vec![], serde_json::json!(true)));
+
+        // the client saw the first block, then went away for two more
+        mine().reply(&api).await;
+        let seen = body(&warp::test::request().path("/events?since=0").reply(&api).await);
+        assert_eq!(seqs(&seen), vec![1]);
+        warp::test::request().method("POST").path("/tx").json(&signed(transfer(&addr("alice"), &addr("bob"), 5))).reply(&api).await;
+        mine().reply(&api).await;
+        mine().reply(&api).await;
+        // a retried /mine for a mined height adds nothing
+        warp::test::request().method("POST").path("/mine").json(&serde_json::json!({"validator": &addr("v1"), "height": 3})).reply(&api).await;
+
+        let replay = body(&warp::test::request().path("/events?since=1").reply(&api).await);
+        assert_eq!(seqs(&replay), vec![2, 3, 4]);
+        assert_eq!(replay["events"][0], serde_json::json!({"seq": 2, "type": "block", "height": 2, "hash": replay["events"][0]["hash"], "tx_count": 2}));
+        assert_eq!(replay["events"][1]["type"], "transaction");
+        assert_eq!((replay["latest"].as_u64(), replay["missed"].as_bool()), (Some(4), Some(false)));
+
+        let live = tokio::spawn({
+            let api = api.clone();
+            async move { warp::test::request().path("/events?since=4&timeout_ms=5000").reply(&api).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!live.is_finished());
+        mine().reply(&api).await;
+        let live = tokio::time::timeout(Duration::from_secs(1), live).await.unwrap().unwrap();
+        assert_eq!(seqs(&body(&live)), vec![5]);
+
+        // a subscriber that fell behind the ring buffer is told it missed events
+        let mut log = EventLog::new(2);
+        for height in 0..3 {
+            log.push(events::EventKind::Block { height, hash: String::new(), tx_count: 1 });
+        }
+        let (kept, missed) = log.since(0);
+        assert_eq!((kept.iter().map(|e| e.seq).collect::<Vec<_>>(), missed), (vec![2, 3], true));
+        assert!(!log.since(1).1);
+    }
+
+    #[test]
+    fn event_numbering_carries_on_after_a_restart() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 100, nonce: 0 });
+        let mut live = EventLog::default();
+        live.record_block(&mine(&mut bc, "v1").unwrap());
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 5))).unwrap();
+        live.record_block(&mine(&mut bc, "v1").unwrap());
+
+        let rebuilt = EventLog::from_chain(&bc.chain, events::EVENT_LOG_CAPACITY);
+        assert_eq!(rebuilt.latest(), 3);
+        assert_eq!(rebuilt.since(0), live.since(0));
+        // a cursor beyond the newest event can't be trusted to resume from
+        assert_eq!(rebuilt.since(7), (vec![], true));
+        assert!(!rebuilt.since(3).1);
+    }
+
+    #[tokio::test]
+    async fn peer_endpoints_require_matching_major_version() {
+        let (bc, checkpoint) = checkpoint_chain();
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+        let submit = |version: &'static str, seed: u8| warp::test::request().method("POST").path("/checkpoint")
+            .header(api::VERSION_HEADER, version)
+            .json(&sign_checkpoint(seed, &checkpoint));
+
+        let newer = submit("2.0.0", 1).reply(&api).await;
+        assert_eq!(newer.status(), 400);
+        let error: serde_json::Value = serde_json::from_slice(newer.body()).unwrap();
+        assert!(error["error"]["message"].as_str().unwrap().contains("incompatible protocol version 2.0.0"));
+        assert_eq!(newer.headers()[api::VERSION_HEADER], api::NEONET_PROTOCOL_VERSION);
+
+        let same_major = submit("1.7.3", 1).reply(&api).await;
+        assert_eq!(same_major.status(), 200);
+        assert_eq!(same_major.headers()[api::VERSION_HEADER], api::NEONET_PROTOCOL_VERSION);
+
+        let status = warp::test::request().path("/status").reply(&api).await;
+        let status: serde_json::Value = serde_json::from_slice(status.body()).unwrap();
+        assert_eq!(status["protocol_version"], api::NEONET_PROTOCOL_VERSION);
+        assert!(status["features"].as_array().unwrap().contains(&serde_json::json!("pqc")));
+        assert_eq!(status["height"], 2);
+    }
+
+    #[test]
+    fn genesis_hash_is_reproducible_and_covers_every_parameter() {
+        let config = || {
+            let mut bc = Blockchain::with_governance(vec![addr("v1"), addr("v2")], vec![addr("gov")]);
+            bc.block_reward = 5;
+            bc.set_genesis_allocation(BTreeMap::from([(addr("alice"), 100)])).unwrap();
+            bc
+        };
+        // built separately, yet down to the genesis block itself the two agree
+        let (a, b) = (config(), config());
+        assert_eq!(a.genesis_hash(), b.genesis_hash());
+        assert_eq!(a.chain[0].hash, b.chain[0].hash);
+
+        let changes: Vec<fn(&mut Blockchain)> = vec![
+            |bc| bc.chain_id = "neonet-test".into(),
+            |bc| bc.validators.push(addr("v3")),
+            |bc| bc.governance.clear(),
+            |bc| bc.set_genesis_allocation(BTreeMap::from([(addr("alice"), 101)])).unwrap(),
+            |bc| bc.set_hash_algo(HashAlgo::Blake3).unwrap(),
+            |bc| bc.set_genesis_time(1_700_000_000).unwrap(),
+            |bc| bc.consensus = ConsensusKind::ProofOfWork { difficulty: 1 },
+            |bc| bc.signature_scheme = SignatureScheme::Hybrid,
+            |bc| bc.block_reward = 6,
+            |bc| bc.min_base_fee = 1,
+            |bc| bc.max_block_txs = 10,
+            |bc| bc.max_block_bytes = 10,
+        ];
+        for change in changes {
+            let mut changed = config();
+            change(&mut changed);
+            assert_ne!(changed.genesis_hash(), a.genesis_hash());
+        }
+
+        // once opened the fingerprint stays put while the validator set evolves
+        let mut opened = Blockchain::open(Box::new(MemoryStore::default()), config).unwrap();
+        opened.validators.push(addr("v3"));
+        assert_eq!(opened.genesis_hash(), a.genesis_hash());
+    }
+
+    #[tokio::test]
+    async fn peers_on_another_genesis_are_rejected() {
+        let (bc, checkpoint) = checkpoint_chain();
+        let ours = bc.genesis_hash();
+        let api = routes(Arc::new(Mutex::new(Blockchain::open(Box::new(MemoryStore::default()), || bc).unwrap())));
+
+        let status = warp::test::request().path("/status").reply(&api).await;
+        let status: serde_json::Value = serde_json::from_slice(status.body()).unwrap();
+        assert_eq!(status["genesis_hash"], ours);
+        assert_eq!(status["chain_id"], "neonet");
+
+        let submit = |genesis: &str| warp::test::request().method("POST").path("/checkpoint")
+            .header(api::GENESIS_HEADER, genesis)
+            .json(&sign_checkpoint(1, &checkpoint));
+        let res = submit(&"0".repeat(64)).reply(&api).await;
+        assert_eq!(res.status(), 400);
+        let error: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(error["error"]["message"].as_str().unwrap().starts_with("genesis mismatch"));
+        assert_eq!(submit(&ours).reply(&api).await.status(), 200);
+    }
+
+    fn exercise_storage(mut store: Box<dyn Storage>) {
+        assert!(store.load().unwrap().is_none());
+
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 4))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        store.save(&bc).unwrap();
+        bc.add_transaction(signed(Transaction { nonce: 1, ..transfer(&addr("alice"), &addr("carol"), 1) })).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        bc.add_transaction(signed(transfer(&addr("bob"), &addr("carol"), 2))).unwrap();
+        store.save(&bc).unwrap();
+
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&bc).unwrap());
+        assert_eq!(loaded.pending.len(), 1);
+
+        let mut reopened = Blockchain::open(store, || panic!("store is not empty")).unwrap();
+        assert!(reopened.find_transaction(&bc.chain[2].transactions[1].hash()).is_some());
+        mine(&mut reopened, "v1").unwrap();
+        reopened.persist().unwrap();
+        let stored = reopened.storage.as_ref().unwrap().load().unwrap().unwrap();
+        assert_eq!(stored.chain.len(), 4);
+        assert!(stored.validate().is_ok());
+    }
+
+    #[test]
+    fn restart_keeps_only_still_valid_pending_transactions() {
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        bc.accounts.insert(addr("alice"), Account { balance: 10, nonce: 0 });
+        bc.add_transaction(signed(transfer(&addr("alice"), &addr("bob"), 4))).unwrap();
+        mine(&mut bc, "v1").unwrap();
+        let kept = signed(Transaction { nonce: 1, ..transfer(&addr("alice"), &addr("carol"), 5) });
+        bc.add_transaction(kept.clone()).unwrap();
+        // entries the node would no longer accept: a copy of a mined transfer,
+        // and one that overspends once `kept` is counted
+        let mined = bc.chain[1].transactions[1].clone();
+        let overspend = signed(Transaction { nonce: 2, ..transfer(&addr("alice"), &addr("dave"), 2) });
+        bc.pending.extend([mined.clone(), overspend.clone()]);
+        let mut store = MemoryStore::default();
+        store.save(&bc).unwrap();
+
+        let mut restarted = Blockchain::open(Box::new(store), || panic!("store is not empty")).unwrap();
+        assert_eq!(restarted.pending.iter().map(Transaction::hash).collect::<Vec<_>>(), vec![kept.hash()]);
+        assert!(restarted.revalidate_pending().is_empty());
+
+        restarted.pending.extend([mined.clone(), overspend.clone()]);
+        assert_eq!(restarted.revalidate_pending(), vec![
+            (mined.hash(), "already mined".to_string()),
+            (overspend.hash(), "insufficient balance".to_string()),
+        ]);
+        let block = mine(&mut restarted, "v1").unwrap();
+        assert_eq!(block.transactions[1].hash(), kept.hash());
+        assert!(restarted.validate().is_ok());
+    }
+
+    #[test]
+    fn keystore_key_signs_blocks_and_needs_its_passphrase() {
+        let dir = std::env::temp_dir().join(format!("neonet-keystore-{}", std::process::id()));
+        let keystore = Keystore::new(&dir);
+        let address = keystore.generate("correct horse").unwrap();
+        assert_eq!(keystore.list().unwrap(), vec![address.clone()]);
+        let err = keystore.unlock(&address, "battery staple").unwrap_err();
+        assert_eq!(err, "wrong passphrase or corrupted keyfile");
+
+        let mut bc = Blockchain::new(vec![address.clone()]);
+        bc.signer = Some(keystore.unlock(&address, "correct horse").unwrap());
+        let block = bc.mine_block(address.clone()).unwrap();
+        assert!(block.signature.is_some());
+        assert!(block.verify_signature().is_ok());
+        assert!(bc.validate().is_ok());
+
+        bc.chain[1].signature = Some(hex::encode([0u8; 64]));
+        assert_eq!(bc.validate(), Err("block 1 has an invalid signature".to_string()));
+
+        // importing a known secret yields its address
+        let secret = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let imported = keystore.import(&hex::encode(secret.to_bytes()), "pw").unwrap();
+        assert_eq!(imported, address_from_pubkey(secret.verifying_key().as_bytes()));
+        assert_eq!(keystore.unlock(&imported, "pw").unwrap().to_bytes(), secret.to_bytes());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn storage_backends_share_save_load_contract() {
+        let dir = std::env::temp_dir().join(format!("neonet-storage-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        exercise_storage(Box::new(MemoryStore::default()));
+        exercise_storage(Box::new(JsonFileStore::new(dir.join("chain.json"))));
+        exercise_storage(Box::new(SledStore::open(dir.join("chain.sled")).unwrap()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn json_store_refuses_a_corrupted_file() {
+        let dir = std::env::temp_dir().join(format!("neonet-corrupt-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("chain.json");
+        let mut store = JsonFileStore::new(&path);
+        let mut bc = Blockchain::new(vec![addr("v1")]);
+        mine(&mut bc, "v1").unwrap();
+        store.save(&bc).unwrap();
+        assert!(!dir.join("chain.json.tmp").exists());
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, saved.replacen("\"index\": 1", "\"index\": 7", 1)).unwrap();
+        assert!(store.load().unwrap_err().contains("checksum mismatch"));
+        std::fs::write(&path, &saved[..saved.len() / 2]).unwrap();
+        assert!(store.load().is_err());
+
+        std::fs::write(&path, saved).unwrap();
+        assert_eq!(store.load().unwrap().unwrap().chain.len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
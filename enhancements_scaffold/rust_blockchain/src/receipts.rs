@@ -0,0 +1,54 @@
+//! Outcome of contract calls mined into blocks, recorded as one receipt per call.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Transaction;
+
+/// Which virtual machine a contract call targets.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractVm {
+    Wasm,
+    Evm,
+}
+
+/// What a runtime reports for one call; a failed call is still mined and pays its fee.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Execution {
+    pub success: bool,
+    pub gas_used: u64,
+    // return data on success, the error message on failure
+    pub output: String,
+    pub events: Vec<String>,
+}
+
+/// Executes mined `ContractCall` and `ContractDeploy` transactions, e.g. by
+/// forwarding them to the WASM VM or the EVM adapter.
+pub trait ContractRuntime: Send + fmt::Debug {
+    fn execute(&mut self, vm: ContractVm, tx: &Transaction) -> Execution;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TxReceipt {
+    pub tx_hash: String,
+    pub block_index: u64,
+    pub status: bool,
+    pub gas_used: u64,
+    pub output: String,
+    pub events: Vec<String>,
+}
+
+impl TxReceipt {
+    pub fn new(tx_hash: String, block_index: u64, execution: Execution) -> Self {
+        TxReceipt {
+            tx_hash,
+            block_index,
+            status: execution.success,
+            gas_used: execution.gas_used,
+            output: execution.output,
+            events: execution.events,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Transaction signature schemes; the chain's scheme fixes what key and signature bytes a sender supplies.
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use pqcrypto_dilithium::dilithium3;
+use pqcrypto_traits::sign::{DetachedSignature, PublicKey};
+use serde::{Deserialize, Serialize};
+
+const ED25519_KEY_LEN: usize = 32;
+const ED25519_SIG_LEN: usize = 64;
+
+/// Chosen at genesis and persisted with the chain, like `ConsensusKind`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum SignatureScheme {
+    #[default]
+    Ed25519,
+    // Ed25519 + Dilithium3, laid out as rust-core's HybridPublicKey/HybridSignature
+    // fields concatenated: `ed25519 || dilithium3`. Both halves must verify.
+    Hybrid,
+}
+
+impl SignatureScheme {
+    pub fn public_key_len(&self) -> usize {
+        match self {
+            SignatureScheme::Ed25519 => ED25519_KEY_LEN,
+            SignatureScheme::Hybrid => ED25519_KEY_LEN + dilithium3::public_key_bytes(),
+        }
+    }
+
+    pub fn signature_len(&self) -> usize {
+        match self {
+            SignatureScheme::Ed25519 => ED25519_SIG_LEN,
+            SignatureScheme::Hybrid => ED25519_SIG_LEN + dilithium3::signature_bytes(),
+        }
+    }
+
+    pub fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), String> {
+        if public_key.len() != self.public_key_len() {
+            return Err(format!("invalid public key length for {:?}", self));
+        }
+        if signature.len() != self.signature_len() {
+            return Err(format!("invalid signature length for {:?}", self));
+        }
+        let (ed_key, pq_key) = public_key.split_at(ED25519_KEY_LEN);
+        let (ed_sig, pq_sig) = signature.split_at(ED25519_SIG_LEN);
+
+        let ed_key = VerifyingKey::from_bytes(ed_key.try_into().unwrap()).map_err(|e| format!("invalid public key: {}", e))?;
+        ed_key.verify(message, &Signature::from_bytes(ed_sig.try_into().unwrap()))
+            .map_err(|_| "invalid signature".to_string())?;
+
+        if *self == SignatureScheme::Hybrid {
+            let pq_key = dilithium3::PublicKey::from_bytes(pq_key).map_err(|_| "invalid Dilithium public key".to_string())?;
+            let pq_sig = dilithium3::DetachedSignature::from_bytes(pq_sig).map_err(|_| "invalid Dilithium signature".to_string())?;
+            dilithium3::verify_detached_signature(&pq_sig, message, &pq_key)
+                .map_err(|_| "invalid signature".to_string())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+//! Persistence backends for the chain, chosen per deployment.
+
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::{Block, Blockchain};
+
+pub trait Storage: Send + fmt::Debug {
+    /// Persist `bc` so a later `load` returns an equal chain.
+    fn save(&mut self, bc: &Blockchain) -> Result<(), String>;
+
+    /// The last saved chain, or `None` if nothing has been saved yet.
+    fn load(&self) -> Result<Option<Blockchain>, String>;
+}
+
+/// The whole chain as one pretty-printed JSON document followed by a
+/// `sha256:<hex>` line over it. Saves go to a sibling temporary file that is
+/// then renamed over the target, so a crash leaves the previous save intact.
+#[derive(Debug)]
+pub struct JsonFileStore {
+    path: PathBuf,
+}
+
+const CHECKSUM_PREFIX: &str = "\nsha256:";
+
+impl JsonFileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JsonFileStore { path: path.into() }
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        self.path.with_file_name(name)
+    }
+}
+
+impl Storage for JsonFileStore {
+    fn save(&mut self, bc: &Blockchain) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(bc).map_err(|e| e.to_string())?;
+        let contents = format!("{}{}{}\n", json, CHECKSUM_PREFIX, hex::encode(Sha256::digest(&json)));
+        let tmp = self.temp_path();
+        let write = || -> std::io::Result<()> {
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&tmp, &self.path)
+        };
+        write().map_err(|e| format!("writing {}: {}", self.path.display(), e))
+    }
+
+    fn load(&self) -> Result<Option<Blockchain>, String> {
+        if !Path::new(&self.path).exists() {
+            return Ok(None);
+        }
+        let s = fs::read_to_string(&self.path).map_err(|e| format!("reading {}: {}", self.path.display(), e))?;
+        // files written before checksums were added are plain JSON
+        let json = match s.rsplit_once(CHECKSUM_PREFIX) {
+            Some((json, checksum)) => {
+                if checksum.trim_end() != hex::encode(Sha256::digest(json)) {
+                    return Err(format!("{} is corrupt: checksum mismatch", self.path.display()));
+                }
+                json
+            }
+            None => &s,
+        };
+        serde_json::from_str(json).map(Some).map_err(|e| format!("parsing {}: {}", self.path.display(), e))
+    }
+}
+
+/// Blocks keyed by big-endian height, so saves only write blocks not yet stored;
+/// everything else is kept as one JSON value under `state`.
+#[derive(Debug)]
+pub struct SledStore {
+    db: sled::Db,
+    blocks: sled::Tree,
+}
+
+impl SledStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let db = sled::open(path).map_err(|e| e.to_string())?;
+        let blocks = db.open_tree("blocks").map_err(|e| e.to_string())?;
+        Ok(SledStore { db, blocks })
+    }
+}
+
+impl Storage for SledStore {
+    fn save(&mut self, bc: &Blockchain) -> Result<(), String> {
+        let stored = self.blocks.len();
+        for block in bc.chain.iter().skip(stored) {
+            let value = serde_json::to_vec(block).map_err(|e| e.to_string())?;
+            self.blocks.insert(block.index.to_be_bytes(), value).map_err(|e| e.to_string())?;
+        }
+        let mut state = serde_json::to_value(bc).map_err(|e| e.to_string())?;
+        state["chain"] = serde_json::Value::Array(vec![]);
+        let state = serde_json::to_vec(&state).map_err(|e| e.to_string())?;
+        self.db.insert("state", state).map_err(|e| e.to_string())?;
+        self.db.flush().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<Blockchain>, String> {
+        let Some(state) = self.db.get("state").map_err(|e| e.to_string())? else {
+            return Ok(None);
+        };
+        let mut state: serde_json::Value = serde_json::from_slice(&state).map_err(|e| e.to_string())?;
+        let chain = self.blocks.iter()
+            .map(|entry| {
+                let (_, value) = entry.map_err(|e| e.to_string())?;
+                serde_json::from_slice::<Block>(&value).map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        state["chain"] = serde_json::to_value(chain).map_err(|e| e.to_string())?;
+        serde_json::from_value(state).map(Some).map_err(|e| e.to_string())
+    }
+}
+
+/// Nothing outlives the process; for tests and throwaway nodes.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    // serialized so a load hands back an independent copy
+    saved: Option<String>,
+}
+
+impl Storage for MemoryStore {
+    fn save(&mut self, bc: &Blockchain) -> Result<(), String> {
+        self.saved = Some(serde_json::to_string(bc).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<Blockchain>, String> {
+        self.saved.as_deref().map(serde_json::from_str).transpose().map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,26 @@
+// Hybrid PQC throughput: `cargo bench --bench pqc`
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use neonet_core::pqc::{verify_hybrid_signature, HybridKeyPair};
+
+fn bench_pqc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pqc");
+    let keypair = HybridKeyPair::generate();
+    let public_key = keypair.public_key();
+    let message = b"NeoNet benchmark message";
+    let signature = keypair.sign(message);
+    let (_, ciphertext) = keypair.kyber_encapsulate();
+
+    group.bench_function("generate", |b| b.iter(HybridKeyPair::generate));
+    group.bench_function("sign", |b| b.iter(|| keypair.sign(black_box(message))));
+    group.bench_function("verify_hybrid_signature", |b| {
+        b.iter(|| verify_hybrid_signature(&public_key, black_box(message), &signature).unwrap())
+    });
+    group.bench_function("kyber_encapsulate", |b| b.iter(|| keypair.kyber_encapsulate()));
+    group.bench_function("kyber_decapsulate", |b| {
+        b.iter(|| keypair.kyber_decapsulate(black_box(&ciphertext)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_pqc);
+criterion_main!(benches);
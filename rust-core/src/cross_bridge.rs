@@ -0,0 +1,282 @@
+// Cross-runtime token bridge - locks value on the EVM side and credits a WASM contract
+// Mirrors the bridge records kept by the ai_registry contract (CROSS_BRIDGES)
+
+use serde::{Deserialize, Serialize};
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::evm_adapter::EVMAdapter;
+use crate::wasm_vm::WasmVM;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BridgeType {
+    TokenBridge,
+    DataBridge,
+    CallBridge,
+    StateBridge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeRecord {
+    // EVM account that escrows locked tokens
+    pub evm_contract: String,
+    pub wasm_contract: String,
+    pub bridge_type: BridgeType,
+    pub is_active: bool,
+}
+
+/// Node-level balances outside the EVM, e.g. an address -> balance account map.
+pub trait NativeLedger {
+    fn balance(&self, account: &str) -> u128;
+    fn set_balance(&mut self, account: &str, balance: u128);
+}
+
+impl NativeLedger for BTreeMap<String, u128> {
+    fn balance(&self, account: &str) -> u128 {
+        self.get(account).copied().unwrap_or(0)
+    }
+
+    fn set_balance(&mut self, account: &str, balance: u128) {
+        self.insert(account.to_string(), balance);
+    }
+}
+
+pub struct CrossRuntimeBridge {
+    bridges: HashMap<String, BridgeRecord>,
+}
+
+/// WASM storage key holding the amount credited to `owner` through the bridge.
+pub fn credit_key(owner: &str) -> String {
+    format!("bridge:{}", owner)
+}
+
+impl CrossRuntimeBridge {
+    pub fn new() -> Self {
+        CrossRuntimeBridge {
+            bridges: HashMap::new(),
+        }
+    }
+
+    pub fn register_bridge(&mut self, bridge_id: String, bridge: BridgeRecord) -> Result<()> {
+        if self.bridges.contains_key(&bridge_id) {
+            return Err(anyhow!("Bridge already registered"));
+        }
+        self.bridges.insert(bridge_id, bridge);
+        Ok(())
+    }
+
+    pub fn get_bridge(&self, bridge_id: &str) -> Option<&BridgeRecord> {
+        self.bridges.get(bridge_id)
+    }
+
+    fn token_bridge(&self, bridge_id: &str) -> Result<&BridgeRecord> {
+        let bridge = self.bridges.get(bridge_id)
+            .ok_or_else(|| anyhow!("Bridge not found"))?;
+        if !bridge.is_active {
+            return Err(anyhow!("Bridge is not active"));
+        }
+        if bridge.bridge_type != BridgeType::TokenBridge {
+            return Err(anyhow!("Bridge does not carry tokens"));
+        }
+        Ok(bridge)
+    }
+
+    /// Amount `owner` currently holds on the WASM side of `bridge_id`.
+    pub fn credited(&self, wasm: &WasmVM, bridge_id: &str, owner: &str) -> Result<u64> {
+        let bridge = self.bridges.get(bridge_id)
+            .ok_or_else(|| anyhow!("Bridge not found"))?;
+        let contract = wasm.get_contract(&bridge.wasm_contract)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
+        match contract.storage.get(&credit_key(owner)) {
+            Some(value) => value.parse().map_err(|_| anyhow!("Corrupt bridge credit")),
+            None => Ok(0),
+        }
+    }
+
+    /// Move `amount` from `owner` into the EVM escrow and credit the WASM contract.
+    pub fn lock_to_wasm(
+        &self,
+        evm: &mut EVMAdapter,
+        wasm: &mut WasmVM,
+        bridge_id: &str,
+        owner: &str,
+        amount: u128,
+    ) -> Result<()> {
+        let bridge = self.token_bridge(bridge_id)?;
+        // WASM balances are u64
+        let wasm_amount = u64::try_from(amount).map_err(|_| anyhow!("Amount exceeds WASM balance range"))?;
+        let credited = self.credited(wasm, bridge_id, owner)?
+            .checked_add(wasm_amount)
+            .ok_or_else(|| anyhow!("Bridge credit overflow"))?;
+        // the EVM side moves first, so everything the WASM side could refuse is checked here
+        let contract = wasm.get_contract(&bridge.wasm_contract)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
+        contract.balance.checked_add(wasm_amount)
+            .ok_or_else(|| anyhow!("Contract balance overflow"))?;
+        evm.get_balance(&bridge.evm_contract)?;
+
+        evm.transfer(owner, &bridge.evm_contract, amount)?;
+        wasm.deposit(&bridge.wasm_contract, wasm_amount)?;
+        wasm.set_storage(&bridge.wasm_contract, &credit_key(owner), credited.to_string())
+    }
+
+    /// Reverse of `lock_to_wasm`: debit the WASM credit and release escrow to `owner`.
+    pub fn release_to_evm(
+        &self,
+        evm: &mut EVMAdapter,
+        wasm: &mut WasmVM,
+        bridge_id: &str,
+        owner: &str,
+        amount: u128,
+    ) -> Result<()> {
+        let bridge = self.token_bridge(bridge_id)?;
+        let wasm_amount = u64::try_from(amount).map_err(|_| anyhow!("Amount exceeds WASM balance range"))?;
+        let credited = self.credited(wasm, bridge_id, owner)?;
+        if credited < wasm_amount {
+            return Err(anyhow!("Insufficient bridge credit"));
+        }
+        if evm.get_balance(&bridge.evm_contract)? < amount {
+            return Err(anyhow!("Insufficient escrow balance"));
+        }
+        evm.get_balance(owner)?;
+
+        wasm.withdraw(&bridge.wasm_contract, wasm_amount)?;
+        wasm.set_storage(&bridge.wasm_contract, &credit_key(owner), (credited - wasm_amount).to_string())?;
+        evm.transfer(&bridge.evm_contract, owner, amount)
+    }
+
+    /// Debit `node_account` and credit `evm_address` (created if new). All
+    /// checks run before either ledger changes.
+    pub fn deposit_to_evm(
+        &self,
+        native: &mut impl NativeLedger,
+        evm: &mut EVMAdapter,
+        node_account: &str,
+        evm_address: &str,
+        amount: u128,
+    ) -> Result<()> {
+        let balance = native.balance(node_account);
+        if balance < amount {
+            return Err(anyhow!("Insufficient native balance"));
+        }
+        evm.fund_account(evm_address, amount)?;
+        native.set_balance(node_account, balance - amount);
+        Ok(())
+    }
+
+    /// Reverse of `deposit_to_evm`.
+    pub fn withdraw_from_evm(
+        &self,
+        native: &mut impl NativeLedger,
+        evm: &mut EVMAdapter,
+        evm_address: &str,
+        node_account: &str,
+        amount: u128,
+    ) -> Result<()> {
+        let balance = native.balance(node_account).checked_add(amount)
+            .ok_or_else(|| anyhow!("Native balance overflow"))?;
+        evm.debit_account(evm_address, amount)?;
+        native.set_balance(node_account, balance);
+        Ok(())
+    }
+}
+
+impl Default for CrossRuntimeBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm_vm::GasSchedule;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+    const ESCROW: &str = "0x000000000000000000000000000000000000e5c0";
+
+    fn setup() -> (CrossRuntimeBridge, EVMAdapter, WasmVM) {
+        let mut evm = EVMAdapter::new();
+        evm.create_account(ALICE.to_string(), 1000).unwrap();
+        evm.create_account(ESCROW.to_string(), 0).unwrap();
+
+        let mut wasm = WasmVM::new(1000000, GasSchedule::default());
+        let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        wasm.deploy_contract("deployer", "wasm_token".to_string(), code).unwrap();
+
+        let mut bridge = CrossRuntimeBridge::new();
+        bridge.register_bridge("tokens".to_string(), BridgeRecord {
+            evm_contract: ESCROW.to_string(),
+            wasm_contract: "wasm_token".to_string(),
+            bridge_type: BridgeType::TokenBridge,
+            is_active: true,
+        }).unwrap();
+        (bridge, evm, wasm)
+    }
+
+    #[test]
+    fn test_lock_and_release_round_trip() {
+        let (bridge, mut evm, mut wasm) = setup();
+
+        bridge.lock_to_wasm(&mut evm, &mut wasm, "tokens", ALICE, 100).unwrap();
+        assert_eq!(evm.get_balance(ALICE).unwrap(), 900);
+        assert_eq!(evm.get_balance(ESCROW).unwrap(), 100);
+        assert_eq!(wasm.get_contract("wasm_token").unwrap().balance, 100);
+        assert_eq!(bridge.credited(&wasm, "tokens", ALICE).unwrap(), 100);
+
+        assert!(bridge.release_to_evm(&mut evm, &mut wasm, "tokens", ALICE, 101).is_err());
+
+        bridge.release_to_evm(&mut evm, &mut wasm, "tokens", ALICE, 100).unwrap();
+        assert_eq!(evm.get_balance(ALICE).unwrap(), 1000);
+        assert_eq!(evm.get_balance(ESCROW).unwrap(), 0);
+        assert_eq!(wasm.get_contract("wasm_token").unwrap().balance, 0);
+        assert_eq!(bridge.credited(&wasm, "tokens", ALICE).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_lock_that_would_overflow_the_contract_moves_nothing() {
+        let (bridge, mut evm, mut wasm) = setup();
+        wasm.deposit("wasm_token", u64::MAX - 50).unwrap();
+
+        let err = bridge.lock_to_wasm(&mut evm, &mut wasm, "tokens", ALICE, 100).unwrap_err();
+        assert_eq!(err.to_string(), "Contract balance overflow");
+        assert_eq!((evm.get_balance(ALICE).unwrap(), evm.get_balance(ESCROW).unwrap()), (1000, 0));
+        assert_eq!(wasm.get_contract("wasm_token").unwrap().balance, u64::MAX - 50);
+        assert_eq!(bridge.credited(&wasm, "tokens", ALICE).unwrap(), 0);
+        assert!(wasm.deposit("wasm_token", 51).is_err());
+    }
+
+    #[test]
+    fn test_deposit_and_withdraw_conserve_value() {
+        let (bridge, mut evm, _) = setup();
+        let mut native = BTreeMap::from([("alice".to_string(), 500u128)]);
+
+        bridge.deposit_to_evm(&mut native, &mut evm, "alice", ALICE, 200).unwrap();
+        assert_eq!(native.balance("alice"), 300);
+        assert_eq!(evm.get_balance(ALICE).unwrap(), 1200);
+
+        assert!(bridge.deposit_to_evm(&mut native, &mut evm, "alice", ALICE, 301).is_err());
+        assert!(bridge.withdraw_from_evm(&mut native, &mut evm, ALICE, "alice", 1201).is_err());
+        assert_eq!((native.balance("alice"), evm.get_balance(ALICE).unwrap()), (300, 1200));
+
+        bridge.withdraw_from_evm(&mut native, &mut evm, ALICE, "alice", 1200).unwrap();
+        assert_eq!(native.balance("alice"), 1500);
+        assert_eq!(evm.get_balance(ALICE).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_only_active_token_bridges_move_value() {
+        let (mut bridge, mut evm, mut wasm) = setup();
+        bridge.register_bridge("data".to_string(), BridgeRecord {
+            evm_contract: ESCROW.to_string(),
+            wasm_contract: "wasm_token".to_string(),
+            bridge_type: BridgeType::DataBridge,
+            is_active: true,
+        }).unwrap();
+        bridge.bridges.get_mut("tokens").unwrap().is_active = false;
+
+        assert!(bridge.lock_to_wasm(&mut evm, &mut wasm, "data", ALICE, 100).is_err());
+        assert!(bridge.lock_to_wasm(&mut evm, &mut wasm, "tokens", ALICE, 100).is_err());
+        assert_eq!(evm.get_balance(ALICE).unwrap(), 1000);
+    }
+}
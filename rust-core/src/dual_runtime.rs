@@ -6,7 +6,7 @@ use anyhow::{Result, anyhow};
 use std::collections::HashMap;
 
 use crate::evm_adapter::EVMAdapter;
-use crate::wasm_vm::WasmVM;
+use crate::wasm_vm::{GasSchedule, WasmVM};
 use crate::pqc::{HybridSignature, HybridPublicKey, verify_hybrid_signature};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,7 +65,7 @@ impl DualRuntimeRouter {
     pub fn new() -> Self {
         DualRuntimeRouter {
             evm: EVMAdapter::new(),
-            wasm: WasmVM::new(10_000_000),
+            wasm: WasmVM::new(10_000_000, GasSchedule::default()),
             routing_rules: HashMap::new(),
             execution_stats: ExecutionStats::default(),
         }
@@ -131,6 +131,7 @@ impl DualRuntimeRouter {
         } else {
             // Contract call
             self.evm.call_contract(&tx.from, &tx.to, tx.data.clone(), tx.value, tx.gas_limit)
+                .map(|receipt| receipt.output)
         };
 
         match result {
@@ -251,14 +252,14 @@ impl DualRuntimeRouter {
             }
             RuntimeType::WASM => {
                 let contract_id = format!("neo1{}", hex::encode(&tx.data[..20.min(tx.data.len())]));
-                self.wasm.deploy_contract(contract_id.clone(), tx.data.clone())?;
+                self.wasm.deploy_contract(&tx.from, contract_id.clone(), tx.data.clone())?;
                 Ok(contract_id)
             }
             RuntimeType::Hybrid => {
                 // Deploy to both runtimes
                 let evm_addr = self.evm.deploy_contract(&tx.from, tx.data.clone())?;
                 let wasm_id = format!("neo1{}", &evm_addr[2..]);
-                self.wasm.deploy_contract(wasm_id.clone(), tx.data.clone())?;
+                self.wasm.deploy_contract(&tx.from, wasm_id.clone(), tx.data.clone())?;
                 Ok(format!("{}|{}", evm_addr, wasm_id))
             }
         }
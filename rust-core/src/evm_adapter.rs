@@ -1,12 +1,15 @@
 // EVM Adapter for NeoNet - Full Ethereum Virtual Machine compatibility with revm
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use revm::{
-    primitives::{Address, U256, Bytecode, TransactTo, ExecutionResult, Output, Bytes},
-    Database, EVM, InMemoryDB,
+    interpreter::{CallInputs, Gas, InstructionResult, Interpreter, OPCODE_JUMPMAP},
+    primitives::{AccountInfo, Address, U256, Bytecode, TransactTo, ExecutionResult, Output, Bytes, SpecId, ResultAndState},
+    db::AccountState,
+    Database, DatabaseCommit, EVMData, Inspector, EVM, InMemoryDB,
 };
-use alloy_primitives::hex;
+use alloy_primitives::{hex, keccak256};
+use alloy_rlp::Encodable;
 use sha2::{Sha256, Digest};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -18,24 +21,218 @@ pub struct EVMAccount {
     pub storage: HashMap<String, String>,
 }
 
+/// Pre-funded state for one address, as in an Ethereum genesis `alloc` entry.
+/// Storage maps hex slot to hex value, e.g. `"0x0" => "0x2a"`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GenesisAccount {
+    pub balance: u128,
+    pub nonce: u64,
+    pub code: Vec<u8>,
+    pub storage: BTreeMap<String, String>,
+}
+
+/// Address -> initial account state.
+pub type GenesisAlloc = BTreeMap<String, GenesisAccount>;
+
+/// Outcome of a contract call, keyed by its transaction hash.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EVMReceipt {
+    pub tx_hash: [u8; 32],
+    pub success: bool,
+    pub gas_used: u64,
+    // gas used in the block up to and including this transaction
+    pub cumulative_gas_used: u64,
+    pub output: Vec<u8>,
+}
+
+/// A call whose signature has already been checked; `from` is the recovered sender.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EVMTransaction {
+    pub from: String,
+    pub to: String,
+    pub nonce: u64,
+    pub value: u128,
+    pub data: Vec<u8>,
+    pub gas_limit: u64,
+}
+
+/// Gas available to `trace_call`, which has no caller-supplied limit.
+pub const TRACE_GAS_LIMIT: u64 = 30_000_000;
+
+/// Total gas all calls in one block may reserve unless `set_block_gas_limit` says otherwise.
+pub const DEFAULT_BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// Furthest ahead of the sender's nonce a transaction may be queued.
+pub const MAX_QUEUED_NONCE_GAP: u64 = 64;
+
+/// Most transactions one sender may have queued at once.
+pub const MAX_QUEUED_PER_SENDER: usize = 16;
+
+/// One interpreted instruction.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceStep {
+    pub pc: usize,
+    pub opcode: u8,
+    pub op: String,
+    // gas left before the instruction runs
+    pub gas_remaining: u64,
+    // call depth, 0 for the top-level frame
+    pub depth: u64,
+    pub stack_len: usize,
+}
+
+/// A message call entered during execution, including the top-level one.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceCall {
+    pub depth: u64,
+    pub to: String,
+    pub input: Vec<u8>,
+    pub gas_limit: u64,
+    pub success: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CallTrace {
+    pub steps: Vec<TraceStep>,
+    pub calls: Vec<TraceCall>,
+    pub gas_used: u64,
+    pub success: bool,
+    pub output: Vec<u8>,
+}
+
+#[derive(Default)]
+struct StepTracer {
+    steps: Vec<TraceStep>,
+    calls: Vec<TraceCall>,
+    // indices into `calls` of frames not yet returned
+    open_calls: Vec<usize>,
+}
+
+impl<DB: Database> Inspector<DB> for StepTracer {
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        let opcode = interp.current_opcode();
+        self.steps.push(TraceStep {
+            pc: interp.program_counter(),
+            opcode,
+            op: OPCODE_JUMPMAP[opcode as usize].unwrap_or("INVALID").to_string(),
+            gas_remaining: interp.gas().remaining(),
+            depth: data.journaled_state.depth(),
+            stack_len: interp.stack.len(),
+        });
+        InstructionResult::Continue
+    }
+
+    fn call(&mut self, data: &mut EVMData<'_, DB>, inputs: &mut CallInputs) -> (InstructionResult, Gas, Bytes) {
+        self.open_calls.push(self.calls.len());
+        self.calls.push(TraceCall {
+            depth: data.journaled_state.depth(),
+            to: format!("{:?}", inputs.contract),
+            input: inputs.input.to_vec(),
+            gas_limit: inputs.gas_limit,
+            success: false,
+        });
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if let Some(index) = self.open_calls.pop() {
+            self.calls[index].success = ret.is_ok();
+        }
+        (ret, remaining_gas, out)
+    }
+}
+
+/// The mutable parts of the adapter as `apply_pending` left them, ahead of
+/// the committed state until `commit_pending` or `discard_pending`.
+struct PendingState {
+    accounts: HashMap<String, EVMAccount>,
+    receipts: HashMap<[u8; 32], EVMReceipt>,
+    queued: HashMap<String, BTreeMap<u64, EVMTransaction>>,
+    db: InMemoryDB,
+    cumulative_gas_used: u64,
+}
+
 pub struct EVMAdapter {
     accounts: HashMap<String, EVMAccount>,
+    receipts: HashMap<[u8; 32], EVMReceipt>,
+    // sender -> nonce -> transaction waiting for the nonce gap to close
+    queued: HashMap<String, BTreeMap<u64, EVMTransaction>>,
+    queue_future_nonces: bool,
     db: InMemoryDB,
     gas_price: u64,
     block_number: u64,
+    block_gas_limit: u64,
+    cumulative_gas_used: u64,
+    spec_id: SpecId,
+    pending: Option<PendingState>,
 }
 
 impl EVMAdapter {
     pub fn new() -> Self {
         EVMAdapter {
             accounts: HashMap::new(),
+            receipts: HashMap::new(),
+            queued: HashMap::new(),
+            queue_future_nonces: false,
             db: InMemoryDB::default(),
             gas_price: 20,
             block_number: 0,
+            block_gas_limit: DEFAULT_BLOCK_GAS_LIMIT,
+            cumulative_gas_used: 0,
+            spec_id: SpecId::LATEST,
+            pending: None,
         }
     }
 
+    /// Create an adapter whose accounts, code and storage start as `alloc` describes.
+    pub fn from_genesis(alloc: GenesisAlloc) -> Result<Self> {
+        let mut adapter = Self::new();
+        for (address, genesis) in alloc {
+            let addr = parse_address(&address)?;
+            let bytecode = Bytecode::new_raw(Bytes::from(genesis.code.clone()));
+            adapter.db.insert_account_info(addr, AccountInfo {
+                balance: U256::from(genesis.balance),
+                nonce: genesis.nonce,
+                code_hash: bytecode.hash_slow(),
+                code: Some(bytecode),
+            });
+            for (slot, value) in &genesis.storage {
+                adapter.db.insert_account_storage(addr, parse_word(slot)?, parse_word(value)?)?;
+            }
+
+            adapter.accounts.insert(address.clone(), EVMAccount {
+                address,
+                balance: genesis.balance,
+                nonce: genesis.nonce,
+                code: genesis.code,
+                storage: genesis.storage.into_iter().collect(),
+            });
+        }
+        Ok(adapter)
+    }
+
+    /// Create an adapter pinned to a specific hardfork. The spec controls the
+    /// gas schedule and which opcodes are available (e.g. PUSH0 from Shanghai).
+    pub fn with_spec(spec_id: SpecId) -> Self {
+        let mut adapter = Self::new();
+        adapter.spec_id = spec_id;
+        adapter
+    }
+
+    pub fn spec_id(&self) -> SpecId {
+        self.spec_id
+    }
+
     pub fn create_account(&mut self, address: String, initial_balance: u128) -> Result<()> {
+        self.ensure_no_pending()?;
         if self.accounts.contains_key(&address) {
             return Err(anyhow!("Account already exists"));
         }
@@ -50,15 +247,49 @@ impl EVMAdapter {
 
         // Also add to revm database
         let addr = parse_address(&address)?;
-        let mut acc_info = self.db.accounts.entry(addr).or_default();
-        acc_info.balance = U256::from(initial_balance);
-        acc_info.nonce = 0;
+        let acc_info = self.db.accounts.entry(addr).or_default();
+        acc_info.info.balance = U256::from(initial_balance);
+        acc_info.info.nonce = 0;
 
         self.accounts.insert(address, account);
         Ok(())
     }
 
+    /// Credit `amount` to `address`, creating the account first if needed.
+    pub fn fund_account(&mut self, address: &str, amount: u128) -> Result<()> {
+        self.ensure_no_pending()?;
+        if !self.accounts.contains_key(address) {
+            return self.create_account(address.to_string(), amount);
+        }
+
+        let addr = parse_address(address)?;
+        let account = self.accounts.get_mut(address).unwrap();
+        account.balance = account.balance.checked_add(amount)
+            .ok_or_else(|| anyhow!("Balance overflow"))?;
+
+        let acc_info = self.db.accounts.entry(addr).or_default();
+        acc_info.info.balance = U256::from(account.balance);
+        Ok(())
+    }
+
+    /// Remove `amount` from `address`; fails without changes if it can't cover it.
+    pub fn debit_account(&mut self, address: &str, amount: u128) -> Result<()> {
+        self.ensure_no_pending()?;
+        let addr = parse_address(address)?;
+        let account = self.accounts.get_mut(address)
+            .ok_or_else(|| anyhow!("Account not found"))?;
+        if account.balance < amount {
+            return Err(anyhow!("Insufficient balance"));
+        }
+        account.balance -= amount;
+
+        let acc_info = self.db.accounts.entry(addr).or_default();
+        acc_info.info.balance = U256::from(account.balance);
+        Ok(())
+    }
+
     pub fn deploy_contract(&mut self, deployer: &str, code: Vec<u8>) -> Result<String> {
+        self.ensure_no_pending()?;
         let deployer_account = self.accounts.get_mut(deployer)
             .ok_or_else(|| anyhow!("Deployer account not found"))?;
 
@@ -74,9 +305,10 @@ impl EVMAdapter {
         let addr = parse_address(&contract_address)?;
         let bytecode = Bytecode::new_raw(Bytes::from(code.clone()));
         
-        let mut acc_info = self.db.accounts.entry(addr).or_default();
-        acc_info.code = Some(bytecode);
-        acc_info.nonce = 1;
+        let acc_info = self.db.accounts.entry(addr).or_default();
+        acc_info.info.code_hash = bytecode.hash_slow();
+        acc_info.info.code = Some(bytecode);
+        acc_info.info.nonce = 1;
 
         let contract = EVMAccount {
             address: contract_address.clone(),
@@ -90,6 +322,11 @@ impl EVMAdapter {
         Ok(contract_address)
     }
 
+    /// Execute and commit a call from `from` to `to`. A call that reverts or
+    /// halts still counts as a transaction: its effects are rolled back, but
+    /// the caller pays for the gas used and spends its nonce, and a failed
+    /// receipt is stored. Only a call revm rejects outright leaves the
+    /// accounts as they were.
     pub fn call_contract(
         &mut self,
         from: &str,
@@ -97,22 +334,29 @@ impl EVMAdapter {
         data: Vec<u8>,
         value: u128,
         gas_limit: u64
-    ) -> Result<Vec<u8>> {
-        // Update account balances
-        let from_account = self.accounts.get_mut(from)
+    ) -> Result<EVMReceipt> {
+        self.ensure_no_pending()?;
+        // Capture both sides so a call revm rejects outright can be undone
+        let from_before = self.accounts.get(from).cloned()
             .ok_or_else(|| anyhow!("From account not found"))?;
+        let to_before = self.accounts.get(to).cloned()
+            .ok_or_else(|| anyhow!("Contract not found"))?;
 
-        if from_account.balance < value {
+        if from_before.balance < value {
             return Err(anyhow!("Insufficient balance"));
         }
+        // the whole limit must fit, as the call may use all of it
+        if self.cumulative_gas_used.saturating_add(gas_limit) > self.block_gas_limit {
+            return Err(anyhow!(
+                "Block gas limit exceeded: {} used, {} requested, limit {}",
+                self.cumulative_gas_used, gas_limit, self.block_gas_limit
+            ));
+        }
+        let tx_hash = tx_hash(from, to, from_before.nonce, value, &data, gas_limit)?;
+        let snapshot = vec![to_before, from_before];
 
-        from_account.balance -= value;
-        from_account.nonce += 1;
-
-        let to_account = self.accounts.get_mut(to)
-            .ok_or_else(|| anyhow!("Contract not found"))?;
-
-        to_account.balance += value;
+        // revm moves `value` itself; balances are synced back after commit
+        self.accounts.get_mut(from).unwrap().nonce += 1;
 
         // Execute using revm
         let from_addr = parse_address(from)?;
@@ -121,6 +365,7 @@ impl EVMAdapter {
         let mut evm = EVM::new();
         evm.database(&mut self.db);
         
+        evm.env.cfg.spec_id = self.spec_id;
         evm.env.tx.caller = from_addr;
         evm.env.tx.transact_to = TransactTo::Call(to_addr);
         evm.env.tx.data = Bytes::from(data.clone());
@@ -134,8 +379,21 @@ impl EVMAdapter {
             .unwrap()
             .as_secs());
 
-        match evm.transact_commit() {
-            Ok(result) => {
+        let mut gas_used = 0;
+        let outcome = evm.transact();
+        drop(evm);
+        let result = match outcome {
+            Ok(ResultAndState { result, state }) => {
+                gas_used = result.gas_used();
+                let destroyed: Vec<Address> = state.iter()
+                    .filter(|(_, account)| account.is_selfdestructed())
+                    .map(|(addr, _)| *addr)
+                    .collect();
+                // besides caller and callee, e.g. a SELFDESTRUCT beneficiary
+                let touched: Vec<Address> = state.keys().copied().collect();
+                self.db.commit(state);
+                self.forget_destroyed(&destroyed);
+                self.sync_accounts(&touched);
                 match result {
                     ExecutionResult::Success { output, .. } => {
                         match output {
@@ -143,6 +401,7 @@ impl EVMAdapter {
                             Output::Create(bytes, _) => Ok(bytes.to_vec()),
                         }
                     },
+                    // the call's effects are rolled back, but gas is paid and the nonce spent
                     ExecutionResult::Revert { output, .. } => {
                         Err(anyhow!("EVM execution reverted: {:?}", output))
                     },
@@ -151,10 +410,280 @@ impl EVMAdapter {
                     },
                 }
             },
-            Err(e) => {
-                // Fallback to simple method dispatch
+            Err(_) => {
+                // Nothing was committed; the fallback is a read-only simulation
+                self.restore_accounts(snapshot);
                 self.fallback_execution(to, &data)
             }
+        };
+
+        self.cumulative_gas_used += gas_used;
+        let receipt = EVMReceipt {
+            tx_hash,
+            success: result.is_ok(),
+            gas_used,
+            cumulative_gas_used: self.cumulative_gas_used,
+            output: result.as_ref().cloned().unwrap_or_default(),
+        };
+        self.receipts.insert(tx_hash, receipt.clone());
+        result.map(|_| receipt)
+    }
+
+    /// Hold transactions with a future nonce until the gap closes instead of
+    /// rejecting them.
+    pub fn set_queue_future_nonces(&mut self, enabled: bool) {
+        self.queue_future_nonces = enabled;
+    }
+
+    /// Execute a signed transaction whose nonce must equal the sender's
+    /// current nonce. Lower nonces are replays and are rejected; higher ones
+    /// are rejected too, or queued (returning `None`) when queueing is on, up
+    /// to `MAX_QUEUED_NONCE_GAP` ahead and `MAX_QUEUED_PER_SENDER` per sender.
+    /// The nonce is consumed even if the call fails, and a call that reverts
+    /// still pays for its gas.
+    pub fn submit_signed_tx(&mut self, tx: EVMTransaction) -> Result<Option<EVMReceipt>> {
+        self.ensure_no_pending()?;
+        let current = self.get_nonce(&tx.from)?;
+        if tx.nonce < current {
+            return Err(anyhow!("Nonce too low: got {}, expected {} (replay)", tx.nonce, current));
+        }
+        if tx.nonce > current {
+            if !self.queue_future_nonces {
+                return Err(anyhow!("Nonce too high: got {}, expected {}", tx.nonce, current));
+            }
+            if tx.nonce - current > MAX_QUEUED_NONCE_GAP {
+                return Err(anyhow!(
+                    "Nonce too far ahead: got {}, expected at most {}",
+                    tx.nonce, current + MAX_QUEUED_NONCE_GAP
+                ));
+            }
+            let queue = self.queued.entry(tx.from.clone()).or_default();
+            // replacing a queued nonce doesn't grow the queue
+            if queue.len() >= MAX_QUEUED_PER_SENDER && !queue.contains_key(&tx.nonce) {
+                return Err(anyhow!("Too many queued transactions for {}: limit {}", tx.from, MAX_QUEUED_PER_SENDER));
+            }
+            queue.insert(tx.nonce, tx);
+            return Ok(None);
+        }
+
+        let from = tx.from.clone();
+        let receipt = self.execute_with_nonce(tx);
+        // run any queued transactions the new nonce unblocks
+        while let Some(next) = self.get_nonce(&from).ok()
+            .and_then(|nonce| self.queued.get_mut(&from)?.remove(&nonce))
+        {
+            let _ = self.execute_with_nonce(next);
+        }
+        receipt.map(Some)
+    }
+
+    /// Run `tx` as `submit_signed_tx` would, but against the pending state, so
+    /// its effects show through `pending_balance`/`pending_nonce` and not the
+    /// committed getters. The first call starts the pending state as a copy of
+    /// the committed one; until it is committed or discarded, the methods that
+    /// change committed state directly return an error rather than being lost
+    /// on commit.
+    pub fn apply_pending(&mut self, tx: EVMTransaction) -> Result<Option<EVMReceipt>> {
+        let mut pending = self.pending.take().unwrap_or_else(|| PendingState {
+            accounts: self.accounts.clone(),
+            receipts: self.receipts.clone(),
+            queued: self.queued.clone(),
+            db: self.db.clone(),
+            cumulative_gas_used: self.cumulative_gas_used,
+        });
+        self.swap_pending(&mut pending);
+        let result = self.submit_signed_tx(tx);
+        self.swap_pending(&mut pending);
+        self.pending = Some(pending);
+        result
+    }
+
+    /// Make everything applied since the last commit or discard the committed state.
+    pub fn commit_pending(&mut self) {
+        if let Some(mut pending) = self.pending.take() {
+            self.swap_pending(&mut pending);
+        }
+    }
+
+    /// Drop everything applied since the last commit or discard.
+    pub fn discard_pending(&mut self) {
+        self.pending = None;
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Balance including pending transactions; the committed one if there are none.
+    pub fn pending_balance(&self, address: &str) -> Result<u128> {
+        self.pending_accounts().get(address)
+            .map(|acc| acc.balance)
+            .ok_or_else(|| anyhow!("Account not found"))
+    }
+
+    /// Nonce including pending transactions; the committed one if there are none.
+    pub fn pending_nonce(&self, address: &str) -> Result<u64> {
+        self.pending_accounts().get(address)
+            .map(|acc| acc.nonce)
+            .ok_or_else(|| anyhow!("Account not found"))
+    }
+
+    // `apply_pending` takes the pending state while it runs, so its own calls pass
+    fn ensure_no_pending(&self) -> Result<()> {
+        if self.pending.is_some() {
+            return Err(anyhow!("Pending state must be committed or discarded first"));
+        }
+        Ok(())
+    }
+
+    fn pending_accounts(&self) -> &HashMap<String, EVMAccount> {
+        self.pending.as_ref().map_or(&self.accounts, |pending| &pending.accounts)
+    }
+
+    fn swap_pending(&mut self, pending: &mut PendingState) {
+        std::mem::swap(&mut self.accounts, &mut pending.accounts);
+        std::mem::swap(&mut self.receipts, &mut pending.receipts);
+        std::mem::swap(&mut self.queued, &mut pending.queued);
+        std::mem::swap(&mut self.db, &mut pending.db);
+        std::mem::swap(&mut self.cumulative_gas_used, &mut pending.cumulative_gas_used);
+    }
+
+    fn execute_with_nonce(&mut self, tx: EVMTransaction) -> Result<EVMReceipt> {
+        let result = self.call_contract(&tx.from, &tx.to, tx.data, tx.value, tx.gas_limit);
+        // a call revm rejected outright leaves the nonce alone, but it stays spent
+        if self.get_nonce(&tx.from)? == tx.nonce {
+            let next = tx.nonce + 1;
+            self.accounts.get_mut(&tx.from).unwrap().nonce = next;
+            let addr = parse_address(&tx.from)?;
+            self.db.accounts.entry(addr).or_default().info.nonce = next;
+        }
+        result
+    }
+
+    /// Smallest gas limit with which the call succeeds, like `eth_estimateGas`.
+    /// Every attempt runs against uncommitted state at a zero gas price, so the
+    /// adapter is left untouched. A revert is an error carrying its reason.
+    pub fn estimate_gas(&mut self, from: &str, to: &str, data: Vec<u8>, value: u128) -> Result<u64> {
+        let from_addr = parse_address(from)?;
+        let to_addr = parse_address(to)?;
+        let data = Bytes::from(data);
+        let cap = self.block_gas_limit;
+
+        let gas_used = match self.simulate(from_addr, to_addr, &data, value, cap)? {
+            ExecutionResult::Success { gas_used, .. } => gas_used,
+            ExecutionResult::Revert { output, .. } => {
+                return Err(anyhow!("EVM execution reverted: {}", decode_revert_reason(&output)));
+            }
+            ExecutionResult::Halt { reason, .. } => return Err(anyhow!("EVM execution halted: {:?}", reason)),
+        };
+
+        // refunds and the 63/64 rule can make the needed limit exceed gas_used,
+        // but never fall below it
+        let (mut failing, mut passing) = (gas_used - 1, cap);
+        while passing - failing > 1 {
+            let mid = failing + (passing - failing) / 2;
+            match self.simulate(from_addr, to_addr, &data, value, mid) {
+                Ok(result) if result.is_success() => passing = mid,
+                _ => failing = mid,
+            }
+        }
+        Ok(passing)
+    }
+
+    // Run a call without committing it; a limit below the intrinsic cost is an Err
+    fn simulate(&mut self, from: Address, to: Address, data: &Bytes, value: u128, gas_limit: u64) -> Result<ExecutionResult> {
+        let mut evm = EVM::new();
+        evm.database(&mut self.db);
+
+        evm.env.cfg.spec_id = self.spec_id;
+        evm.env.tx.caller = from;
+        evm.env.tx.transact_to = TransactTo::Call(to);
+        evm.env.tx.data = data.clone();
+        evm.env.tx.value = U256::from(value);
+        evm.env.tx.gas_limit = gas_limit;
+        evm.env.tx.gas_price = U256::ZERO;
+        evm.env.block.number = U256::from(self.block_number);
+
+        evm.transact()
+            .map(|outcome| outcome.result)
+            .map_err(|e| anyhow!("EVM simulation failed: {:?}", e))
+    }
+
+    /// Execute a call without committing it and record every step, for
+    /// debugging. Balances, nonces and storage are left untouched. As with
+    /// `estimate_gas`, the gas price is zero, so the caller needn't cover
+    /// `TRACE_GAS_LIMIT` at the adapter's price.
+    pub fn trace_call(&mut self, from: &str, to: &str, data: Vec<u8>, value: u128) -> Result<CallTrace> {
+        let from_addr = parse_address(from)?;
+        let to_addr = parse_address(to)?;
+
+        let mut evm = EVM::new();
+        evm.database(&mut self.db);
+
+        evm.env.cfg.spec_id = self.spec_id;
+        evm.env.tx.caller = from_addr;
+        evm.env.tx.transact_to = TransactTo::Call(to_addr);
+        evm.env.tx.data = Bytes::from(data);
+        evm.env.tx.value = U256::from(value);
+        evm.env.tx.gas_limit = TRACE_GAS_LIMIT;
+        evm.env.tx.gas_price = U256::ZERO;
+        evm.env.block.number = U256::from(self.block_number);
+
+        let mut tracer = StepTracer::default();
+        let outcome = evm.inspect(&mut tracer)
+            .map_err(|e| anyhow!("EVM trace failed: {:?}", e))?;
+
+        let result = outcome.result;
+        let output = match &result {
+            ExecutionResult::Success { output: Output::Call(bytes), .. } => bytes.to_vec(),
+            ExecutionResult::Success { output: Output::Create(bytes, _), .. } => bytes.to_vec(),
+            ExecutionResult::Revert { output, .. } => output.to_vec(),
+            ExecutionResult::Halt { .. } => vec![],
+        };
+
+        Ok(CallTrace {
+            steps: tracer.steps,
+            calls: tracer.calls,
+            gas_used: result.gas_used(),
+            success: result.is_success(),
+            output,
+        })
+    }
+
+    pub fn get_receipt(&self, tx_hash: &[u8; 32]) -> Option<&EVMReceipt> {
+        self.receipts.get(tx_hash)
+    }
+
+    /// Copy revm's balances and nonces (value moved, gas paid, nonce spent)
+    /// into the cache entries of the accounts a call touched.
+    fn sync_accounts(&mut self, touched: &[Address]) {
+        for (address, account) in self.accounts.iter_mut() {
+            let Ok(addr) = parse_address(address) else { continue };
+            if !touched.contains(&addr) {
+                continue;
+            }
+            if let Some(db_account) = self.db.accounts.get(&addr) {
+                account.balance = db_account.info.balance.saturating_to::<u128>();
+                account.nonce = db_account.info.nonce;
+            }
+        }
+    }
+
+    /// Drop cache entries for accounts revm deleted, e.g. by SELFDESTRUCT.
+    fn forget_destroyed(&mut self, destroyed: &[Address]) {
+        if destroyed.is_empty() {
+            return;
+        }
+        self.accounts.retain(|address, _| {
+            parse_address(address).map_or(true, |addr| !destroyed.contains(&addr))
+        });
+    }
+
+    /// Put back account entries captured before a call mutated them, for
+    /// calls revm rejected before executing.
+    fn restore_accounts(&mut self, snapshot: Vec<EVMAccount>) {
+        for account in snapshot {
+            self.accounts.insert(account.address.clone(), account);
         }
     }
 
@@ -189,6 +718,7 @@ impl EVMAdapter {
     }
 
     pub fn transfer(&mut self, from: &str, to: &str, amount: u128) -> Result<()> {
+        self.ensure_no_pending()?;
         let from_account = self.accounts.get_mut(from)
             .ok_or_else(|| anyhow!("From account not found"))?;
 
@@ -209,23 +739,102 @@ impl EVMAdapter {
         let to_addr = parse_address(to)?;
 
         if let Some(from_info) = self.db.accounts.get_mut(&from_addr) {
-            from_info.balance = U256::from(from_account.balance);
-            from_info.nonce = from_account.nonce;
+            from_info.info.balance = U256::from(self.accounts[from].balance);
+            from_info.info.nonce = self.accounts[from].nonce;
         }
 
         if let Some(to_info) = self.db.accounts.get_mut(&to_addr) {
-            to_info.balance = U256::from(to_account.balance);
+            to_info.info.balance = U256::from(self.accounts[to].balance);
         }
 
         Ok(())
     }
 
+    /// Pay every recipient from `from` in one step, creating recipients that
+    /// don't exist yet. Nothing changes unless the sender covers the total and
+    /// every credit succeeds.
+    pub fn batch_transfer(&mut self, from: &str, recipients: Vec<(String, u128)>) -> Result<()> {
+        self.ensure_no_pending()?;
+        if recipients.is_empty() {
+            return Err(anyhow!("No recipients"));
+        }
+        let sender = self.accounts.get(from)
+            .ok_or_else(|| anyhow!("From account not found"))?;
+        let total = recipients.iter()
+            .try_fold(0u128, |sum, (_, amount)| sum.checked_add(*amount))
+            .ok_or_else(|| anyhow!("Total amount overflows"))?;
+        if sender.balance < total {
+            return Err(anyhow!("Insufficient balance: {} needed, {} available", total, sender.balance));
+        }
+
+        // Stage every resulting balance first; a recipient listed twice is credited twice
+        let mut staged = BTreeMap::new();
+        staged.insert(from.to_string(), (parse_address(from)?, sender.balance - total));
+        for (to, amount) in &recipients {
+            let current = match staged.get(to) {
+                Some((_, balance)) => *balance,
+                None => self.accounts.get(to).map_or(0, |acc| acc.balance),
+            };
+            let balance = current.checked_add(*amount)
+                .ok_or_else(|| anyhow!("Balance overflow for {}", to))?;
+            staged.insert(to.clone(), (parse_address(to)?, balance));
+        }
+
+        for (address, (addr, balance)) in staged {
+            let account = self.accounts.entry(address.clone()).or_insert_with(|| EVMAccount {
+                address,
+                balance: 0,
+                nonce: 0,
+                code: vec![],
+                storage: HashMap::new(),
+            });
+            account.balance = balance;
+            self.db.accounts.entry(addr).or_default().info.balance = U256::from(balance);
+        }
+
+        let sender = self.accounts.get_mut(from).unwrap();
+        sender.nonce += 1;
+        self.db.accounts.entry(parse_address(from)?).or_default().info.nonce = sender.nonce;
+        Ok(())
+    }
+
     pub fn get_balance(&self, address: &str) -> Result<u128> {
         self.accounts.get(address)
             .map(|acc| acc.balance)
             .ok_or_else(|| anyhow!("Account not found"))
     }
 
+    pub fn get_code(&self, address: &str) -> Result<Vec<u8>> {
+        self.accounts.get(address)
+            .map(|acc| acc.code.clone())
+            .ok_or_else(|| anyhow!("Account not found"))
+    }
+
+    /// Root over the account's storage, from the revm database; see `storage_root`.
+    pub fn get_storage_root(&self, address: &str) -> Result<[u8; 32]> {
+        let account = self.db.accounts.get(&parse_address(address)?)
+            .ok_or_else(|| anyhow!("Account not found"))?;
+        Ok(storage_root(&account.storage))
+    }
+
+    /// keccak256 over every live account in address order, each encoded as
+    /// `address || balance || nonce || code_hash || storage_root`.
+    pub fn state_root(&self) -> [u8; 32] {
+        let mut accounts: Vec<_> = self.db.accounts.iter()
+            .filter(|(_, account)| account.account_state != AccountState::NotExisting)
+            .collect();
+        accounts.sort_by_key(|(addr, _)| **addr);
+        let mut preimage = Vec::with_capacity(accounts.len() * 124);
+        for (addr, account) in accounts {
+            preimage.extend_from_slice(addr.as_slice());
+            preimage.extend(account.info.balance.to_be_bytes::<32>());
+            preimage.extend(account.info.nonce.to_be_bytes());
+            preimage.extend_from_slice(account.info.code_hash.as_slice());
+            preimage.extend(storage_root(&account.storage));
+        }
+        keccak256(preimage).0
+    }
+
     pub fn get_nonce(&self, address: &str) -> Result<u64> {
         self.accounts.get(address)
             .map(|acc| acc.nonce)
@@ -234,6 +843,20 @@ impl EVMAdapter {
 
     pub fn increment_block(&mut self) {
         self.block_number += 1;
+        self.cumulative_gas_used = 0;
+    }
+
+    pub fn set_block_gas_limit(&mut self, limit: u64) {
+        self.block_gas_limit = limit;
+    }
+
+    pub fn block_gas_limit(&self) -> u64 {
+        self.block_gas_limit
+    }
+
+    /// Gas used by calls in the current block so far.
+    pub fn cumulative_gas_used(&self) -> u64 {
+        self.cumulative_gas_used
     }
 
     pub fn get_block_number(&self) -> u64 {
@@ -247,6 +870,63 @@ impl Default for EVMAdapter {
     }
 }
 
+/// keccak256 over the non-zero slots in slot order, each as the 32-byte slot
+/// followed by the 32-byte value; equal storage gives equal roots.
+fn storage_root<'a>(storage: impl IntoIterator<Item = (&'a U256, &'a U256)>) -> [u8; 32] {
+    let mut slots: Vec<_> = storage.into_iter().filter(|(_, value)| **value != U256::ZERO).collect();
+    slots.sort();
+    let mut preimage = Vec::with_capacity(slots.len() * 64);
+    for (slot, value) in slots {
+        preimage.extend(slot.to_be_bytes::<32>());
+        preimage.extend(value.to_be_bytes::<32>());
+    }
+    keccak256(preimage).0
+}
+
+/// keccak256 over the RLP list `[from, to, nonce, value, data, gas_limit]`.
+pub fn tx_hash(from: &str, to: &str, nonce: u64, value: u128, data: &[u8], gas_limit: u64) -> Result<[u8; 32]> {
+    let from = parse_address(from)?;
+    let to = parse_address(to)?;
+    let value = U256::from(value);
+    let data = Bytes::copy_from_slice(data);
+    let fields: [&dyn Encodable; 6] = [&from, &to, &nonce, &value, &data, &gas_limit];
+
+    let mut encoded = Vec::new();
+    alloy_rlp::encode_list::<_, dyn Encodable>(&fields, &mut encoded);
+    Ok(keccak256(encoded).0)
+}
+
+/// Hash of a signed transaction: keccak256 of its full signed encoding.
+pub fn signed_tx_hash(signed_payload: &[u8]) -> [u8; 32] {
+    keccak256(signed_payload).0
+}
+
+/// Reason of a revert: the message of a Solidity `Error(string)` payload,
+/// otherwise the raw output in hex.
+pub fn decode_revert_reason(output: &[u8]) -> String {
+    const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+    if output.is_empty() {
+        return "no reason given".to_string();
+    }
+    if output.len() >= 68 && output[..4] == ERROR_SELECTOR {
+        let len = U256::from_be_slice(&output[36..68]);
+        if let Ok(len) = usize::try_from(len) {
+            // a length near usize::MAX would overflow 68 + len
+            if let Some(message) = 68usize.checked_add(len).and_then(|end| output.get(68..end)) {
+                if let Ok(message) = std::str::from_utf8(message) {
+                    return message.to_string();
+                }
+            }
+        }
+    }
+    format!("0x{}", hex::encode(output))
+}
+
+fn parse_word(word: &str) -> Result<U256> {
+    U256::from_str_radix(word.trim_start_matches("0x"), 16)
+        .map_err(|e| anyhow!("Invalid storage word '{}': {}", word, e))
+}
+
 fn parse_address(addr_str: &str) -> Result<Address> {
     let clean = addr_str.trim_start_matches("0x");
     let bytes = hex::decode(clean)
@@ -276,6 +956,21 @@ mod tests {
         assert_eq!(evm.get_balance("0xalice").unwrap(), 1000);
     }
 
+    #[test]
+    fn test_fund_account_creates_then_tops_up() {
+        let address = "0x00000000000000000000000000000000000000cc";
+        let mut evm = EVMAdapter::new();
+
+        evm.fund_account(address, 300).unwrap();
+        assert_eq!(evm.get_balance(address).unwrap(), 300);
+        assert_eq!(evm.get_nonce(address).unwrap(), 0);
+
+        evm.fund_account(address, 200).unwrap();
+        assert_eq!(evm.get_balance(address).unwrap(), 500);
+        let db_balance = evm.db.accounts[&parse_address(address).unwrap()].info.balance;
+        assert_eq!(db_balance, U256::from(500));
+    }
+
     #[test]
     fn test_transfer() {
         let mut evm = EVMAdapter::new();
@@ -287,6 +982,46 @@ mod tests {
         assert_eq!(evm.get_balance("0xbob").unwrap(), 100);
     }
 
+    #[test]
+    fn test_batch_transfer_airdrop() {
+        let sender = "0x00000000000000000000000000000000000000aa";
+        let bob = "0x00000000000000000000000000000000000000bb";
+        let carol = "0x00000000000000000000000000000000000000cc";
+        let mut evm = EVMAdapter::new();
+        evm.create_account(sender.to_string(), 1000).unwrap();
+        evm.create_account(bob.to_string(), 5).unwrap();
+
+        let recipients = vec![(bob.to_string(), 100), (carol.to_string(), 200), (bob.to_string(), 50)];
+        evm.batch_transfer(sender, recipients).unwrap();
+        assert_eq!(evm.get_balance(sender).unwrap(), 650);
+        assert_eq!(evm.get_balance(bob).unwrap(), 155);
+        assert_eq!(evm.get_balance(carol).unwrap(), 200);
+        assert_eq!(evm.get_nonce(sender).unwrap(), 1);
+        let db_balance = |address: &str| evm.db.accounts[&parse_address(address).unwrap()].info.balance;
+        assert_eq!((db_balance(sender), db_balance(bob), db_balance(carol)), (U256::from(650), U256::from(155), U256::from(200)));
+    }
+
+    #[test]
+    fn test_batch_transfer_over_balance_changes_nothing() {
+        let sender = "0x00000000000000000000000000000000000000aa";
+        let bob = "0x00000000000000000000000000000000000000bb";
+        let carol = "0x00000000000000000000000000000000000000cc";
+        let mut evm = EVMAdapter::new();
+        evm.create_account(sender.to_string(), 300).unwrap();
+        evm.create_account(bob.to_string(), 5).unwrap();
+
+        // each payment is affordable on its own, but not together
+        let err = evm.batch_transfer(sender, vec![(bob.to_string(), 200), (carol.to_string(), 200)]).unwrap_err();
+        assert!(err.to_string().contains("Insufficient balance"));
+        assert_eq!((evm.get_balance(sender).unwrap(), evm.get_balance(bob).unwrap()), (300, 5));
+        assert!(evm.get_balance(carol).is_err());
+        assert_eq!(evm.get_nonce(sender).unwrap(), 0);
+
+        let err = evm.batch_transfer(sender, vec![(bob.to_string(), 100), ("0xnot-hex".to_string(), 1)]).unwrap_err();
+        assert!(err.to_string().contains("Invalid hex address"));
+        assert_eq!((evm.get_balance(sender).unwrap(), evm.get_balance(bob).unwrap()), (300, 5));
+    }
+
     #[test]
     fn test_deploy_contract() {
         let mut evm = EVMAdapter::new();
@@ -300,6 +1035,21 @@ mod tests {
         assert_eq!(evm.get_nonce("0xdeployer").unwrap(), 1);
     }
 
+    #[test]
+    fn test_tx_hash_is_deterministic() {
+        let from = "0x00000000000000000000000000000000000000aa";
+        let to = "0x00000000000000000000000000000000000000bb";
+        let a = tx_hash(from, to, 0, 100, b"data", 21000).unwrap();
+        let b = tx_hash(from, to, 0, 100, b"data", 21000).unwrap();
+        assert_eq!(a, b);
+
+        assert_ne!(a, tx_hash(from, to, 1, 100, b"data", 21000).unwrap());
+        assert_ne!(a, tx_hash(from, to, 0, 101, b"data", 21000).unwrap());
+        assert_ne!(a, tx_hash(from, to, 0, 100, b"datb", 21000).unwrap());
+        assert_ne!(a, tx_hash(to, from, 0, 100, b"data", 21000).unwrap());
+        assert_ne!(signed_tx_hash(b"signed-1"), signed_tx_hash(b"signed-2"));
+    }
+
     #[test]
     fn test_parse_address() {
         let addr = parse_address("0x1234567890123456789012345678901234567890");
@@ -308,4 +1058,398 @@ mod tests {
         let short_addr = parse_address("0x1234");
         assert!(short_addr.is_ok());
     }
+
+    #[test]
+    fn test_spec_selection_push0() {
+        // PUSH0 PUSH0 RETURN - PUSH0 only exists from Shanghai onwards
+        let code = vec![0x5f, 0x5f, 0xf3];
+        let caller = "0x00000000000000000000000000000000000000aa";
+
+        let mut shanghai = EVMAdapter::with_spec(SpecId::SHANGHAI);
+        assert_eq!(shanghai.spec_id(), SpecId::SHANGHAI);
+        shanghai.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = shanghai.deploy_contract(caller, code.clone()).unwrap();
+        assert!(shanghai.call_contract(caller, &contract, vec![], 0, 100_000).is_ok());
+
+        let mut london = EVMAdapter::with_spec(SpecId::LONDON);
+        assert_eq!(london.spec_id(), SpecId::LONDON);
+        london.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = london.deploy_contract(caller, code).unwrap();
+        assert!(london.call_contract(caller, &contract, vec![], 0, 100_000).is_err());
+    }
+
+    #[test]
+    fn test_call_value_moves_once() {
+        // PUSH0 PUSH0 RETURN
+        let code = vec![0x5f, 0x5f, 0xf3];
+        let caller = "0x00000000000000000000000000000000000000aa";
+
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = evm.deploy_contract(caller, code).unwrap();
+        let before = evm.get_balance(caller).unwrap();
+
+        let receipt = evm.call_contract(caller, &contract, vec![], 100, 100_000).unwrap();
+        let gas_fee = receipt.gas_used as u128 * evm.gas_price as u128;
+        assert_eq!(evm.get_balance(&contract).unwrap(), 100);
+        assert_eq!(evm.get_balance(caller).unwrap(), before - 100 - gas_fee);
+    }
+
+    fn signed_tx(from: &str, to: &str, nonce: u64) -> EVMTransaction {
+        EVMTransaction { from: from.to_string(), to: to.to_string(), nonce, value: 1, data: vec![], gas_limit: 100_000 }
+    }
+
+    #[test]
+    fn test_signed_tx_nonce_must_match() {
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        // PUSH0 PUSH0 RETURN
+        let contract = evm.deploy_contract(caller, vec![0x5f, 0x5f, 0xf3]).unwrap();
+        let nonce = evm.get_nonce(caller).unwrap();
+
+        assert!(evm.submit_signed_tx(signed_tx(caller, &contract, nonce)).unwrap().unwrap().success);
+        assert_eq!(evm.get_nonce(caller).unwrap(), nonce + 1);
+
+        let replay = evm.submit_signed_tx(signed_tx(caller, &contract, nonce)).unwrap_err();
+        assert!(replay.to_string().contains("too low"));
+        let future = evm.submit_signed_tx(signed_tx(caller, &contract, nonce + 5)).unwrap_err();
+        assert!(future.to_string().contains("too high"));
+        assert_eq!(evm.get_nonce(caller).unwrap(), nonce + 1);
+    }
+
+    #[test]
+    fn test_queued_future_nonce_runs_once_gap_closes() {
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let mut evm = EVMAdapter::new();
+        evm.set_queue_future_nonces(true);
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = evm.deploy_contract(caller, vec![0x5f, 0x5f, 0xf3]).unwrap();
+        let nonce = evm.get_nonce(caller).unwrap();
+
+        assert!(evm.submit_signed_tx(signed_tx(caller, &contract, nonce + 1)).unwrap().is_none());
+        assert_eq!(evm.get_nonce(caller).unwrap(), nonce);
+        evm.submit_signed_tx(signed_tx(caller, &contract, nonce)).unwrap();
+        assert_eq!(evm.get_nonce(caller).unwrap(), nonce + 2);
+        assert_eq!(evm.get_balance(&contract).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_queue_is_bounded() {
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let mut evm = EVMAdapter::new();
+        evm.set_queue_future_nonces(true);
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = evm.deploy_contract(caller, vec![0x5f, 0x5f, 0xf3]).unwrap();
+        let nonce = evm.get_nonce(caller).unwrap();
+
+        let gap = evm.submit_signed_tx(signed_tx(caller, &contract, nonce + MAX_QUEUED_NONCE_GAP + 1)).unwrap_err();
+        assert!(gap.to_string().contains("too far ahead"));
+        assert!(evm.submit_signed_tx(signed_tx(caller, &contract, nonce + MAX_QUEUED_NONCE_GAP)).unwrap().is_none());
+
+        for ahead in 1..MAX_QUEUED_PER_SENDER as u64 {
+            assert!(evm.submit_signed_tx(signed_tx(caller, &contract, nonce + ahead)).unwrap().is_none());
+        }
+        let full = evm.submit_signed_tx(signed_tx(caller, &contract, nonce + MAX_QUEUED_PER_SENDER as u64)).unwrap_err();
+        assert!(full.to_string().contains("Too many queued"));
+        // resubmitting a queued nonce replaces it
+        assert!(evm.submit_signed_tx(signed_tx(caller, &contract, nonce + 1)).unwrap().is_none());
+        assert_eq!(evm.queued[caller].len(), MAX_QUEUED_PER_SENDER);
+    }
+
+    #[test]
+    fn test_pending_state_is_invisible_until_committed() {
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        // PUSH0 PUSH0 RETURN
+        let contract = evm.deploy_contract(caller, vec![0x5f, 0x5f, 0xf3]).unwrap();
+        let nonce = evm.get_nonce(caller).unwrap();
+        let balance = evm.get_balance(caller).unwrap();
+        let root = evm.state_root();
+
+        let receipt = evm.apply_pending(signed_tx(caller, &contract, nonce)).unwrap().unwrap();
+        evm.apply_pending(signed_tx(caller, &contract, nonce + 1)).unwrap();
+        assert!(evm.has_pending());
+        assert_eq!(evm.pending_nonce(caller).unwrap(), nonce + 2);
+        assert_eq!(evm.pending_balance(&contract).unwrap(), 2);
+        assert!(evm.pending_balance(caller).unwrap() < balance - 2);
+        assert_eq!(evm.get_nonce(caller).unwrap(), nonce);
+        assert_eq!(evm.get_balance(caller).unwrap(), balance);
+        assert_eq!(evm.get_balance(&contract).unwrap(), 0);
+        assert!(evm.get_receipt(&receipt.tx_hash).is_none());
+        assert_eq!(evm.state_root(), root);
+
+        evm.discard_pending();
+        assert!(!evm.has_pending());
+        assert_eq!(evm.pending_nonce(caller).unwrap(), nonce);
+        assert_eq!(evm.pending_balance(&contract).unwrap(), 0);
+
+        let receipt = evm.apply_pending(signed_tx(caller, &contract, nonce)).unwrap().unwrap();
+        evm.commit_pending();
+        assert!(!evm.has_pending());
+        assert_eq!(evm.get_nonce(caller).unwrap(), nonce + 1);
+        assert_eq!(evm.get_balance(&contract).unwrap(), 1);
+        assert!(evm.get_receipt(&receipt.tx_hash).is_some());
+        assert_ne!(evm.state_root(), root);
+    }
+
+    #[test]
+    fn test_committed_changes_wait_for_pending_state() {
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let other = "0x00000000000000000000000000000000000000bb";
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        evm.create_account(other.to_string(), 0).unwrap();
+        // PUSH0 PUSH0 RETURN
+        let contract = evm.deploy_contract(caller, vec![0x5f, 0x5f, 0xf3]).unwrap();
+        let nonce = evm.get_nonce(caller).unwrap();
+
+        evm.apply_pending(signed_tx(caller, &contract, nonce)).unwrap();
+        // a committed transfer now would be overwritten by commit_pending
+        let err = evm.transfer(caller, other, 100).unwrap_err();
+        assert!(err.to_string().contains("Pending state"));
+        assert!(evm.fund_account(other, 5).is_err());
+        assert!(evm.submit_signed_tx(signed_tx(caller, &contract, nonce)).is_err());
+        assert_eq!(evm.get_balance(other).unwrap(), 0);
+
+        evm.commit_pending();
+        assert_eq!(evm.get_nonce(caller).unwrap(), nonce + 1);
+        evm.transfer(caller, other, 100).unwrap();
+        assert_eq!(evm.get_balance(other).unwrap(), 100);
+        assert_eq!(evm.get_balance(&contract).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_trace_call_records_steps() {
+        // PUSH1 2 PUSH1 3 ADD PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+        let code = vec![0x60, 0x02, 0x60, 0x03, 0x01, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3];
+        let caller = "0x00000000000000000000000000000000000000aa";
+
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = evm.deploy_contract(caller, code).unwrap();
+        let balance = evm.get_balance(caller).unwrap();
+
+        let trace = evm.trace_call(caller, &contract, vec![], 0).unwrap();
+        let ops: Vec<&str> = trace.steps.iter().map(|s| s.op.as_str()).collect();
+        assert_eq!(ops, ["PUSH1", "PUSH1", "ADD", "PUSH0", "MSTORE", "PUSH1", "PUSH0", "RETURN"]);
+        assert_eq!(trace.steps[2].stack_len, 2);
+        assert!(trace.steps.windows(2).all(|w| w[1].gas_remaining < w[0].gas_remaining));
+        assert_eq!(trace.calls.len(), 1);
+        assert!(trace.success && trace.calls[0].success);
+        assert_eq!(trace.output[31], 5);
+        // intrinsic 21000 plus a handful of cheap opcodes
+        assert!(trace.gas_used > 21_000 && trace.gas_used < 21_100);
+        assert_eq!(evm.get_balance(caller).unwrap(), balance);
+
+        // tracing is free, so a caller that couldn't fund the trace gas limit can trace too
+        let empty = "0x00000000000000000000000000000000000000bb";
+        evm.create_account(empty.to_string(), 0).unwrap();
+        assert!(evm.trace_call(empty, &contract, vec![], 0).unwrap().success);
+    }
+
+    #[test]
+    fn test_block_gas_limit_fills_then_resets() {
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        // PUSH0 PUSH0 RETURN
+        let contract = evm.deploy_contract(caller, vec![0x5f, 0x5f, 0xf3]).unwrap();
+        evm.set_block_gas_limit(70_000);
+
+        let first = evm.call_contract(caller, &contract, vec![], 0, 30_000).unwrap();
+        let second = evm.call_contract(caller, &contract, vec![], 0, 30_000).unwrap();
+        assert_eq!(first.cumulative_gas_used, first.gas_used);
+        assert_eq!(second.cumulative_gas_used, first.gas_used + second.gas_used);
+        assert_eq!(evm.cumulative_gas_used(), second.cumulative_gas_used);
+
+        let nonce = evm.get_nonce(caller).unwrap();
+        let balance = evm.get_balance(caller).unwrap();
+        let full = evm.call_contract(caller, &contract, vec![], 0, 30_000).unwrap_err();
+        assert!(full.to_string().contains("Block gas limit exceeded"));
+        assert_eq!((evm.get_nonce(caller).unwrap(), evm.get_balance(caller).unwrap()), (nonce, balance));
+
+        evm.increment_block();
+        assert_eq!(evm.cumulative_gas_used(), 0);
+        let next = evm.call_contract(caller, &contract, vec![], 0, 30_000).unwrap();
+        assert_eq!(next.cumulative_gas_used, next.gas_used);
+    }
+
+    #[test]
+    fn test_genesis_contract_is_callable() {
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let contract = "0x00000000000000000000000000000000000000cc";
+        let alloc = GenesisAlloc::from([
+            (caller.to_string(), GenesisAccount { balance: 1_000_000_000, nonce: 5, ..Default::default() }),
+            (contract.to_string(), GenesisAccount {
+                // PUSH0 SLOAD PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+                code: vec![0x5f, 0x54, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3],
+                storage: BTreeMap::from([("0x0".to_string(), "0x2a".to_string())]),
+                ..Default::default()
+            }),
+        ]);
+
+        let mut evm = EVMAdapter::from_genesis(alloc).unwrap();
+        assert_eq!(evm.get_balance(caller).unwrap(), 1_000_000_000);
+        assert_eq!(evm.get_nonce(caller).unwrap(), 5);
+
+        let receipt = evm.call_contract(caller, contract, vec![], 0, 100_000).unwrap();
+        assert_eq!(receipt.output[31], 0x2a);
+        assert_eq!(evm.get_nonce(caller).unwrap(), 6);
+    }
+
+    #[test]
+    fn test_reverted_call_charges_gas_and_returns_value() {
+        // PUSH0 PUSH0 REVERT
+        let code = vec![0x5f, 0x5f, 0xfd];
+        let caller = "0x00000000000000000000000000000000000000aa";
+
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = evm.deploy_contract(caller, code).unwrap();
+        let nonce_before = evm.get_nonce(caller).unwrap();
+        let balance_before = evm.get_balance(caller).unwrap();
+
+        let result = evm.call_contract(caller, &contract, vec![], 500, 100_000);
+        assert!(result.unwrap_err().to_string().contains("reverted"));
+        let hash = tx_hash(caller, &contract, nonce_before, 500, &[], 100_000).unwrap();
+        let receipt = evm.get_receipt(&hash).unwrap();
+        assert!(!receipt.success);
+        let gas_fee = receipt.gas_used as u128 * evm.gas_price as u128;
+
+        // the cache agrees with revm: value back, gas paid, nonce spent
+        let db_account = &evm.db.accounts[&parse_address(caller).unwrap()].info;
+        assert_eq!(evm.get_balance(caller).unwrap(), db_account.balance.saturating_to::<u128>());
+        assert_eq!(evm.get_nonce(caller).unwrap(), db_account.nonce);
+        assert_eq!(evm.get_balance(caller).unwrap(), balance_before - gas_fee);
+        assert_eq!(evm.get_nonce(caller).unwrap(), nonce_before + 1);
+        assert_eq!(evm.get_balance(&contract).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_selfdestruct_removes_account() {
+        // CALLER SELFDESTRUCT
+        let code = vec![0x33, 0xff];
+        let caller = "0x00000000000000000000000000000000000000aa";
+
+        // from Cancun, SELFDESTRUCT only deletes contracts created in the same transaction
+        let mut evm = EVMAdapter::with_spec(SpecId::SHANGHAI);
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = evm.deploy_contract(caller, code.clone()).unwrap();
+        assert_eq!(evm.get_code(&contract).unwrap(), code);
+        let before = evm.get_balance(caller).unwrap();
+
+        // the value sent along is paid straight back to the caller
+        let receipt = evm.call_contract(caller, &contract, vec![], 100, 100_000).unwrap();
+        let gas_fee = receipt.gas_used as u128 * evm.gas_price as u128;
+        assert_eq!(evm.get_balance(caller).unwrap(), before - gas_fee);
+        assert!(evm.get_balance(&contract).is_err());
+        assert!(evm.get_code(&contract).is_err());
+        assert!(evm.call_contract(caller, &contract, vec![], 0, 100_000).is_err());
+    }
+
+    #[test]
+    fn test_selfdestruct_credits_beneficiary() {
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let beneficiary = "0x00000000000000000000000000000000000000bb";
+        // PUSH20 beneficiary SELFDESTRUCT
+        let mut code = vec![0x73];
+        code.extend(parse_address(beneficiary).unwrap().as_slice());
+        code.push(0xff);
+
+        let mut evm = EVMAdapter::with_spec(SpecId::SHANGHAI);
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        evm.create_account(beneficiary.to_string(), 5).unwrap();
+        let contract = evm.deploy_contract(caller, code).unwrap();
+
+        evm.call_contract(caller, &contract, vec![], 100, 100_000).unwrap();
+        assert_eq!(evm.get_balance(beneficiary).unwrap(), 105);
+        let db_balance = evm.db.accounts[&parse_address(beneficiary).unwrap()].info.balance;
+        assert_eq!(db_balance, U256::from(105));
+        assert!(evm.get_balance(&contract).is_err());
+    }
+
+    #[test]
+    fn test_storage_root_tracks_slot_writes() {
+        let store_one = "0x00000000000000000000000000000000000000c1";
+        let store_two = "0x00000000000000000000000000000000000000c2";
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let mut alloc = GenesisAlloc::new();
+        for (address, value) in [(store_one, 0x01), (store_two, 0x02)] {
+            alloc.insert(address.to_string(), GenesisAccount {
+                // PUSH1 value PUSH0 SSTORE STOP
+                code: vec![0x60, value, 0x5f, 0x55, 0x00],
+                storage: BTreeMap::from([("0x0".to_string(), "0x1".to_string())]),
+                ..Default::default()
+            });
+        }
+        alloc.insert(caller.to_string(), GenesisAccount { balance: 1_000_000_000, ..Default::default() });
+        let mut evm = EVMAdapter::from_genesis(alloc).unwrap();
+
+        // same slots and values, different code: the storage roots still agree
+        let initial = evm.get_storage_root(store_one).unwrap();
+        assert_eq!(evm.get_storage_root(store_two).unwrap(), initial);
+        assert_ne!(initial, evm.get_storage_root(caller).unwrap());
+
+        // rewriting slot 0 with the value it already holds leaves its root alone
+        assert!(evm.call_contract(caller, store_one, vec![], 0, 100_000).unwrap().success);
+        assert_eq!(evm.get_storage_root(store_one).unwrap(), initial);
+
+        let state_root = evm.state_root();
+        assert!(evm.call_contract(caller, store_two, vec![], 0, 100_000).unwrap().success);
+        assert_ne!(evm.get_storage_root(store_two).unwrap(), initial);
+        assert_ne!(evm.state_root(), state_root);
+        // only the storage root differs between otherwise identical state
+        let mut rewritten = evm.db.accounts[&parse_address(store_two).unwrap()].clone();
+        rewritten.storage.insert(U256::ZERO, U256::from(1));
+        assert_eq!(storage_root(&rewritten.storage), initial);
+        assert!(evm.get_storage_root("0x00000000000000000000000000000000000000ff").is_err());
+    }
+
+    #[test]
+    fn test_estimate_gas_does_not_mutate_state() {
+        // PUSH1 1 PUSH0 SSTORE STOP
+        let code = vec![0x60, 0x01, 0x5f, 0x55, 0x00];
+        let caller = "0x00000000000000000000000000000000000000aa";
+
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = evm.deploy_contract(caller, code).unwrap();
+        let (nonce, balance) = (evm.get_nonce(caller).unwrap(), evm.get_balance(caller).unwrap());
+
+        let estimate = evm.estimate_gas(caller, &contract, vec![], 0).unwrap();
+        assert!(estimate > 21_000);
+        assert_eq!(evm.estimate_gas(caller, &contract, vec![], 0).unwrap(), estimate);
+        assert_eq!((evm.get_nonce(caller).unwrap(), evm.get_balance(caller).unwrap()), (nonce, balance));
+        assert_eq!(evm.cumulative_gas_used(), 0);
+
+        // the estimate is exactly enough
+        assert!(evm.call_contract(caller, &contract, vec![], 0, estimate - 1).is_err());
+        let receipt = evm.call_contract(caller, &contract, vec![], 0, estimate).unwrap();
+        assert!(receipt.success);
+    }
+
+    #[test]
+    fn test_estimate_gas_reports_revert_reason() {
+        // PUSH0 PUSH0 REVERT
+        let code = vec![0x5f, 0x5f, 0xfd];
+        let caller = "0x00000000000000000000000000000000000000aa";
+        let mut evm = EVMAdapter::new();
+        evm.create_account(caller.to_string(), 1_000_000_000).unwrap();
+        let contract = evm.deploy_contract(caller, code).unwrap();
+        let err = evm.estimate_gas(caller, &contract, vec![], 0).unwrap_err();
+        assert_eq!(err.to_string(), "EVM execution reverted: no reason given");
+
+        // Error("nope")
+        let mut payload = vec![0x08, 0xc3, 0x79, 0xa0];
+        payload.extend(U256::from(32).to_be_bytes::<32>());
+        payload.extend(U256::from(4).to_be_bytes::<32>());
+        payload.extend(b"nope");
+        payload.extend([0u8; 28]);
+        assert_eq!(decode_revert_reason(&payload), "nope");
+        payload[36..68].copy_from_slice(&U256::from(usize::MAX).to_be_bytes::<32>());
+        assert!(decode_revert_reason(&payload).starts_with("0x08c379a0"));
+        assert_eq!(decode_revert_reason(&[0xde, 0xad]), "0xdead");
+    }
 }
@@ -0,0 +1,3 @@
+// Library target: the PQC module on its own, so front-ends can build it for
+// wasm32 with the `wasm` feature. The node itself is the binary in main.rs.
+pub mod pqc;
@@ -2,11 +2,12 @@ mod bridge;
 mod wasm_vm;
 mod pqc;
 mod evm_adapter;
+mod cross_bridge;
 
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use std::{fs, time::{SystemTime, UNIX_EPOCH}, collections::VecDeque, sync::{Arc, Mutex}};
-use wasm_vm::WasmVM;
+use wasm_vm::{GasSchedule, WasmVM};
 use pqc::{HybridKeyPair, verify_hybrid_signature};
 use evm_adapter::EVMAdapter;
 
@@ -126,9 +127,10 @@ fn main() {
     println!("   PQC Test: Signature valid = {}", is_valid);
     
     println!("\n2. Initializing WASM Virtual Machine...");
-    let mut wasm_vm = WasmVM::new(1000000);
-    let contract_code = vec![0x00, 0x61, 0x73, 0x6d];
-    wasm_vm.deploy_contract("wasm_contract_1".to_string(), contract_code).unwrap();
+    let mut wasm_vm = WasmVM::new(1000000, GasSchedule::default());
+    // the smallest valid module: `\0asm` and version 1
+    let contract_code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    wasm_vm.deploy_contract("validator-1", "wasm_contract_1".to_string(), contract_code).unwrap();
     println!("   WASM VM: Contract deployed, Gas used: {}", wasm_vm.get_gas_used());
     
     println!("\n3. Initializing EVM Adapter...");
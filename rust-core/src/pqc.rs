@@ -1,32 +1,160 @@
 // Post-Quantum Cryptography module for NeoNet
 // Full implementation with Dilithium3 signatures and Kyber1024 key exchange
+// On wasm32 only verification is compiled, and the `wasm` feature adds the
+// JS bindings (`cargo build --lib --target wasm32-unknown-unknown --features wasm`).
+// Gating on the target rather than the feature keeps `--all-features` native
+// builds whole
 use serde::{Deserialize, Serialize};
-use ed25519_dalek::{Keypair as EdKeypair, PublicKey as EdPublicKey, Signature as EdSignature, Signer, Verifier};
-use rand::rngs::OsRng;
+use ed25519_dalek::{PublicKey as EdPublicKey, Signature as EdSignature, Verifier};
 use anyhow::{Result, anyhow};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use sha2::{Digest, Sha256};
 
 // PQC imports
 use pqcrypto_dilithium::dilithium3;
-use pqcrypto_kyber::kyber1024;
-use pqcrypto_traits::sign::{PublicKey as PQPublicKey, SecretKey as PQSecretKey, DetachedSignature};
-use pqcrypto_traits::kem::{PublicKey as KemPublicKey, SecretKey as KemSecretKey, Ciphertext, SharedSecret};
+use pqcrypto_traits::sign::{PublicKey as PQPublicKey, DetachedSignature};
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    ed25519_dalek::{Keypair as EdKeypair, Signer},
+    pqcrypto_kyber::kyber1024,
+    pqcrypto_traits::kem::{PublicKey as KemPublicKey, SecretKey as KemSecretKey, Ciphertext, SharedSecret},
+    pqcrypto_traits::sign::SecretKey as PQSecretKey,
+    rand::rngs::OsRng,
+    rand::{CryptoRng, RngCore},
+    rayon::prelude::*,
+    std::collections::HashMap,
+    std::time::Instant,
+};
+
+/// Algorithm suites, serialized as their stable string identifiers.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum Algorithm {
+    Ed25519Dilithium3,
+    Ed25519Dilithium3Kyber1024,
+    // signatures carrying only one half, as a one-sided `HybridPolicy` signs
+    Ed25519,
+    Dilithium3,
+}
+
+impl Algorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::Ed25519Dilithium3 => "Ed25519+Dilithium3",
+            Algorithm::Ed25519Dilithium3Kyber1024 => "Ed25519+Dilithium3+Kyber1024",
+            Algorithm::Ed25519 => "Ed25519",
+            Algorithm::Dilithium3 => "Dilithium3",
+        }
+    }
+
+    /// Signature suite holding exactly the halves `policy` requires.
+    pub fn for_policy(policy: HybridPolicy) -> Result<Self> {
+        match (policy.require_classical, policy.require_pqc) {
+            (true, true) => Ok(Algorithm::Ed25519Dilithium3),
+            (true, false) => Ok(Algorithm::Ed25519),
+            (false, true) => Ok(Algorithm::Dilithium3),
+            (false, false) => Err(anyhow!("Hybrid policy must require at least one signature component")),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "Ed25519+Dilithium3" => Ok(Algorithm::Ed25519Dilithium3),
+            "Ed25519+Dilithium3+Kyber1024" => Ok(Algorithm::Ed25519Dilithium3Kyber1024),
+            "Ed25519" => Ok(Algorithm::Ed25519),
+            "Dilithium3" => Ok(Algorithm::Dilithium3),
+            _ => Err(anyhow!("Unknown algorithm '{}'", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Algorithm {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Algorithm> for String {
+    fn from(algorithm: Algorithm) -> Self {
+        algorithm.as_str().to_string()
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HybridPublicKey {
     pub ed25519_public: Vec<u8>,
     pub dilithium_public: Vec<u8>,
     pub kyber_public: Vec<u8>,
-    pub algorithm: String,
+    pub algorithm: Algorithm,
+}
+
+/// Encoding version written into every `HybridSignature`. Bump when the
+/// signature layout changes so older verifiers reject it explicitly.
+pub const SIGNATURE_FORMAT_VERSION: u16 = 1;
+
+fn default_format_version() -> u16 {
+    // signatures serialized before versioning existed use the v1 layout
+    1
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HybridSignature {
+    #[serde(default = "default_format_version")]
+    pub format_version: u16,
     pub ed25519_sig: Vec<u8>,
     pub dilithium_sig: Vec<u8>,
-    pub algorithm: String,
+    pub algorithm: Algorithm,
     pub timestamp: u64,
 }
 
+/// Which halves of a hybrid signature are produced and must verify; the
+/// migration posture of a deployment. A half the policy does not require is
+/// left empty by `sign` and ignored by `verify_hybrid_signature`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HybridPolicy {
+    pub require_classical: bool,
+    pub require_pqc: bool,
+}
+
+impl HybridPolicy {
+    pub const BOTH: HybridPolicy = HybridPolicy { require_classical: true, require_pqc: true };
+    pub const CLASSICAL_ONLY: HybridPolicy = HybridPolicy { require_classical: true, require_pqc: false };
+    pub const PQC_ONLY: HybridPolicy = HybridPolicy { require_classical: false, require_pqc: true };
+
+    fn check(&self) -> Result<()> {
+        Algorithm::for_policy(*self).map(|_| ())
+    }
+}
+
+impl Default for HybridPolicy {
+    fn default() -> Self {
+        HybridPolicy::BOTH
+    }
+}
+
+static HYBRID_POLICY: OnceLock<HybridPolicy> = OnceLock::new();
+
+/// Set the process-wide policy used by `HybridKeyPair::sign` and
+/// `verify_hybrid_signature`. Fails if a policy is already in effect.
+pub fn set_hybrid_policy(policy: HybridPolicy) -> Result<()> {
+    policy.check()?;
+    HYBRID_POLICY.set(policy).map_err(|_| anyhow!("Hybrid policy is already set"))
+}
+
+/// The configured policy; both halves are required until one is set.
+pub fn hybrid_policy() -> HybridPolicy {
+    HYBRID_POLICY.get().copied().unwrap_or_default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 pub struct HybridKeyPair {
     ed_keypair: EdKeypair,
     dilithium_public: dilithium3::PublicKey,
@@ -35,11 +163,18 @@ pub struct HybridKeyPair {
     kyber_secret: kyber1024::SecretKey,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl HybridKeyPair {
     /// Generate new hybrid keypair with Ed25519 + Dilithium3 + Kyber1024
     pub fn generate() -> Self {
-        let mut csprng = OsRng;
-        let ed_keypair = EdKeypair::generate(&mut csprng);
+        Self::generate_with_rng(&mut OsRng)
+    }
+
+    /// Like `generate`, drawing the Ed25519 key from `rng` (a hardware source,
+    /// or a seeded RNG in tests). pqcrypto's Dilithium and Kyber keygen take
+    /// no RNG and still use their own internal entropy.
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let ed_keypair = EdKeypair::generate(rng);
         
         // Generate Dilithium3 keypair for signatures
         let (dilithium_public, dilithium_secret) = dilithium3::keypair();
@@ -83,27 +218,87 @@ impl HybridKeyPair {
             ed25519_public: self.ed_keypair.public.to_bytes().to_vec(),
             dilithium_public: self.dilithium_public.as_bytes().to_vec(),
             kyber_public: self.kyber_public.as_bytes().to_vec(),
-            algorithm: "Ed25519+Dilithium3+Kyber1024".to_string(),
+            algorithm: Algorithm::Ed25519Dilithium3Kyber1024,
         }
     }
 
-    /// Sign message with hybrid signature (Ed25519 + Dilithium3)
+    /// Sign message with hybrid signature (Ed25519 + Dilithium3), producing
+    /// the halves the configured `hybrid_policy` requires
     pub fn sign(&self, message: &[u8]) -> HybridSignature {
+        self.sign_with_policy(message, hybrid_policy())
+            .expect("the configured policy is checked when it is set")
+    }
+
+    /// `sign` under an explicit policy instead of the configured one. The
+    /// signature's `algorithm` names the halves it carries; a policy
+    /// requiring neither is an error.
+    pub fn sign_with_policy(&self, message: &[u8], policy: HybridPolicy) -> Result<HybridSignature> {
+        let algorithm = Algorithm::for_policy(policy)?;
+
         // Classical signature
-        let ed_sig = self.ed_keypair.sign(message);
-        
+        let ed_sig = if policy.require_classical {
+            self.ed_keypair.sign(message).to_bytes().to_vec()
+        } else {
+            Vec::new()
+        };
+
         // Post-quantum signature
-        let dilithium_sig = dilithium3::detached_sign(message, &self.dilithium_secret);
-        
-        HybridSignature {
-            ed25519_sig: ed_sig.to_bytes().to_vec(),
-            dilithium_sig: dilithium_sig.as_bytes().to_vec(),
-            algorithm: "Ed25519+Dilithium3".to_string(),
+        let dilithium_sig = if policy.require_pqc {
+            dilithium3::detached_sign(message, &self.dilithium_secret).as_bytes().to_vec()
+        } else {
+            Vec::new()
+        };
+
+        Ok(HybridSignature {
+            format_version: SIGNATURE_FORMAT_VERSION,
+            ed25519_sig: ed_sig,
+            dilithium_sig,
+            algorithm,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+        })
+    }
+
+    /// Sign the prehash of `message`, so it can be verified incrementally
+    /// with `HybridVerifier` or in one shot with `verify_prehashed`.
+    pub fn sign_prehashed(&self, message: &[u8]) -> HybridSignature {
+        let mut hasher = prehasher();
+        hasher.update(message);
+        self.sign(&hasher.finalize())
+    }
+
+    /// Sign `message` bound to `aad`, context such as a chain id and nonce that
+    /// the verifier supplies separately. Verify with `verify_with_aad`.
+    pub fn sign_with_aad(&self, message: &[u8], aad: &[u8]) -> HybridSignature {
+        self.sign(&aad_digest(message, aad))
+    }
+
+    /// Check that each public key belongs to its secret key, by signing (or,
+    /// for Kyber, encapsulating) a random nonce and verifying it with the
+    /// public half. The error names the first inconsistent component.
+    pub fn validate(&self) -> Result<()> {
+        let mut nonce = [0u8; 32];
+        OsRng.fill_bytes(&mut nonce);
+
+        let ed_sig = self.ed_keypair.sign(&nonce);
+        if EdPublicKey::from(&self.ed_keypair.secret) != self.ed_keypair.public
+            || self.ed_keypair.public.verify(&nonce, &ed_sig).is_err()
+        {
+            return Err(anyhow!("Ed25519 public key does not match its secret key"));
         }
+
+        let dilithium_sig = dilithium3::detached_sign(&nonce, &self.dilithium_secret);
+        if dilithium3::verify_detached_signature(&dilithium_sig, &nonce, &self.dilithium_public).is_err() {
+            return Err(anyhow!("Dilithium3 public key does not match its secret key"));
+        }
+
+        let (shared, ciphertext) = kyber1024::encapsulate(&self.kyber_public);
+        if kyber1024::decapsulate(&ciphertext, &self.kyber_secret).as_bytes() != shared.as_bytes() {
+            return Err(anyhow!("Kyber1024 public key does not match its secret key"));
+        }
+        Ok(())
     }
 
     pub fn secret_bytes(&self) -> Vec<u8> {
@@ -130,52 +325,341 @@ impl HybridKeyPair {
     }
 }
 
-/// Verify hybrid signature (both Ed25519 and Dilithium3 must be valid)
+/// Verify hybrid signature; the halves the configured `hybrid_policy`
+/// requires must all be valid (both Ed25519 and Dilithium3 by default)
 pub fn verify_hybrid_signature(
     public_key: &HybridPublicKey,
     message: &[u8],
     signature: &HybridSignature
 ) -> Result<bool> {
-    // Verify Ed25519 signature
-    if public_key.ed25519_public.len() != 32 {
-        return Err(anyhow!("Invalid Ed25519 public key length"));
+    verify_with_policy(public_key, message, signature, hybrid_policy())
+}
+
+/// `verify_hybrid_signature` under an explicit policy instead of the configured one.
+pub fn verify_with_policy(
+    public_key: &HybridPublicKey,
+    message: &[u8],
+    signature: &HybridSignature,
+    policy: HybridPolicy,
+) -> Result<bool> {
+    policy.check()?;
+    // Unknown encodings are an error, not merely an invalid signature
+    if signature.format_version != SIGNATURE_FORMAT_VERSION {
+        return Err(anyhow!(
+            "Unsupported signature format version {} (expected {})",
+            signature.format_version,
+            SIGNATURE_FORMAT_VERSION
+        ));
     }
 
-    let ed_public = EdPublicKey::from_bytes(&public_key.ed25519_public)?;
-    
-    if signature.ed25519_sig.len() != 64 {
-        return Err(anyhow!("Invalid Ed25519 signature length"));
+    let (has_classical, has_pqc) = match signature.algorithm {
+        // before one-sided suites existed, every signature was labelled hybrid
+        Algorithm::Ed25519Dilithium3 => (true, true),
+        Algorithm::Ed25519 => (true, false),
+        Algorithm::Dilithium3 => (false, true),
+        other => return Err(anyhow!("Unsupported signature algorithm {}", other.as_str())),
+    };
+    if (policy.require_classical && !has_classical) || (policy.require_pqc && !has_pqc) {
+        return Err(anyhow!(
+            "Signature algorithm {} lacks a component the policy requires",
+            signature.algorithm.as_str()
+        ));
     }
 
-    let ed_sig = EdSignature::from_bytes(&signature.ed25519_sig)?;
-    
-    // Ed25519 verification
-    if ed_public.verify(message, &ed_sig).is_err() {
+    if policy.require_classical && !verify_ed25519(&public_key.ed25519_public, message, &signature.ed25519_sig)? {
         return Ok(false);
     }
-    
-    // Verify Dilithium3 signature
-    if signature.dilithium_sig.len() != dilithium3::signature_bytes() {
+    if !policy.require_pqc {
+        return Ok(true);
+    }
+
+    // Dilithium3 verification - both signatures must be valid
+    verify_dilithium(&public_key.dilithium_public, message, &signature.dilithium_sig)
+}
+
+/// Check the Ed25519 half on its own. Malformed keys or signatures are an
+/// error; a well-formed signature that does not match is `Ok(false)`.
+pub fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool> {
+    if public_key.len() != 32 {
+        return Err(anyhow!("Invalid Ed25519 public key length"));
+    }
+
+    let ed_public = EdPublicKey::from_bytes(public_key)?;
+
+    if signature.len() != 64 {
+        return Err(anyhow!("Invalid Ed25519 signature length"));
+    }
+
+    let ed_sig = EdSignature::from_bytes(signature)?;
+    Ok(ed_public.verify(message, &ed_sig).is_ok())
+}
+
+/// Check the Dilithium3 half on its own, with the same error convention as
+/// `verify_ed25519`.
+pub fn verify_dilithium(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool> {
+    if signature.len() != dilithium3::signature_bytes() {
         return Err(anyhow!("Invalid Dilithium signature length"));
     }
-    
-    let dil_public = dilithium3::PublicKey::from_bytes(&public_key.dilithium_public)
+
+    let dil_public = dilithium3::PublicKey::from_bytes(public_key)
         .map_err(|_| anyhow!("Failed to parse Dilithium public key"))?;
-    
-    let dil_sig = dilithium3::DetachedSignature::from_bytes(&signature.dilithium_sig)
+
+    let dil_sig = dilithium3::DetachedSignature::from_bytes(signature)
         .map_err(|_| anyhow!("Failed to parse Dilithium signature"))?;
-    
-    // Dilithium3 verification - both signatures must be valid
-    match dilithium3::verify_detached_signature(&dil_sig, message, &dil_public) {
-        Ok(_) => Ok(true),
-        Err(_) => Ok(false),
+
+    Ok(dilithium3::verify_detached_signature(&dil_sig, message, &dil_public).is_ok())
+}
+
+// Domain-separated so a prehash signature can't be mistaken for one over raw data
+const PREHASH_DOMAIN: &[u8] = b"neonet-pqc-prehash-v1";
+
+fn prehasher() -> Sha256 {
+    let mut hasher = Sha256::new();
+    hasher.update(PREHASH_DOMAIN);
+    hasher
+}
+
+/// Incremental verification of a `sign_prehashed` signature: feed the
+/// message in chunks, then check the signature against their digest.
+pub struct HybridVerifier {
+    public_key: HybridPublicKey,
+    hasher: Sha256,
+}
+
+impl HybridVerifier {
+    pub fn new(public_key: HybridPublicKey) -> Self {
+        HybridVerifier { public_key, hasher: prehasher() }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.hasher.update(chunk);
+    }
+
+    pub fn finalize(self, signature: &HybridSignature) -> Result<bool> {
+        verify_hybrid_signature(&self.public_key, &self.hasher.finalize(), signature)
+    }
+}
+
+/// One-shot counterpart of `HybridVerifier`.
+pub fn verify_prehashed(public_key: &HybridPublicKey, message: &[u8], signature: &HybridSignature) -> Result<bool> {
+    let mut verifier = HybridVerifier::new(public_key.clone());
+    verifier.update(message);
+    verifier.finalize(signature)
+}
+
+// Distinct from the prehash domain; the AAD is length-prefixed so no split of
+// the same bytes into (aad, message) produces the same pre-image
+const AAD_DOMAIN: &[u8] = b"neonet-pqc-aad-v1";
+
+fn aad_digest(message: &[u8], aad: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(AAD_DOMAIN);
+    hasher.update((aad.len() as u64).to_be_bytes());
+    hasher.update(aad);
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// Verify a `sign_with_aad` signature; fails unless `aad` matches the signer's.
+pub fn verify_with_aad(public_key: &HybridPublicKey, message: &[u8], aad: &[u8], signature: &HybridSignature) -> Result<bool> {
+    verify_hybrid_signature(public_key, &aad_digest(message, aad), signature)
+}
+
+/// Dilithium3 checks for a whole block. Dilithium has no true aggregation, so
+/// every signature is still verified, but each distinct key is parsed once and
+/// the checks run across all cores.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct DilithiumBatch {
+    // (public key, message, signature)
+    entries: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DilithiumBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, public_key: &[u8], message: &[u8], signature: &[u8]) {
+        self.entries.push((public_key.to_vec(), message.to_vec(), signature.to_vec()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// `Err(i)` names the lowest-indexed entry that fails, counting malformed
+    /// keys and signatures as failures.
+    pub fn verify(&self) -> std::result::Result<(), usize> {
+        let mut keys: HashMap<&[u8], Option<dilithium3::PublicKey>> = HashMap::new();
+        for (public_key, _, _) in &self.entries {
+            keys.entry(public_key.as_slice())
+                .or_insert_with(|| dilithium3::PublicKey::from_bytes(public_key).ok());
+        }
+
+        let failed = self.entries.par_iter().position_first(|(public_key, message, signature)| {
+            let Some(key) = &keys[public_key.as_slice()] else {
+                return true;
+            };
+            match dilithium3::DetachedSignature::from_bytes(signature) {
+                Ok(signature) => dilithium3::verify_detached_signature(&signature, message, key).is_err(),
+                Err(_) => true,
+            }
+        });
+        match failed {
+            Some(index) => Err(index),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Expected Ed25519 output for a fixed key and message.
+pub struct Ed25519Kat {
+    pub secret: &'static str,
+    pub public: &'static str,
+    pub message: &'static [u8],
+    pub signature: &'static str,
+}
+
+/// RFC 8032 section 7.1, test 1.
+pub const ED25519_KAT: Ed25519Kat = Ed25519Kat {
+    secret: "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+    public: "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+    message: b"",
+    signature: "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+};
+
+/// Power-on self-test. Ed25519 is checked against a known answer; pqcrypto
+/// offers no seeded key generation, so Dilithium3 and Kyber1024 get
+/// pairwise-consistency checks on a fresh keypair instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn self_test() -> Result<(), String> {
+    self_test_with(&ED25519_KAT)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn self_test_with(kat: &Ed25519Kat) -> Result<(), String> {
+    let secret = hex::decode(kat.secret).map_err(|e| format!("Ed25519 KAT: bad secret: {}", e))?;
+    let keypair = HybridKeyPair::from_bytes(&secret).map_err(|e| format!("Ed25519 KAT: {}", e))?;
+    let public_key = keypair.public_key();
+    if hex::encode(&public_key.ed25519_public) != kat.public {
+        return Err("Ed25519 KAT: public key mismatch".to_string());
+    }
+    // both halves whatever the configured policy, so neither goes untested
+    let signature = keypair.sign_with_policy(kat.message, HybridPolicy::BOTH).map_err(|e| e.to_string())?;
+    if hex::encode(&signature.ed25519_sig) != kat.signature {
+        return Err("Ed25519 KAT: signature mismatch".to_string());
+    }
+
+    // covers the Dilithium3 half as well as Ed25519 verification
+    match verify_with_policy(&public_key, kat.message, &signature, HybridPolicy::BOTH) {
+        Ok(true) => {}
+        _ => return Err("Dilithium3 self-test: signature did not verify".to_string()),
+    }
+    if !matches!(verify_with_policy(&public_key, b"self-test tamper", &signature, HybridPolicy::BOTH), Ok(false)) {
+        return Err("Dilithium3 self-test: tampered message verified".to_string());
+    }
+
+    let (shared, ciphertext) = keypair.kyber_encapsulate();
+    if keypair.kyber_decapsulate(&ciphertext).ok() != Some(shared.clone()) {
+        return Err("Kyber1024 self-test: decapsulated secret differs".to_string());
+    }
+    let mut tampered = ciphertext;
+    tampered[0] ^= 1;
+    if keypair.kyber_decapsulate(&tampered).ok() == Some(shared) {
+        return Err("Kyber1024 self-test: tampered ciphertext was accepted".to_string());
     }
+    Ok(())
 }
 
-#[cfg(test)]
+/// Throughput measured by `bench_sign_verify`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BenchReport {
+    pub iterations: usize,
+    pub sign_ops_per_sec: f64,
+    pub verify_ops_per_sec: f64,
+}
+
+/// Sign and verify `n` messages with one fresh keypair and report ops/sec.
+/// Key generation is excluded from the timings.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn bench_sign_verify(n: usize) -> Result<BenchReport> {
+    let n = n.max(1);
+    let keypair = HybridKeyPair::generate();
+    let public_key = keypair.public_key();
+    let messages: Vec<Vec<u8>> = (0..n).map(|i| format!("bench message {}", i).into_bytes()).collect();
+
+    let start = Instant::now();
+    let signatures: Vec<HybridSignature> = messages.iter().map(|m| keypair.sign(m)).collect();
+    let sign_secs = start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    for (message, signature) in messages.iter().zip(&signatures) {
+        if !verify_hybrid_signature(&public_key, message, signature)? {
+            return Err(anyhow!("Benchmark signature failed to verify"));
+        }
+    }
+    let verify_secs = start.elapsed().as_secs_f64();
+
+    Ok(BenchReport {
+        iterations: n,
+        sign_ops_per_sec: n as f64 / sign_secs.max(f64::EPSILON),
+        verify_ops_per_sec: n as f64 / verify_secs.max(f64::EPSILON),
+    })
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+mod bindings {
+    use super::*;
+    use wasm_bindgen::prelude::*;
+
+    /// `verify_hybrid_signature` for JavaScript callers, taking the public key
+    /// and signature in their JSON encoding.
+    #[wasm_bindgen(js_name = verifyHybridSignature)]
+    pub fn verify_hybrid_signature_json(public_key: &str, message: &[u8], signature: &str) -> Result<bool, JsError> {
+        let public_key: HybridPublicKey = serde_json::from_str(public_key)?;
+        let signature: HybridSignature = serde_json::from_str(signature)?;
+        verify_hybrid_signature(&public_key, message, &signature).map_err(|e| JsError::new(&e.to_string()))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_dilithium_batch_reports_first_invalid() {
+        let keys: Vec<_> = (0..5).map(|_| dilithium3::keypair()).collect();
+        let entries: Vec<_> = (0..50).map(|i| {
+            let (public_key, secret_key) = &keys[i % keys.len()];
+            let message = format!("block tx {}", i).into_bytes();
+            let mut signature = dilithium3::detached_sign(&message, secret_key).as_bytes().to_vec();
+            if i == 37 {
+                signature[0] ^= 1;
+            }
+            (public_key.as_bytes().to_vec(), message, signature)
+        }).collect();
+
+        let mut batch = DilithiumBatch::new();
+        for (public_key, message, signature) in &entries {
+            batch.add(public_key, message, signature);
+        }
+        assert_eq!(batch.len(), 50);
+        assert_eq!(batch.verify(), Err(37));
+
+        let mut rest = DilithiumBatch::new();
+        for (_, (public_key, message, signature)) in entries.iter().enumerate().filter(|(i, _)| *i != 37) {
+            rest.add(public_key, message, signature);
+        }
+        assert_eq!(rest.verify(), Ok(()));
+        assert_eq!(DilithiumBatch::new().verify(), Ok(()));
+    }
+
     #[test]
     fn test_keygen_and_sign() {
         let keypair = HybridKeyPair::generate();
@@ -186,7 +670,20 @@ mod tests {
         
         let is_valid = verify_hybrid_signature(&public_key, message, &signature).unwrap();
         assert!(is_valid);
-        assert_eq!(public_key.algorithm, "Ed25519+Dilithium3+Kyber1024");
+        assert_eq!(public_key.algorithm, Algorithm::Ed25519Dilithium3Kyber1024);
+    }
+
+    #[test]
+    fn test_algorithm_string_round_trip() {
+        for algorithm in [Algorithm::Ed25519Dilithium3, Algorithm::Ed25519Dilithium3Kyber1024, Algorithm::Ed25519, Algorithm::Dilithium3] {
+            assert_eq!(algorithm.as_str().parse::<Algorithm>().unwrap(), algorithm);
+            let json = serde_json::to_string(&algorithm).unwrap();
+            assert_eq!(json, format!("\"{}\"", algorithm.as_str()));
+            assert_eq!(serde_json::from_str::<Algorithm>(&json).unwrap(), algorithm);
+        }
+
+        assert!("Ed25519+RSA".parse::<Algorithm>().is_err());
+        assert!(serde_json::from_str::<Algorithm>("\"Ed25519+RSA\"").is_err());
     }
 
     #[test]
@@ -202,6 +699,74 @@ mod tests {
         assert!(!is_valid);
     }
 
+    #[test]
+    fn test_unknown_format_version_rejected() {
+        let keypair = HybridKeyPair::generate();
+        let message = b"Versioned message";
+        let public_key = keypair.public_key();
+
+        let mut signature = keypair.sign(message);
+        assert_eq!(signature.format_version, SIGNATURE_FORMAT_VERSION);
+        assert!(verify_hybrid_signature(&public_key, message, &signature).unwrap());
+
+        // A future version is an explicit error...
+        signature.format_version = SIGNATURE_FORMAT_VERSION + 1;
+        let err = verify_hybrid_signature(&public_key, message, &signature).unwrap_err();
+        assert!(err.to_string().contains("Unsupported signature format version"));
+
+        // ...while a bad signature in the current format is just invalid
+        let other = HybridKeyPair::generate().public_key();
+        signature.format_version = SIGNATURE_FORMAT_VERSION;
+        assert!(!verify_hybrid_signature(&other, message, &signature).unwrap());
+    }
+
+    #[test]
+    fn test_signature_without_version_defaults_to_v1() {
+        let keypair = HybridKeyPair::generate();
+        let message = b"Legacy encoding";
+        let signature = keypair.sign(message);
+
+        let mut json = serde_json::to_value(&signature).unwrap();
+        json.as_object_mut().unwrap().remove("format_version");
+        let legacy: HybridSignature = serde_json::from_value(json).unwrap();
+
+        assert_eq!(legacy.format_version, 1);
+        assert!(verify_hybrid_signature(&keypair.public_key(), message, &legacy).unwrap());
+    }
+
+    #[test]
+    fn test_seeded_rng_reproduces_ed25519_key() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let first = HybridKeyPair::generate_with_rng(&mut StdRng::seed_from_u64(7)).public_key();
+        let second = HybridKeyPair::generate_with_rng(&mut StdRng::seed_from_u64(7)).public_key();
+        let other = HybridKeyPair::generate_with_rng(&mut StdRng::seed_from_u64(8)).public_key();
+
+        assert_eq!(first.ed25519_public, second.ed25519_public);
+        assert_ne!(first.ed25519_public, other.ed25519_public);
+        // Dilithium keygen ignores the RNG
+        assert_ne!(first.dilithium_public, second.dilithium_public);
+    }
+
+    #[test]
+    fn test_validate_names_mismatched_component() {
+        let keypair = HybridKeyPair::generate();
+        assert!(keypair.validate().is_ok());
+
+        let other = HybridKeyPair::generate();
+        let mut ed = HybridKeyPair::generate();
+        ed.ed_keypair.public = other.ed_keypair.public;
+        let mut dilithium = HybridKeyPair::generate();
+        dilithium.dilithium_public = other.dilithium_public;
+        let mut kyber = HybridKeyPair::generate();
+        kyber.kyber_public = other.kyber_public;
+
+        for (keypair, component) in [(ed, "Ed25519"), (dilithium, "Dilithium3"), (kyber, "Kyber1024")] {
+            let err = keypair.validate().unwrap_err().to_string();
+            assert!(err.starts_with(component), "{}", err);
+        }
+    }
+
     #[test]
     fn test_from_bytes() {
         let keypair1 = HybridKeyPair::generate();
@@ -244,4 +809,92 @@ mod tests {
         assert_eq!(signature.dilithium_sig.len(), dilithium3::signature_bytes());
         assert!(signature.timestamp > 0);
     }
+
+    #[test]
+    fn test_bench_sign_verify_smoke() {
+        let report = bench_sign_verify(3).unwrap();
+
+        assert_eq!(report.iterations, 3);
+        // Sanity floor only; real numbers come from `cargo bench`
+        assert!(report.sign_ops_per_sec > 1.0);
+        assert!(report.verify_ops_per_sec > 1.0);
+    }
+
+    #[test]
+    fn test_chunked_and_one_shot_prehash_agree() {
+        let keypair = HybridKeyPair::generate();
+        let public_key = keypair.public_key();
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+        let signature = keypair.sign_prehashed(&data);
+
+        let mut verifier = HybridVerifier::new(public_key.clone());
+        for chunk in data.chunks(4096) {
+            verifier.update(chunk);
+        }
+        assert!(verifier.finalize(&signature).unwrap());
+        assert!(verify_prehashed(&public_key, &data, &signature).unwrap());
+
+        let mut verifier = HybridVerifier::new(public_key.clone());
+        verifier.update(&data[1..]);
+        assert!(!verifier.finalize(&signature).unwrap());
+        // the prehash signature is not a signature over the raw data
+        assert!(!verify_hybrid_signature(&public_key, &data, &signature).unwrap());
+    }
+
+    #[test]
+    fn test_aad_must_match() {
+        let keypair = HybridKeyPair::generate();
+        let public_key = keypair.public_key();
+        let aad = b"chain_id=1;nonce=7";
+        let signature = keypair.sign_with_aad(b"transfer 10", aad);
+
+        assert!(verify_with_aad(&public_key, b"transfer 10", aad, &signature).unwrap());
+        assert!(!verify_with_aad(&public_key, b"transfer 10", b"chain_id=1;nonce=8", &signature).unwrap());
+        assert!(!verify_with_aad(&public_key, b"transfer 10", b"", &signature).unwrap());
+        assert!(!verify_with_aad(&public_key, b"transfer 11", aad, &signature).unwrap());
+        // moving bytes between the AAD and the message changes the pre-image
+        let split = keypair.sign_with_aad(b"b", b"a");
+        assert!(!verify_with_aad(&public_key, b"", b"ab", &split).unwrap());
+        assert!(!verify_hybrid_signature(&public_key, b"transfer 10", &signature).unwrap());
+    }
+
+    #[test]
+    fn test_pqc_only_policy() {
+        let keypair = HybridKeyPair::generate();
+        let public_key = keypair.public_key();
+        let message = b"migrate";
+
+        let mut pqc_only = keypair.sign_with_policy(message, HybridPolicy::PQC_ONLY).unwrap();
+        assert!(pqc_only.ed25519_sig.is_empty());
+        assert_eq!(pqc_only.algorithm, Algorithm::Dilithium3);
+        assert!(verify_with_policy(&public_key, message, &pqc_only, HybridPolicy::PQC_ONLY).unwrap());
+        // the classical half is not consulted, even when present and wrong
+        pqc_only.ed25519_sig = vec![0u8; 64];
+        assert!(verify_with_policy(&public_key, message, &pqc_only, HybridPolicy::PQC_ONLY).unwrap());
+        assert!(verify_with_policy(&public_key, message, &pqc_only, HybridPolicy::BOTH).is_err());
+
+        let classical_only = keypair.sign_with_policy(message, HybridPolicy::CLASSICAL_ONLY).unwrap();
+        assert!(classical_only.dilithium_sig.is_empty());
+        assert_eq!(classical_only.algorithm, Algorithm::Ed25519);
+        assert!(verify_with_policy(&public_key, message, &classical_only, HybridPolicy::CLASSICAL_ONLY).unwrap());
+        assert!(verify_with_policy(&public_key, message, &classical_only, HybridPolicy::PQC_ONLY).is_err());
+        assert_eq!(keypair.sign_with_policy(message, HybridPolicy::BOTH).unwrap().algorithm, Algorithm::Ed25519Dilithium3);
+
+        let neither = HybridPolicy { require_classical: false, require_pqc: false };
+        assert!(keypair.sign_with_policy(message, neither).is_err());
+        assert!(verify_with_policy(&public_key, message, &keypair.sign(message), neither).is_err());
+        assert!(set_hybrid_policy(neither).is_err());
+        assert_eq!(hybrid_policy(), HybridPolicy::BOTH);
+    }
+
+    #[test]
+    fn test_self_test() {
+        assert_eq!(self_test(), Ok(()));
+
+        let corrupted = Ed25519Kat {
+            signature: "f5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+            ..ED25519_KAT
+        };
+        assert_eq!(self_test_with(&corrupted), Err("Ed25519 KAT: signature mismatch".to_string()));
+    }
 }
@@ -2,22 +2,446 @@
 // Full implementation with Wasmer runtime
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
-use wasmer::{Store, Module, Instance, Value, imports, Function, FunctionEnv, FunctionEnvMut};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use sha2::{Digest, Sha256};
+use wasmer::{Store, Module, Instance, Value, imports, Function, FunctionEnv, FunctionEnvMut, FunctionType, CompilerConfig, EngineBuilder, Imports, RuntimeError, AsStoreRef, AsStoreMut};
+use wasmer::{FunctionMiddleware, LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware};
+use wasmer::wasmparser::{Operator, Parser, Payload, Validator, WasmFeatures};
+use wasmer_compiler_cranelift::Cranelift;
+use wasmer_middlewares::Metering;
+use wasmer_middlewares::metering::{get_remaining_points, set_remaining_points, MeteringPoints};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    /// Execution ran past the instruction limit
+    Timeout,
+    /// The VM has used up its exported-function invocation budget
+    InvocationLimit,
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::Timeout => write!(f, "Execution timed out: instruction limit exceeded"),
+            VmError::InvocationLimit => write!(f, "Exported-function invocation limit reached"),
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// Output of `WasmVM::call_contract`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallResult {
+    /// Built-in method output or a status message
+    Text(String),
+    /// Buffer returned by an export with an `i64` result packing `(ptr << 32) | len`
+    Bytes(Vec<u8>),
+}
+
+/// Coarse safety limits applied ahead of per-opcode gas metering.
+/// The instruction limit is deterministic, so every node aborts a
+/// pathological module at exactly the same point (unlike a wall-clock timer).
+#[derive(Debug, Clone, Copy)]
+pub struct ExecutionLimits {
+    pub max_instructions: u64,
+    pub max_invocations: u64,
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        ExecutionLimits {
+            max_instructions: 10_000_000,
+            max_invocations: u64::MAX,
+        }
+    }
+}
+
+/// Deepest chain of nested `call_contract` frames, counting the top-level call.
+pub const MAX_CALL_DEPTH: usize = 8;
+
+/// Flat gas charged for every deployment.
+pub const DEPLOY_BASE_GAS: u64 = 21_000;
+/// Additional deployment gas per byte of module code.
+pub const DEPLOY_GAS_PER_BYTE: u64 = 200;
+
+/// Host-side gas prices, fixed when the VM is constructed. Every validator
+/// must run the same schedule or their gas accounting diverges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasSchedule {
+    /// Flat cost of a deployment or upgrade
+    pub deploy_base: u64,
+    /// Deployment cost per byte of module code
+    pub deploy_per_byte: u64,
+    /// Every `call_contract`, built-in or WASM
+    pub call_base: u64,
+    /// A storage read, built-in `get_storage` or host `storage_get`
+    pub storage_read: u64,
+    /// A storage write, built-in `set_storage` or host `storage_set`
+    pub storage_write: u64,
+    /// The built-in `transfer`
+    pub transfer: u64,
+    /// Loading a module before it is compiled and instantiated
+    pub instantiate: u64,
+    /// Invoking an exported function
+    pub execute: u64,
+    /// A `call_contract` host call, on top of the callee's own usage
+    pub host_call: u64,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        GasSchedule {
+            deploy_base: DEPLOY_BASE_GAS,
+            deploy_per_byte: DEPLOY_GAS_PER_BYTE,
+            call_base: 3_000,
+            storage_read: 0,
+            storage_write: 5_000,
+            transfer: 10_000,
+            instantiate: 1_000,
+            execute: 10_000,
+            host_call: 10_000,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WasmContract {
     pub address: String,
     pub code: Vec<u8>,
-    pub storage: HashMap<String, String>,
+    // ordered so serialized contracts (and any hash over them) are deterministic
+    pub storage: BTreeMap<String, String>,
     pub balance: u64,
+    // JSON interface description supplied by the deployer, if any
+    #[serde(default)]
+    pub abi: Option<String>,
+    // the deployer; empty for contracts saved before ownership was recorded
+    #[serde(default)]
+    pub owner: String,
+    // callers the owner allowed to upgrade or destroy the contract
+    #[serde(default)]
+    pub operators: BTreeSet<String>,
 }
 
 #[derive(Clone)]
 struct WasmEnv {
-    storage: HashMap<String, String>,
+    storage: BTreeMap<String, String>,
     gas_used: u64,
     gas_limit: u64,
+    // set once instantiated so host functions can read metering points
+    instance: Option<Instance>,
+    // inputs to random_seed: block hash, the contract called, and draws so far this call
+    block_hash: [u8; 32],
+    address: String,
+    random_draws: u64,
+    gas: GasSchedule,
+    // shared by every frame of one top-level call; see `CallContext`
+    context: Arc<Mutex<CallContext>>,
+}
+
+/// State shared by the frames of a single top-level call. The VM's contracts
+/// are moved in while the call runs so `call_contract` can reach callee code;
+/// storage committed by nested frames sits in `written` until the top-level
+/// call succeeds, and is dropped if it fails.
+#[derive(Default)]
+struct CallContext {
+    contracts: HashMap<String, WasmContract>,
+    written: HashMap<String, BTreeMap<String, String>>,
+    // addresses of the frames currently executing, outermost first
+    stack: Vec<String>,
+    block_hash: [u8; 32],
+    gas: GasSchedule,
+}
+
+/// `random_seed` output: the first 8 bytes (big-endian) of
+/// sha256(block_hash || address || draw). Anyone who knows the block hash can
+/// predict it, and the block producer can grind it; it is NOT secure randomness.
+fn random_seed(block_hash: &[u8; 32], address: &str, draw: u64) -> i64 {
+    let digest = Sha256::new()
+        .chain_update(block_hash)
+        .chain_update(address.as_bytes())
+        .chain_update(draw.to_be_bytes())
+        .finalize();
+    i64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+/// Module of every host function; contracts may import nothing else.
+pub const HOST_MODULE: &str = "env";
+/// Names registered by `host_imports`, checked against a module's imports at deploy time.
+pub const HOST_FUNCTIONS: &[&str] = &["storage_get", "storage_set", "gas_remaining", "random_seed", "call_contract"];
+
+// Reject a module importing anything `host_imports` doesn't provide, so it
+// fails at deployment instead of at its first call
+fn check_imports(code: &[u8]) -> Result<()> {
+    for payload in Parser::new(0).parse_all(code) {
+        let payload = payload.map_err(|e| anyhow!("Invalid WASM module: {}", e))?;
+        if let Payload::ImportSection(reader) = payload {
+            for import in reader {
+                let import = import.map_err(|e| anyhow!("Invalid WASM module: {}", e))?;
+                if import.module != HOST_MODULE || !HOST_FUNCTIONS.contains(&import.name) {
+                    return Err(anyhow!("unsupported import: {}.{}", import.module, import.name));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Float results are not bit-identical across hosts (NaN payloads above all),
+// so contracts may not declare float types or use float operators.
+fn check_floats(code: &[u8]) -> Result<()> {
+    let features = WasmFeatures { floats: false, ..WasmFeatures::default() };
+    Validator::new_with_features(features).validate_all(code)
+        .map_err(|e| anyhow!("Invalid WASM module: {}", e))?;
+    Ok(())
+}
+
+fn is_float_operator(operator: &Operator) -> bool {
+    use Operator::*;
+    matches!(operator,
+        F32Load { .. } | F64Load { .. } | F32Store { .. } | F64Store { .. }
+        | F32Const { .. } | F64Const { .. }
+        | F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge
+        | F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge
+        | F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt
+        | F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign
+        | F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt
+        | F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign
+        | I32TruncF32S | I32TruncF32U | I32TruncF64S | I32TruncF64U
+        | I64TruncF32S | I64TruncF32U | I64TruncF64S | I64TruncF64U
+        | I32TruncSatF32S | I32TruncSatF32U | I32TruncSatF64S | I32TruncSatF64U
+        | I64TruncSatF32S | I64TruncSatF32U | I64TruncSatF64S | I64TruncSatF64U
+        | F32ConvertI32S | F32ConvertI32U | F32ConvertI64S | F32ConvertI64U | F32DemoteF64
+        | F64ConvertI32S | F64ConvertI32U | F64ConvertI64S | F64ConvertI64U | F64PromoteF32
+        | I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64)
+}
+
+/// Compiles every scalar float operator to `unreachable`. Deployment already
+/// rejects float code; this covers modules that reach the compiler some other
+/// way (e.g. contracts stored before the check existed), so they trap at the
+/// first float operation instead of computing host-dependent results.
+#[derive(Debug, Default)]
+struct TrapFloats;
+
+impl ModuleMiddleware for TrapFloats {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionTrapFloats)
+    }
+}
+
+#[derive(Debug)]
+struct FunctionTrapFloats;
+
+impl FunctionMiddleware for FunctionTrapFloats {
+    fn feed<'a>(&mut self, operator: Operator<'a>, state: &mut MiddlewareReaderState<'a>) -> Result<(), MiddlewareError> {
+        if is_float_operator(&operator) {
+            state.push_operator(Operator::Unreachable);
+        } else {
+            state.push_operator(operator);
+        }
+        Ok(())
+    }
+}
+
+/// Host functions imported by every frame. Each frame has its own `WasmEnv`,
+/// so storage access is always scoped to the contract that frame is running.
+fn host_imports(store: &mut Store, env: &FunctionEnv<WasmEnv>) -> Imports {
+    // Define host functions available to WASM contracts
+    // NOTE: Current implementation uses i32 values directly (baseline version)
+    // Production version should use memory pointers: storage_get(key_ptr, key_len) -> value_offset
+    // and storage_set(key_ptr, key_len, value_ptr, value_len) for arbitrary data
+    let store_get_fn = Function::new_typed_with_env(
+        store,
+        env,
+        |mut env: FunctionEnvMut<WasmEnv>, key: i32| -> i32 {
+            // Baseline: Read numeric keys from storage
+            // TODO: Implement memory-based storage for production use
+            let key_str = key.to_string();
+            env.data_mut().gas_used += env.data().gas.storage_read;
+            env.data().storage.get(&key_str)
+                .and_then(|v| v.parse::<i32>().ok())
+                .unwrap_or(0)
+        }
+    );
+
+    let store_set_fn = Function::new_typed_with_env(
+        store,
+        env,
+        |mut env: FunctionEnvMut<WasmEnv>, key: i32, value: i32| {
+            // Baseline: Write numeric key-value pairs
+            // TODO: Implement memory-based storage for production use
+            let key_str = key.to_string();
+            let value_str = value.to_string();
+            env.data_mut().storage.insert(key_str, value_str);
+            env.data_mut().gas_used += env.data().gas.storage_write;
+        }
+    );
+
+    // Gas left for this call, capped by the remaining instruction budget
+    let gas_remaining_fn = Function::new_typed_with_env(
+        store,
+        env,
+        |mut env: FunctionEnvMut<WasmEnv>| -> i64 {
+            let (data, mut store) = env.data_and_store_mut();
+            let gas_left = data.gas_limit.saturating_sub(data.gas_used);
+            let points_left = match &data.instance {
+                Some(instance) => points_left(&mut store, instance),
+                None => u64::MAX,
+            };
+            gas_left.min(points_left) as i64
+        }
+    );
+
+    // Deterministic pseudo-randomness; see `random_seed`
+    let random_seed_fn = Function::new_typed_with_env(
+        store,
+        env,
+        |mut env: FunctionEnvMut<WasmEnv>| -> i64 {
+            let data = env.data_mut();
+            let seed = random_seed(&data.block_hash, &data.address, data.random_draws);
+            data.random_draws += 1;
+            seed
+        }
+    );
+
+    // Invoke an export of another contract: call_contract(addr_ptr, addr_len,
+    // method_ptr, method_len) -> the callee's i32 result, or 0 if it has none.
+    // Both names are read from this contract's exported memory. The callee
+    // runs on this frame's remaining instructions, and whatever it executes
+    // is taken off them.
+    let call_contract_fn = Function::new_typed_with_env(
+        store,
+        env,
+        |mut env: FunctionEnvMut<WasmEnv>, address_ptr: i32, address_len: i32, method_ptr: i32, method_len: i32| -> std::result::Result<i32, RuntimeError> {
+            let (data, mut store) = env.data_and_store_mut();
+            let address = read_guest_string(&data.instance, &store, address_ptr, address_len)
+                .map_err(|e| RuntimeError::new(e.to_string()))?;
+            let method = read_guest_string(&data.instance, &store, method_ptr, method_len)
+                .map_err(|e| RuntimeError::new(e.to_string()))?;
+            let host_call = data.gas.host_call;
+            let gas_limit = data.gas_limit.saturating_sub(data.gas_used).saturating_sub(host_call);
+            let context = data.context.clone();
+            let instance = data.instance.clone().ok_or_else(|| RuntimeError::new("Contract is not instantiated"))?;
+            let mut points = points_left(&mut store, &instance);
+            let outcome = call_nested(&context, &address, &method, gas_limit, &mut points);
+            set_remaining_points(&mut store, &instance, points);
+            let (value, gas_used) = outcome.map_err(|e| RuntimeError::new(e.to_string()))?;
+            env.data_mut().gas_used += host_call + gas_used;
+            Ok(value)
+        }
+    );
+
+    imports! {
+        "env" => {
+            "storage_get" => store_get_fn,
+            "storage_set" => store_set_fn,
+            "gas_remaining" => gas_remaining_fn,
+            "random_seed" => random_seed_fn,
+            "call_contract" => call_contract_fn,
+        }
+    }
+}
+
+// Instructions an instance may still execute
+fn points_left(store: &mut impl AsStoreMut, instance: &Instance) -> u64 {
+    match get_remaining_points(store, instance) {
+        MeteringPoints::Remaining(points) => points,
+        MeteringPoints::Exhausted => 0,
+    }
+}
+
+fn read_guest_string(instance: &Option<Instance>, store: &impl AsStoreRef, ptr: i32, len: i32) -> Result<String> {
+    let memory = instance.as_ref()
+        .ok_or_else(|| anyhow!("Contract is not instantiated"))?
+        .exports.get_memory("memory")
+        .map_err(|_| anyhow!("call_contract requires an exported memory"))?;
+    let view = memory.view(store);
+    let (ptr, len) = (ptr as u32 as u64, len as u32 as u64);
+    // check the region before allocating, as read_packed_bytes does
+    if !ptr.checked_add(len).is_some_and(|end| end <= view.data_size()) {
+        return Err(anyhow!("call_contract argument out of bounds"));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    view.read(ptr, &mut bytes)
+        .map_err(|e| anyhow!("call_contract argument out of bounds: {}", e))?;
+    String::from_utf8(bytes).map_err(|_| anyhow!("call_contract argument is not UTF-8"))
+}
+
+/// Run `method` of `address` for a contract that is already executing. The
+/// callee gets its own store and a `WasmEnv` over its own storage, and its
+/// writes are committed to `context` only if it returns without trapping.
+/// Returns the callee's i32 result and the host gas it used. `points` is the
+/// instruction budget the callee may use; on return it holds what is left.
+fn call_nested(context: &Arc<Mutex<CallContext>>, address: &str, method: &str, gas_limit: u64, points: &mut u64) -> Result<(i32, u64)> {
+    let (code, storage, block_hash, gas) = {
+        let mut ctx = context.lock().unwrap();
+        if ctx.stack.iter().any(|frame| frame == address) {
+            return Err(anyhow!("Re-entrant call into {}", address));
+        }
+        if ctx.stack.len() >= MAX_CALL_DEPTH {
+            return Err(anyhow!("Call depth limit of {} reached", MAX_CALL_DEPTH));
+        }
+        let contract = ctx.contracts.get(address)
+            .ok_or_else(|| anyhow!("Contract not found: {}", address))?;
+        let storage = ctx.written.get(address).unwrap_or(&contract.storage).clone();
+        let code = contract.code.clone();
+        ctx.stack.push(address.to_string());
+        (code, storage, ctx.block_hash, ctx.gas)
+    };
+
+    let frame = || -> Result<(i32, BTreeMap<String, String>, u64)> {
+        let mut store = metered_store(*points);
+        let module = Module::new(&store, &code)?;
+        let env = FunctionEnv::new(&mut store, WasmEnv {
+            storage,
+            gas_used: 0,
+            gas_limit,
+            instance: None,
+            block_hash,
+            address: address.to_string(),
+            random_draws: 0,
+            gas,
+            context: context.clone(),
+        });
+        let import_object = host_imports(&mut store, &env);
+        let instance = Instance::new(&mut store, &module, &import_object)?;
+        let func = instance.exports.get_function(method)?;
+        env.as_mut(&mut store).instance = Some(instance.clone());
+        let results = func.call(&mut store, &[]);
+        *points = points_left(&mut store, &instance);
+        let results = results.map_err(|e| match *points {
+            0 => anyhow::Error::from(VmError::Timeout),
+            _ => anyhow!("Call into {} failed: {}", address, e),
+        })?;
+        let data = env.as_ref(&store);
+        if data.gas_used > gas_limit {
+            return Err(anyhow!("Out of gas in call to {}", address));
+        }
+        let value = match results.first() {
+            Some(Value::I32(value)) => *value,
+            _ => 0,
+        };
+        Ok((value, data.storage.clone(), data.gas_used))
+    };
+    let outcome = frame();
+
+    let mut ctx = context.lock().unwrap();
+    ctx.stack.pop();
+    let (value, storage, gas_used) = outcome?;
+    ctx.written.insert(address.to_string(), storage);
+    Ok((value, gas_used))
+}
+
+/// Everything a test can observe about a `WasmVM`, captured by `snapshot`.
+/// The compilation store is not included; it holds no contract state.
+#[derive(Debug, Clone)]
+pub struct VmSnapshot {
+    contracts: HashMap<String, WasmContract>,
+    gas_limit: u64,
+    gas_used: u64,
+    invocations: u64,
 }
 
 pub struct WasmVM {
@@ -25,52 +449,213 @@ pub struct WasmVM {
     gas_limit: u64,
     gas_used: u64,
     store: Store,
+    limits: ExecutionLimits,
+    invocations: u64,
+    gas: GasSchedule,
+    // hash of the block being executed, seeding random_seed
+    block_hash: [u8; 32],
+}
+
+// Every operator costs one point, so the metering limit is an instruction count.
+// A Metering middleware can only be attached to a single module, hence a fresh
+// store (and engine) for every compilation.
+/// Compiler shared by every contract. Besides metering it guarantees float
+/// determinism: floats are rejected at deploy, any float operator that still
+/// reaches compilation traps, and NaNs are canonicalized as a last resort.
+fn metered_store(max_instructions: u64) -> Store {
+    let metering = Arc::new(Metering::new(max_instructions, |_: &Operator| -> u64 { 1 }));
+    let mut compiler = Cranelift::default();
+    compiler.canonicalize_nans(true);
+    compiler.push_middleware(metering);
+    compiler.push_middleware(Arc::new(TrapFloats));
+    Store::new(EngineBuilder::new(compiler))
 }
 
 impl WasmVM {
-    pub fn new(gas_limit: u64) -> Self {
+    pub fn new(gas_limit: u64, gas: GasSchedule) -> Self {
+        Self::with_limits(gas_limit, ExecutionLimits::default()).with_gas_schedule(gas)
+    }
+
+    /// A VM with custom limits and the default gas schedule; chain
+    /// `with_gas_schedule` to price it differently.
+    pub fn with_limits(gas_limit: u64, limits: ExecutionLimits) -> Self {
         WasmVM {
             contracts: HashMap::new(),
             gas_limit,
             gas_used: 0,
-            store: Store::default(),
+            store: metered_store(limits.max_instructions),
+            limits,
+            invocations: 0,
+            gas: GasSchedule::default(),
+            block_hash: [0; 32],
         }
     }
 
-    pub fn deploy_contract(&mut self, address: String, code: Vec<u8>) -> Result<()> {
+    pub fn with_gas_schedule(mut self, gas: GasSchedule) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// Set the block context for subsequent calls. Every validator must set
+    /// the same hash before executing a block's calls so `random_seed` agrees.
+    pub fn set_block_hash(&mut self, block_hash: [u8; 32]) {
+        self.block_hash = block_hash;
+    }
+
+    /// Override the deployment gas schedule (`base + per_byte * code.len()`).
+    pub fn set_deploy_gas(&mut self, base: u64, per_byte: u64) {
+        self.gas.deploy_base = base;
+        self.gas.deploy_per_byte = per_byte;
+    }
+
+    pub fn gas_schedule(&self) -> &GasSchedule {
+        &self.gas
+    }
+
+    pub fn deploy_cost(&self, code_len: usize) -> u64 {
+        self.gas.deploy_per_byte
+            .saturating_mul(code_len as u64)
+            .saturating_add(self.gas.deploy_base)
+    }
+
+    pub fn deploy_contract(&mut self, deployer: &str, address: String, code: Vec<u8>) -> Result<()> {
+        self.deploy_contract_with_abi(deployer, address, code, None)
+    }
+
+    /// Deploy with an optional JSON ABI that tooling can later fetch via `get_abi`.
+    pub fn deploy_contract_with_abi(&mut self, deployer: &str, address: String, code: Vec<u8>, abi: Option<String>) -> Result<()> {
+        if let Some(abi) = &abi {
+            serde_json::from_str::<serde_json::Value>(abi)
+                .map_err(|e| anyhow!("ABI is not valid JSON: {}", e))?;
+        }
+
         if self.contracts.contains_key(&address) {
             return Err(anyhow!("Contract already exists at address"));
         }
 
-        // Validate WASM bytecode
-        if code.len() < 4 || &code[0..4] != b"\0asm" {
-            return Err(anyhow!("Invalid WASM magic number"));
-        }
+        // Checked up front so an unaffordable deployment leaves no contract behind
+        let cost = self.check_deployable(&code)?;
 
         let contract = WasmContract {
             address: address.clone(),
             code,
-            storage: HashMap::new(),
+            storage: BTreeMap::new(),
             balance: 0,
+            abi,
+            owner: deployer.to_string(),
+            operators: BTreeSet::new(),
         };
 
         self.contracts.insert(address, contract);
-        self.consume_gas(21000)?;
+        self.consume_gas(cost)
+    }
+
+    // Validate the module header and imports and return its deployment cost if affordable
+    fn check_deployable(&self, code: &[u8]) -> Result<u64> {
+        if code.len() < 4 || &code[0..4] != b"\0asm" {
+            return Err(anyhow!("Invalid WASM magic number"));
+        }
+        check_imports(code)?;
+        check_floats(code)?;
+        let cost = self.deploy_cost(code.len());
+        let remaining = self.gas_limit.saturating_sub(self.gas_used);
+        if cost > remaining {
+            return Err(anyhow!("Out of gas: deployment needs {} but only {} remains", cost, remaining));
+        }
+        Ok(cost)
+    }
+
+    pub fn owner(&self, address: &str) -> Result<&str> {
+        let contract = self.contracts.get(address)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
+        Ok(&contract.owner)
+    }
+
+    /// Let `operator` upgrade or destroy the contract; only the owner may do this.
+    pub fn authorize_operator(&mut self, address: &str, caller: &str, operator: &str) -> Result<()> {
+        let contract = self.owned_contract(address, caller)?;
+        contract.operators.insert(operator.to_string());
+        Ok(())
+    }
+
+    pub fn revoke_operator(&mut self, address: &str, caller: &str, operator: &str) -> Result<()> {
+        let contract = self.owned_contract(address, caller)?;
+        contract.operators.remove(operator);
         Ok(())
     }
 
-    pub fn call_contract(&mut self, address: &str, method: &str, args: Vec<String>) -> Result<String> {
+    /// Replace the contract's code, keeping its storage and balance. Charged
+    /// like a deployment of the new code.
+    pub fn upgrade_contract(&mut self, address: &str, caller: &str, code: Vec<u8>) -> Result<()> {
+        self.ensure_privileged(address, caller)?;
+        let cost = self.check_deployable(&code)?;
+        self.contracts.get_mut(address).unwrap().code = code;
+        self.consume_gas(cost)
+    }
+
+    /// Remove the contract, returning the balance it held.
+    pub fn destroy_contract(&mut self, address: &str, caller: &str) -> Result<u64> {
+        self.ensure_privileged(address, caller)?;
+        Ok(self.contracts.remove(address).unwrap().balance)
+    }
+
+    fn owned_contract(&mut self, address: &str, caller: &str) -> Result<&mut WasmContract> {
         let contract = self.contracts.get_mut(address)
             .ok_or_else(|| anyhow!("Contract not found"))?;
+        if contract.owner.is_empty() || contract.owner != caller {
+            return Err(anyhow!("Caller is not the contract owner"));
+        }
+        Ok(contract)
+    }
+
+    // Owner or an operator the owner authorized
+    fn ensure_privileged(&self, address: &str, caller: &str) -> Result<()> {
+        let contract = self.contracts.get(address)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
+        let is_owner = !contract.owner.is_empty() && contract.owner == caller;
+        if !is_owner && !contract.operators.contains(caller) {
+            return Err(anyhow!("Caller is not the contract owner or an authorized operator"));
+        }
+        Ok(())
+    }
+
+    pub fn get_abi(&self, address: &str) -> Result<Option<&str>> {
+        let contract = self.contracts.get(address)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
+        Ok(contract.abi.as_deref())
+    }
+
+    /// Exported functions of the contract's module with their signatures.
+    pub fn list_exports(&self, address: &str) -> Result<Vec<(String, FunctionType)>> {
+        let contract = self.contracts.get(address)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
+        // a store of its own: the shared one's Metering middleware can only
+        // ever be attached to the module it last compiled
+        let store = Store::new(EngineBuilder::new(Cranelift::default()));
+        let module = Module::new(&store, &contract.code)
+            .map_err(|e| anyhow!("Failed to compile contract: {}", e))?;
+        Ok(module.exports().functions().map(|f| (f.name().to_string(), f.ty().clone())).collect())
+    }
 
-        self.consume_gas(3000)?;
+    pub fn call_contract(&mut self, address: &str, method: &str, args: Vec<String>) -> Result<CallResult> {
+        if !self.contracts.contains_key(address) {
+            return Err(anyhow!("Contract not found"));
+        }
+
+        self.consume_gas(self.gas.call_base)?;
+        let gas = self.gas;
+
+        let contract = self.contracts.get_mut(address)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
 
         // Handle built-in methods
-        match method {
+        let text = match method {
             "get_balance" => Ok(contract.balance.to_string()),
             "get_storage" => {
                 if let Some(key) = args.get(0) {
-                    Ok(contract.storage.get(key).cloned().unwrap_or_default())
+                    let value = contract.storage.get(key).cloned().unwrap_or_default();
+                    self.consume_gas(gas.storage_read)?;
+                    Ok(value)
                 } else {
                     Err(anyhow!("Missing storage key"))
                 }
@@ -80,7 +665,7 @@ impl WasmVM {
                     let key = args[0].clone();
                     let value = args[1].clone();
                     contract.storage.insert(key.clone(), value.clone());
-                    self.consume_gas(5000)?;
+                    self.consume_gas(gas.storage_write)?;
                     Ok(format!("Storage set: {} = {}", key, value))
                 } else {
                     Err(anyhow!("Missing key or value"))
@@ -91,7 +676,7 @@ impl WasmVM {
                     let amount: u64 = args[0].parse().unwrap_or(0);
                     if contract.balance >= amount {
                         contract.balance -= amount;
-                        self.consume_gas(10000)?;
+                        self.consume_gas(gas.transfer)?;
                         Ok(format!("Transferred: {}", amount))
                     } else {
                         Err(anyhow!("Insufficient balance"))
@@ -102,14 +687,15 @@ impl WasmVM {
             },
             _ => {
                 // Execute WASM for custom methods
-                self.execute_wasm_method(address, method, &args)
+                return self.execute_wasm_method(address, method, &args);
             }
-        }
+        };
+        text.map(CallResult::Text)
     }
 
-    fn execute_wasm_method(&mut self, address: &str, method: &str, args: &[String]) -> Result<String> {
+    fn execute_wasm_method(&mut self, address: &str, method: &str, args: &[String]) -> Result<CallResult> {
         // Get contract data for execution
-        self.consume_gas(1000)?;
+        self.consume_gas(self.gas.instantiate)?;
 
         let (contract_code, contract_storage) = {
             let contract = self.contracts.get(address)
@@ -117,7 +703,15 @@ impl WasmVM {
             (contract.code.clone(), contract.storage.clone())
         };
 
+        let context = Arc::new(Mutex::new(CallContext {
+            stack: vec![address.to_string()],
+            block_hash: self.block_hash,
+            gas: self.gas,
+            ..CallContext::default()
+        }));
+
         // Try to compile and execute WASM
+        self.store = metered_store(self.limits.max_instructions);
         match Module::new(&self.store, &contract_code) {
             Ok(module) => {
                 // Create environment for host functions
@@ -125,56 +719,41 @@ impl WasmVM {
                     storage: contract_storage.clone(),
                     gas_used: 0,
                     gas_limit: self.gas_limit - self.gas_used,
+                    instance: None,
+                    block_hash: self.block_hash,
+                    address: address.to_string(),
+                    random_draws: 0,
+                    gas: self.gas,
+                    context: context.clone(),
                 });
 
-                // Define host functions available to WASM contracts
-                // NOTE: Current implementation uses i32 values directly (baseline version)
-                // Production version should use memory pointers: storage_get(key_ptr, key_len) -> value_offset
-                // and storage_set(key_ptr, key_len, value_ptr, value_len) for arbitrary data
-                let store_get_fn = Function::new_typed_with_env(
-                    &mut self.store,
-                    &env,
-                    |env: FunctionEnvMut<WasmEnv>, key: i32| -> i32 {
-                        // Baseline: Read numeric keys from storage
-                        // TODO: Implement memory-based storage for production use
-                        let key_str = key.to_string();
-                        env.data().storage.get(&key_str)
-                            .and_then(|v| v.parse::<i32>().ok())
-                            .unwrap_or(0)
-                    }
-                );
-
-                let store_set_fn = Function::new_typed_with_env(
-                    &mut self.store,
-                    &env,
-                    |mut env: FunctionEnvMut<WasmEnv>, key: i32, value: i32| {
-                        // Baseline: Write numeric key-value pairs
-                        // TODO: Implement memory-based storage for production use
-                        let key_str = key.to_string();
-                        let value_str = value.to_string();
-                        env.data_mut().storage.insert(key_str, value_str);
-                        env.data_mut().gas_used += 5000;
-                    }
-                );
-
-                let import_object = imports! {
-                    "env" => {
-                        "storage_get" => store_get_fn,
-                        "storage_set" => store_set_fn,
-                    }
-                };
+                let import_object = host_imports(&mut self.store, &env);
 
                 // Instantiate WASM module
                 match Instance::new(&mut self.store, &module, &import_object) {
                     Ok(instance) => {
                         // Try to call the exported function
                         if let Ok(func) = instance.exports.get_function(method) {
-                            self.consume_gas(10000)?;
-                            
+                            self.consume_gas(self.gas.execute)?;
+                            self.record_invocation()?;
+                            let env_data = env.as_mut(&mut self.store);
+                            env_data.gas_limit = self.gas_limit - self.gas_used;
+                            env_data.instance = Some(instance.clone());
+
+                            // Lend the contracts to the call context so nested calls can reach them
+                            context.lock().unwrap().contracts = std::mem::take(&mut self.contracts);
                             // Call with no arguments for simplicity
-                            match func.call(&mut self.store, &[]) {
+                            let outcome = func.call(&mut self.store, &[]);
+                            let finished = std::mem::take(&mut *context.lock().unwrap());
+                            self.contracts = finished.contracts;
+                            match outcome {
                                 Ok(results) => {
-                                    // Persist storage changes from environment back to contract
+                                    // Commit storage written by nested calls, then the caller's own
+                                    for (callee, storage) in finished.written {
+                                        if let Some(contract) = self.contracts.get_mut(&callee) {
+                                            contract.storage = storage;
+                                        }
+                                    }
                                     let updated_storage = env.as_ref(&self.store).storage.clone();
                                     let gas_consumed = env.as_ref(&self.store).gas_used;
                                     
@@ -183,48 +762,79 @@ impl WasmVM {
                                     }
                                     self.consume_gas(gas_consumed)?;
                                     
-                                    if let Some(Value::I32(result)) = results.get(0) {
-                                        Ok(format!("WASM execution result: {}", result))
-                                    } else {
-                                        Ok(format!("WASM execution completed"))
+                                    match results.first() {
+                                        Some(Value::I32(result)) => Ok(CallResult::Text(format!("WASM execution result: {}", result))),
+                                        Some(Value::I64(packed)) => self.read_packed_bytes(&instance, *packed).map(CallResult::Bytes),
+                                        _ => Ok(CallResult::Text("WASM execution completed".to_string())),
                                     }
                                 },
-                                Err(e) => Ok(format!("WASM execution error: {}", e)),
+                                Err(e) => {
+                                    // a nested call that ran out leaves this frame with no points either
+                                    if points_left(&mut self.store, &instance) == 0 {
+                                        return Err(VmError::Timeout.into());
+                                    }
+                                    Ok(CallResult::Text(format!("WASM execution error: {}", e)))
+                                },
                             }
                         } else {
-                            Ok(format!("Method '{}' not found in WASM exports", method))
+                            Ok(CallResult::Text(format!("Method '{}' not found in WASM exports", method)))
                         }
                     },
-                    Err(e) => Ok(format!("WASM instantiation failed: {}", e)),
+                    Err(e) => Ok(CallResult::Text(format!("WASM instantiation failed: {}", e))),
                 }
             },
             Err(_) => {
                 // Fallback for invalid WASM
-                Ok(format!("WASM execution fallback for method '{}' with {} args", method, args.len()))
+                Ok(CallResult::Text(format!("WASM execution fallback for method '{}' with {} args", method, args.len())))
             }
         }
     }
 
+    // Unpack `(ptr << 32) | len` and copy that region out of the exported memory
+    fn read_packed_bytes(&self, instance: &Instance, packed: i64) -> Result<Vec<u8>> {
+        let ptr = packed as u64 >> 32;
+        let len = (packed as u64 & 0xffff_ffff) as usize;
+        let memory = instance.exports.get_memory("memory")
+            .map_err(|_| anyhow!("Contract returned a buffer but exports no memory"))?;
+        let view = memory.view(&self.store);
+        // check the region before allocating, so a bogus length can't request 4 GiB
+        if !ptr.checked_add(len as u64).is_some_and(|end| end <= view.data_size()) {
+            return Err(anyhow!("Returned buffer out of bounds"));
+        }
+        let mut bytes = vec![0u8; len];
+        view.read(ptr, &mut bytes)
+            .map_err(|e| anyhow!("Returned buffer out of bounds: {}", e))?;
+        Ok(bytes)
+    }
+
     pub fn execute_wasm(&mut self, address: &str, input: &[u8]) -> Result<Vec<u8>> {
-        let contract = self.contracts.get(address)
-            .ok_or_else(|| anyhow!("Contract not found"))?;
+        let code = self.contracts.get(address)
+            .ok_or_else(|| anyhow!("Contract not found"))?
+            .code.clone();
 
-        self.consume_gas(1000)?;
+        self.consume_gas(self.gas.instantiate)?;
         
         // Try to execute WASM module
-        match Module::new(&self.store, &contract.code) {
+        self.store = metered_store(self.limits.max_instructions);
+        match Module::new(&self.store, &code) {
             Ok(module) => {
                 let env = FunctionEnv::new(&mut self.store, WasmEnv {
-                    storage: HashMap::new(),
+                    storage: BTreeMap::new(),
                     gas_used: 0,
                     gas_limit: self.gas_limit - self.gas_used,
+                    instance: None,
+                    block_hash: self.block_hash,
+                    address: address.to_string(),
+                    random_draws: 0,
+                    gas: self.gas,
+                    context: Arc::default(),
                 });
 
                 let import_object = imports! {};
                 
                 match Instance::new(&mut self.store, &module, &import_object) {
                     Ok(_instance) => {
-                        self.consume_gas(10000)?;
+                        self.consume_gas(self.gas.execute)?;
                         Ok(format!("WASM executed for {} bytes input", input.len()).into_bytes())
                     },
                     Err(e) => Ok(format!("WASM instantiation error: {}", e).into_bytes()),
@@ -236,6 +846,14 @@ impl WasmVM {
         }
     }
 
+    fn record_invocation(&mut self) -> Result<()> {
+        if self.invocations >= self.limits.max_invocations {
+            return Err(VmError::InvocationLimit.into());
+        }
+        self.invocations += 1;
+        Ok(())
+    }
+
     fn consume_gas(&mut self, amount: u64) -> Result<()> {
         self.gas_used += amount;
         if self.gas_used > self.gas_limit {
@@ -256,9 +874,46 @@ impl WasmVM {
     pub fn deposit(&mut self, address: &str, amount: u64) -> Result<()> {
         let contract = self.contracts.get_mut(address)
             .ok_or_else(|| anyhow!("Contract not found"))?;
-        contract.balance += amount;
+        contract.balance = contract.balance.checked_add(amount)
+            .ok_or_else(|| anyhow!("Contract balance overflow"))?;
+        Ok(())
+    }
+
+    pub fn withdraw(&mut self, address: &str, amount: u64) -> Result<()> {
+        let contract = self.contracts.get_mut(address)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
+        if contract.balance < amount {
+            return Err(anyhow!("Insufficient contract balance"));
+        }
+        contract.balance -= amount;
+        Ok(())
+    }
+
+    /// Write contract storage directly, bypassing a call (host-side bookkeeping).
+    pub fn set_storage(&mut self, address: &str, key: &str, value: String) -> Result<()> {
+        let contract = self.contracts.get_mut(address)
+            .ok_or_else(|| anyhow!("Contract not found"))?;
+        contract.storage.insert(key.to_string(), value);
         Ok(())
     }
+
+    /// Capture contracts (code, storage, balances) and gas/invocation counters.
+    pub fn snapshot(&self) -> VmSnapshot {
+        VmSnapshot {
+            contracts: self.contracts.clone(),
+            gas_limit: self.gas_limit,
+            gas_used: self.gas_used,
+            invocations: self.invocations,
+        }
+    }
+
+    /// Reset to a previously captured state, dropping anything deployed since.
+    pub fn restore(&mut self, snapshot: VmSnapshot) {
+        self.contracts = snapshot.contracts;
+        self.gas_limit = snapshot.gas_limit;
+        self.gas_used = snapshot.gas_used;
+        self.invocations = snapshot.invocations;
+    }
 }
 
 #[cfg(test)]
@@ -267,23 +922,134 @@ mod tests {
 
     #[test]
     fn test_deploy_and_call_contract() {
-        let mut vm = WasmVM::new(1000000);
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
         
         // Valid WASM magic number
         let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
-        assert!(vm.deploy_contract("contract1".to_string(), code).is_ok());
+        assert!(vm.deploy_contract("deployer", "contract1".to_string(), code).is_ok());
         
         let result = vm.call_contract("contract1", "get_balance", vec![]);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "0");
+        assert_eq!(result.unwrap(), CallResult::Text("0".to_string()));
+    }
+
+    #[test]
+    fn test_abi_and_exports() {
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
+
+        let code = wasmer::wat2wasm(br#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "add") (param i32 i64) (result i64)
+                (i64.add (i64.extend_i32_u (local.get 0)) (local.get 1)))
+              (func (export "noop")))
+        "#).unwrap().to_vec();
+        let abi = r#"{"add": {"params": ["u32", "u64"], "returns": "u64"}}"#;
+        vm.deploy_contract_with_abi("deployer", "adder".to_string(), code, Some(abi.to_string())).unwrap();
+
+        assert_eq!(vm.get_abi("adder").unwrap(), Some(abi));
+        let exports = vm.list_exports("adder").unwrap();
+        let add = FunctionType::new([wasmer::Type::I32, wasmer::Type::I64], [wasmer::Type::I64]);
+        assert_eq!(exports, vec![("add".to_string(), add), ("noop".to_string(), FunctionType::new([], []))]);
+        // listing again, and after an execution has compiled on the VM's store, still works
+        assert_eq!(vm.list_exports("adder").unwrap(), exports);
+        vm.call_contract("adder", "noop", vec![]).unwrap();
+        assert_eq!(vm.list_exports("adder").unwrap(), exports);
+
+        let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        assert!(vm.deploy_contract_with_abi("deployer", "bad".to_string(), code, Some("not json".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_only_owner_or_operator_can_upgrade_and_destroy() {
+        let mut vm = WasmVM::new(10_000_000, GasSchedule::default());
+        let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let mut upgraded = code.clone();
+        upgraded.extend([0u8; 4]);
+        vm.deploy_contract("alice", "c1".to_string(), code.clone()).unwrap();
+        vm.call_contract("c1", "set_storage", vec!["k".to_string(), "v".to_string()]).unwrap();
+        vm.deposit("c1", 40).unwrap();
+        assert_eq!(vm.owner("c1").unwrap(), "alice");
+
+        let err = vm.upgrade_contract("c1", "mallory", upgraded.clone()).unwrap_err();
+        assert!(err.to_string().contains("not the contract owner"));
+        assert!(vm.destroy_contract("c1", "mallory").is_err());
+        assert!(vm.authorize_operator("c1", "mallory", "mallory").is_err());
+        assert_eq!(vm.get_contract("c1").unwrap().code, code);
+
+        vm.upgrade_contract("c1", "alice", upgraded.clone()).unwrap();
+        let contract = vm.get_contract("c1").unwrap();
+        assert_eq!(contract.code, upgraded);
+        assert_eq!(contract.storage.get("k").map(String::as_str), Some("v"));
+
+        vm.authorize_operator("c1", "alice", "ops").unwrap();
+        vm.upgrade_contract("c1", "ops", code.clone()).unwrap();
+        vm.revoke_operator("c1", "alice", "ops").unwrap();
+        assert!(vm.destroy_contract("c1", "ops").is_err());
+
+        assert_eq!(vm.destroy_contract("c1", "alice").unwrap(), 40);
+        assert!(vm.get_contract("c1").is_none());
+    }
+
+    #[test]
+    fn test_deploy_gas_scales_with_code_size() {
+        let small = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        // a 1000-byte custom section: id, LEB128 size 997, name "x", padding
+        let mut large = small.clone();
+        large.extend([0x00, 0xE5, 0x07, 0x01, b'x']);
+        large.extend(vec![0u8; 995]);
+
+        let mut vm = WasmVM::new(10_000_000, GasSchedule::default());
+        vm.deploy_contract("deployer", "small".to_string(), small.clone()).unwrap();
+        let small_gas = vm.get_gas_used();
+        vm.deploy_contract("deployer", "large".to_string(), large.clone()).unwrap();
+        let large_gas = vm.get_gas_used() - small_gas;
+
+        assert_eq!(small_gas, DEPLOY_BASE_GAS + DEPLOY_GAS_PER_BYTE * 8);
+        assert_eq!(large_gas - small_gas, DEPLOY_GAS_PER_BYTE * 1000);
+
+        let mut vm = WasmVM::new(vm.deploy_cost(large.len()) - 1, GasSchedule::default());
+        let err = vm.deploy_contract("deployer", "large".to_string(), large).unwrap_err();
+        assert!(err.to_string().contains("Out of gas"));
+        assert_eq!(vm.get_gas_used(), 0);
+        assert!(vm.get_abi("large").is_err());
+    }
+
+    #[test]
+    fn test_custom_gas_schedule_prices_storage_writes() {
+        let schedule = GasSchedule { call_base: 7, storage_write: 1_234, deploy_base: 0, deploy_per_byte: 0, ..GasSchedule::default() };
+        let mut vm = WasmVM::new(1000000, schedule);
+        let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        vm.deploy_contract("deployer", "contract1".to_string(), code).unwrap();
+        assert_eq!(vm.get_gas_used(), 0);
+
+        vm.call_contract("contract1", "set_storage", vec!["k".to_string(), "v".to_string()]).unwrap();
+        assert_eq!(vm.get_gas_used(), 7 + 1_234);
+        assert_eq!(vm.gas_schedule().storage_write, 1_234);
+
+        assert_eq!(GasSchedule::default().storage_write, 5_000);
+    }
+
+    #[test]
+    fn test_custom_limits_keep_a_custom_gas_schedule() {
+        let schedule = GasSchedule { call_base: 11, storage_write: 99, deploy_base: 0, deploy_per_byte: 0, ..GasSchedule::default() };
+        let limits = ExecutionLimits { max_invocations: 1, ..ExecutionLimits::default() };
+        let mut vm = WasmVM::with_limits(1000000, limits).with_gas_schedule(schedule);
+        assert_eq!(vm.gas_schedule(), &schedule);
+        assert_eq!(vm.limits.max_invocations, 1);
+
+        let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        vm.deploy_contract("deployer", "contract1".to_string(), code).unwrap();
+        vm.call_contract("contract1", "set_storage", vec!["k".to_string(), "v".to_string()]).unwrap();
+        assert_eq!(vm.get_gas_used(), 11 + 99);
     }
 
     #[test]
     fn test_storage_operations() {
-        let mut vm = WasmVM::new(1000000);
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
         
         let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
-        vm.deploy_contract("contract1".to_string(), code).unwrap();
+        vm.deploy_contract("deployer", "contract1".to_string(), code).unwrap();
         
         let set_result = vm.call_contract(
             "contract1",
@@ -297,15 +1063,64 @@ mod tests {
             "get_storage",
             vec!["key1".to_string()]
         );
-        assert_eq!(get_result.unwrap(), "value1");
+        assert_eq!(get_result.unwrap(), CallResult::Text("value1".to_string()));
+    }
+
+    #[test]
+    fn test_storage_serialization_is_deterministic() {
+        // two nodes applying the same writes in a different order
+        let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let write_all = |keys: &[&str]| {
+            let mut vm = WasmVM::new(1000000, GasSchedule::default());
+            vm.deploy_contract("deployer", "contract1".to_string(), code.clone()).unwrap();
+            for key in keys {
+                vm.call_contract("contract1", "set_storage", vec![key.to_string(), format!("{}-value", key)]).unwrap();
+            }
+            vm
+        };
+        let first = write_all(&["zeta", "alpha", "mid"]);
+        let second = write_all(&["mid", "zeta", "alpha"]);
+
+        let encoded = serde_json::to_vec(&first.contracts["contract1"]).unwrap();
+        assert_eq!(encoded, serde_json::to_vec(&second.contracts["contract1"]).unwrap());
+        let keys: Vec<&String> = first.contracts["contract1"].storage.keys().collect();
+        assert_eq!(keys, ["alpha", "mid", "zeta"]);
+
+        // decoding and re-encoding gives the same bytes back
+        let decoded: WasmContract = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(serde_json::to_vec(&decoded).unwrap(), encoded);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
+
+        let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        vm.deploy_contract("deployer", "contract1".to_string(), code.clone()).unwrap();
+        vm.call_contract("contract1", "set_storage", vec!["key1".to_string(), "value1".to_string()]).unwrap();
+        vm.deposit("contract1", 50).unwrap();
+        let gas_before = vm.get_gas_used();
+        let snap = vm.snapshot();
+
+        vm.call_contract("contract1", "set_storage", vec!["key1".to_string(), "value2".to_string()]).unwrap();
+        vm.deposit("contract1", 25).unwrap();
+        vm.deploy_contract("deployer", "contract2".to_string(), code).unwrap();
+        assert!(vm.get_gas_used() > gas_before);
+
+        vm.restore(snap);
+        let contract = vm.get_contract("contract1").unwrap();
+        assert_eq!(contract.storage.get("key1").map(String::as_str), Some("value1"));
+        assert_eq!(contract.balance, 50);
+        assert!(vm.get_contract("contract2").is_none());
+        assert_eq!(vm.get_gas_used(), gas_before);
     }
 
     #[test]
     fn test_gas_limit() {
-        let mut vm = WasmVM::new(5000);
+        let mut vm = WasmVM::new(5000, GasSchedule::default());
         
         let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
-        vm.deploy_contract("contract1".to_string(), code).unwrap();
+        vm.deploy_contract("deployer", "contract1".to_string(), code).unwrap();
         
         let result = vm.call_contract(
             "contract1",
@@ -316,13 +1131,287 @@ mod tests {
         assert!(vm.get_gas_used() > 0);
     }
 
+    #[test]
+    fn test_instruction_limit_stops_infinite_loop() {
+        let limits = ExecutionLimits { max_instructions: 10_000, ..ExecutionLimits::default() };
+        let mut vm = WasmVM::with_limits(1000000, limits);
+
+        let code = wasmer::wat2wasm(br#"
+            (module
+              (func (export "spin")
+                (loop $l (br $l))))
+        "#).unwrap().to_vec();
+        vm.deploy_contract("deployer", "looper".to_string(), code).unwrap();
+
+        let err = vm.call_contract("looper", "spin", vec![]).unwrap_err();
+        assert_eq!(err.downcast_ref::<VmError>(), Some(&VmError::Timeout));
+    }
+
+    #[test]
+    fn test_invocation_limit() {
+        let limits = ExecutionLimits { max_invocations: 1, ..ExecutionLimits::default() };
+        let mut vm = WasmVM::with_limits(1000000, limits);
+
+        let code = wasmer::wat2wasm(br#"(module (func (export "noop")))"#).unwrap().to_vec();
+        vm.deploy_contract("deployer", "c".to_string(), code).unwrap();
+
+        assert!(vm.call_contract("c", "noop", vec![]).is_ok());
+        let err = vm.call_contract("c", "noop", vec![]).unwrap_err();
+        assert_eq!(err.downcast_ref::<VmError>(), Some(&VmError::InvocationLimit));
+    }
+
+    #[test]
+    fn test_packed_i64_return_decodes_bytes() {
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
+
+        // "hello neonet" at offset 16: (16 << 32) | 12
+        let code = wasmer::wat2wasm(br#"
+            (module
+              (memory (export "memory") 1)
+              (data (i32.const 16) "hello neonet")
+              (func (export "greet") (result i64)
+                (i64.const 68719476748)))
+        "#).unwrap().to_vec();
+        vm.deploy_contract("deployer", "greeter".to_string(), code).unwrap();
+
+        let result = vm.call_contract("greeter", "greet", vec![]).unwrap();
+        assert_eq!(result, CallResult::Bytes(b"hello neonet".to_vec()));
+    }
+
+    #[test]
+    fn test_packed_return_past_memory_is_rejected() {
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
+
+        // offset 16, length u32::MAX: far past the single 64 KiB page
+        let code = wasmer::wat2wasm(br#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "greet") (result i64)
+                (i64.const 73014444031)))
+        "#).unwrap().to_vec();
+        vm.deploy_contract("deployer", "greeter".to_string(), code).unwrap();
+
+        let err = vm.call_contract("greeter", "greet", vec![]).unwrap_err();
+        assert_eq!(err.to_string(), "Returned buffer out of bounds");
+    }
+
+    #[test]
+    fn test_gas_remaining_host_function() {
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
+
+        let code = wasmer::wat2wasm(br#"
+            (module
+              (import "env" "gas_remaining" (func $gas_remaining (result i64)))
+              (func (export "gas") (result i32)
+                (i32.wrap_i64 (call $gas_remaining))))
+        "#).unwrap().to_vec();
+        vm.deploy_contract("deployer", "meter".to_string(), code).unwrap();
+
+        let result = vm.call_contract("meter", "gas", vec![]).unwrap();
+        // every charge up to the call: deploy, call, execute and invoke
+        let expected = 1000000 - vm.get_gas_used();
+        assert_eq!(result, CallResult::Text(format!("WASM execution result: {}", expected)));
+    }
+
+    #[test]
+    fn test_random_seed_is_deterministic_per_block() {
+        // store the low 32 bits of two draws under keys 1 and 2
+        let code = wasmer::wat2wasm(br#"
+            (module
+              (import "env" "random_seed" (func $random_seed (result i64)))
+              (import "env" "storage_set" (func $storage_set (param i32 i32)))
+              (func (export "draw") (result i32)
+                (call $storage_set (i32.const 1) (i32.wrap_i64 (call $random_seed)))
+                (call $storage_set (i32.const 2) (i32.wrap_i64 (call $random_seed)))
+                (i32.const 0)))
+        "#).unwrap().to_vec();
+        let run = |block_hash: [u8; 32]| {
+            let mut vm = WasmVM::new(1000000, GasSchedule::default());
+            vm.set_block_hash(block_hash);
+            vm.deploy_contract("deployer", "dice".to_string(), code.clone()).unwrap();
+            vm.call_contract("dice", "draw", vec![]).unwrap();
+            vm.get_contract("dice").unwrap().storage.clone()
+        };
+
+        let first = run([7; 32]);
+        assert_eq!(first, run([7; 32]));
+        assert_ne!(first, run([8; 32]));
+        let draw = |n: u64| (random_seed(&[7; 32], "dice", n) as i32).to_string();
+        assert_eq!(first.get("1"), Some(&draw(0)));
+        assert_eq!(first.get("2"), Some(&draw(1)));
+        assert_ne!(first.get("1"), first.get("2"));
+    }
+
+    #[test]
+    fn test_cross_call_storage_is_isolated() {
+        // A writes 10 under key 1, calls B (which writes 20 under the same key
+        // and returns what it reads back), then records what it sees itself
+        let caller = wasmer::wat2wasm(br#"
+            (module
+              (import "env" "storage_get" (func $storage_get (param i32) (result i32)))
+              (import "env" "storage_set" (func $storage_set (param i32 i32)))
+              (import "env" "call_contract" (func $call_contract (param i32 i32 i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 0) "b")
+              (data (i32.const 8) "write")
+              (func (export "run") (result i32)
+                (call $storage_set (i32.const 1) (i32.const 10))
+                (call $storage_set (i32.const 2)
+                  (call $call_contract (i32.const 0) (i32.const 1) (i32.const 8) (i32.const 5)))
+                (call $storage_set (i32.const 3) (call $storage_get (i32.const 1)))
+                (i32.const 0)))
+        "#).unwrap().to_vec();
+        let callee = wasmer::wat2wasm(br#"
+            (module
+              (import "env" "storage_get" (func $storage_get (param i32) (result i32)))
+              (import "env" "storage_set" (func $storage_set (param i32 i32)))
+              (func (export "write") (result i32)
+                (call $storage_set (i32.const 1) (i32.const 20))
+                (call $storage_get (i32.const 1))))
+        "#).unwrap().to_vec();
+
+        let mut vm = WasmVM::new(10_000_000, GasSchedule::default());
+        vm.deploy_contract("deployer", "a".to_string(), caller).unwrap();
+        vm.deploy_contract("deployer", "b".to_string(), callee).unwrap();
+        vm.set_storage("b", "1", "5".to_string()).unwrap();
+        let result = vm.call_contract("a", "run", vec![]).unwrap();
+        assert_eq!(result, CallResult::Text("WASM execution result: 0".to_string()));
+
+        let a = &vm.get_contract("a").unwrap().storage;
+        assert_eq!(a.get("1").map(String::as_str), Some("10"));
+        assert_eq!(a.get("2").map(String::as_str), Some("20"));
+        assert_eq!(a.get("3").map(String::as_str), Some("10"));
+        let b = &vm.get_contract("b").unwrap().storage;
+        assert_eq!(b.len(), 1);
+        assert_eq!(b.get("1").map(String::as_str), Some("20"));
+    }
+
+    #[test]
+    fn test_nested_calls_share_the_instruction_budget() {
+        // each call to `burn` runs ~6400 instructions: one fits in 10_000, two don't
+        let caller = wasmer::wat2wasm(br#"
+            (module
+              (import "env" "call_contract" (func $call_contract (param i32 i32 i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 0) "b")
+              (data (i32.const 8) "burn")
+              (func (export "once") (result i32)
+                (call $call_contract (i32.const 0) (i32.const 1) (i32.const 8) (i32.const 4)))
+              (func (export "twice") (result i32)
+                (drop (call $call_contract (i32.const 0) (i32.const 1) (i32.const 8) (i32.const 4)))
+                (call $call_contract (i32.const 0) (i32.const 1) (i32.const 8) (i32.const 4))))
+        "#).unwrap().to_vec();
+        let callee = wasmer::wat2wasm(br#"
+            (module
+              (func (export "burn") (result i32) (local $i i32)
+                (loop $l
+                  (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                  (br_if $l (i32.lt_u (local.get $i) (i32.const 800))))
+                (local.get $i)))
+        "#).unwrap().to_vec();
+
+        let limits = ExecutionLimits { max_instructions: 10_000, ..ExecutionLimits::default() };
+        let mut vm = WasmVM::with_limits(10_000_000, limits);
+        vm.deploy_contract("deployer", "a".to_string(), caller).unwrap();
+        vm.deploy_contract("deployer", "b".to_string(), callee).unwrap();
+
+        let result = vm.call_contract("a", "once", vec![]).unwrap();
+        assert_eq!(result, CallResult::Text("WASM execution result: 800".to_string()));
+        let err = vm.call_contract("a", "twice", vec![]).unwrap_err();
+        assert_eq!(err.downcast_ref::<VmError>(), Some(&VmError::Timeout));
+    }
+
+    #[test]
+    fn test_call_contract_rejects_out_of_bounds_names() {
+        let caller = wasmer::wat2wasm(br#"
+            (module
+              (import "env" "call_contract" (func $call_contract (param i32 i32 i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (func (export "run") (result i32)
+                (call $call_contract (i32.const 0) (i32.const -1) (i32.const 0) (i32.const 1))))
+        "#).unwrap().to_vec();
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
+        vm.deploy_contract("deployer", "a".to_string(), caller).unwrap();
+
+        let CallResult::Text(text) = vm.call_contract("a", "run", vec![]).unwrap() else { panic!("expected a text result") };
+        assert!(text.contains("call_contract argument out of bounds"), "{}", text);
+    }
+
+    #[test]
+    fn test_deploy_rejects_unsupported_imports() {
+        let mut vm = WasmVM::new(10_000_000, GasSchedule::default());
+        let code = wasmer::wat2wasm(br#"
+            (module
+                (import "env" "storage_get" (func (param i32) (result i32)))
+                (import "env" "foo" (func)))
+        "#).unwrap().to_vec();
+        let err = vm.deploy_contract("deployer", "c".to_string(), code).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported import: env.foo");
+        assert_eq!(vm.get_gas_used(), 0);
+
+        let other_module = wasmer::wat2wasm(br#"(module (import "wasi" "storage_get" (func (param i32) (result i32))))"#).unwrap().to_vec();
+        let err = vm.deploy_contract("deployer", "c".to_string(), other_module).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported import: wasi.storage_get");
+
+        let supported = wasmer::wat2wasm(br#"(module (import "env" "storage_set" (func (param i32 i32))))"#).unwrap().to_vec();
+        vm.deploy_contract("deployer", "c".to_string(), supported).unwrap();
+    }
+
+    #[test]
+    fn test_float_code_cannot_deploy_or_run() {
+        let mut vm = WasmVM::new(10_000_000, GasSchedule::default());
+        let code = wasmer::wat2wasm(br#"
+            (module
+              (func (export "half") (result i32)
+                (i32.trunc_f32_s (f32.div (f32.const 7) (f32.const 2)))))
+        "#).unwrap().to_vec();
+        let err = vm.deploy_contract("deployer", "f".to_string(), code.clone()).unwrap_err();
+        assert!(err.to_string().contains("floating-point"), "{}", err);
+        assert_eq!(vm.get_gas_used(), 0);
+
+        let local = wasmer::wat2wasm(br#"(module (func (local f64)))"#).unwrap().to_vec();
+        assert!(vm.deploy_contract("deployer", "f".to_string(), local).is_err());
+
+        // A float contract that predates the deploy check traps instead of computing 3
+        vm.contracts.insert("legacy".to_string(), WasmContract {
+            address: "legacy".to_string(),
+            code,
+            storage: BTreeMap::new(),
+            balance: 0,
+            abi: None,
+            owner: "deployer".to_string(),
+            operators: BTreeSet::new(),
+        });
+        match vm.call_contract("legacy", "half", vec![]).unwrap() {
+            CallResult::Text(text) => {
+                assert!(text.starts_with("WASM execution error"), "{}", text);
+                assert!(text.contains("unreachable"), "{}", text);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
     #[test]
     fn test_invalid_wasm() {
-        let mut vm = WasmVM::new(1000000);
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
         
         // Invalid WASM magic
         let bad_code = vec![0xFF, 0xFF, 0xFF, 0xFF];
-        let result = vm.deploy_contract("bad_contract".to_string(), bad_code);
+        let result = vm.deploy_contract("deployer", "bad_contract".to_string(), bad_code);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_truncated_module_is_rejected() {
+        let mut vm = WasmVM::new(1000000, GasSchedule::default());
+        // the magic number alone, and the magic plus half a version
+        for code in [vec![0x00, 0x61, 0x73, 0x6d], vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00]] {
+            let result = vm.deploy_contract("deployer", "truncated".to_string(), code);
+            assert!(result.is_err());
+        }
+        assert_eq!(vm.get_gas_used(), 0);
+        // with the full version the empty module deploys
+        let code = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        assert!(vm.deploy_contract("deployer", "minimal".to_string(), code).is_ok());
+    }
 }
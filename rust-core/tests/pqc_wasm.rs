@@ -0,0 +1,52 @@
+// Verification-only PQC build, as shipped to browsers:
+//   wasm-pack test --node -- --no-default-features --features wasm --test pqc_wasm
+// The same checks run natively with
+//   cargo test --no-default-features --features wasm --test pqc_wasm
+// Signing is compiled out on wasm32, so the vector in fixtures/ was produced by a native build.
+#![cfg(feature = "wasm")]
+
+use neonet_core::pqc::{
+    verify_dilithium, verify_ed25519, verify_hybrid_signature, HybridPublicKey, HybridSignature, ED25519_KAT,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Vector {
+    message: String,
+    public_key: HybridPublicKey,
+    signature: HybridSignature,
+}
+
+fn vector() -> (Vec<u8>, HybridPublicKey, HybridSignature) {
+    let v: Vector = serde_json::from_str(include_str!("fixtures/pqc_wasm_vector.json")).unwrap();
+    (hex::decode(v.message).unwrap(), v.public_key, v.signature)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn hybrid_signature_verifies() {
+    let (message, public_key, signature) = vector();
+    assert!(verify_hybrid_signature(&public_key, &message, &signature).unwrap());
+    assert!(!verify_hybrid_signature(&public_key, b"tampered", &signature).unwrap());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn halves_verify_separately() {
+    let (message, public_key, signature) = vector();
+    assert!(verify_ed25519(&public_key.ed25519_public, &message, &signature.ed25519_sig).unwrap());
+    assert!(verify_dilithium(&public_key.dilithium_public, &message, &signature.dilithium_sig).unwrap());
+
+    let mut dilithium_sig = signature.dilithium_sig.clone();
+    dilithium_sig[0] ^= 1;
+    assert!(!verify_dilithium(&public_key.dilithium_public, &message, &dilithium_sig).unwrap());
+    assert!(verify_dilithium(&public_key.dilithium_public, &message, &dilithium_sig[1..]).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn ed25519_known_answer() {
+    let public = hex::decode(ED25519_KAT.public).unwrap();
+    let signature = hex::decode(ED25519_KAT.signature).unwrap();
+    assert!(verify_ed25519(&public, ED25519_KAT.message, &signature).unwrap());
+}